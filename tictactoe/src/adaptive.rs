@@ -0,0 +1,141 @@
+use super::grid::Player;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AdaptiveStrength {
+    pub target_win_rate: f64,
+    pub window: usize,
+    pub step: u8,
+    pub streak_length: usize,
+}
+
+impl Default for AdaptiveStrength {
+    fn default() -> AdaptiveStrength {
+        AdaptiveStrength {
+            target_win_rate: 0.5,
+            window: 6,
+            step: 5,
+            streak_length: 3,
+        }
+    }
+}
+
+impl AdaptiveStrength {
+    // Ties count as half a win for the human, so a player drawing every game
+    // sits exactly on a 50% target.
+    pub fn human_score_rate(&self, results: &[Option<Player>]) -> Option<f64> {
+        let recent_results = &results[results.len().saturating_sub(self.window)..];
+        if recent_results.is_empty() {
+            return None;
+        }
+        let score: f64 = recent_results
+            .iter()
+            .map(|winner| match winner {
+                Some(Player::Human) => 1.0,
+                None => 0.5,
+                Some(Player::Cpu) => 0.0,
+            })
+            .sum();
+        Some(score / recent_results.len() as f64)
+    }
+
+    fn trailing_streak(&self, results: &[Option<Player>]) -> Option<Player> {
+        let last_winner = (*results.last()?)?;
+        let streak = results
+            .iter()
+            .rev()
+            .take_while(|winner| **winner == Some(last_winner))
+            .count();
+        if streak >= self.streak_length {
+            Some(last_winner)
+        } else {
+            None
+        }
+    }
+
+    pub fn next_strength(&self, current_strength: u8, results: &[Option<Player>]) -> u8 {
+        let Some(score_rate) = self.human_score_rate(results) else {
+            return current_strength;
+        };
+        let step = match self.trailing_streak(results) {
+            Some(_) => self.step.saturating_mul(2),
+            None => self.step,
+        };
+
+        if score_rate > self.target_win_rate {
+            current_strength.saturating_add(step).min(100)
+        } else if score_rate < self.target_win_rate {
+            current_strength.saturating_sub(step)
+        } else {
+            current_strength
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_strength_should_not_change_without_history() {
+        let adaptive = AdaptiveStrength::default();
+        assert_eq!(adaptive.next_strength(60, &[]), 60);
+    }
+
+    #[test]
+    fn next_strength_should_raise_strength_when_human_wins_too_often() {
+        let adaptive = AdaptiveStrength::default();
+        let results = [Some(Player::Human), Some(Player::Cpu), Some(Player::Human)];
+        assert_eq!(adaptive.next_strength(60, &results), 65);
+    }
+
+    #[test]
+    fn next_strength_should_lower_strength_when_human_loses_too_often() {
+        let adaptive = AdaptiveStrength::default();
+        let results = [
+            Some(Player::Cpu),
+            None,
+            Some(Player::Human),
+            Some(Player::Cpu),
+        ];
+        assert_eq!(adaptive.next_strength(60, &results), 55);
+    }
+
+    #[test]
+    fn next_strength_should_hold_when_on_target() {
+        let adaptive = AdaptiveStrength::default();
+        let results = [Some(Player::Cpu), Some(Player::Human), None, None];
+        assert_eq!(adaptive.next_strength(60, &results), 60);
+    }
+
+    #[test]
+    fn next_strength_should_move_faster_on_streaks() {
+        let adaptive = AdaptiveStrength::default();
+        let winning_streak = [Some(Player::Human); 3];
+        assert_eq!(adaptive.next_strength(60, &winning_streak), 70);
+
+        let losing_streak = [Some(Player::Cpu); 4];
+        assert_eq!(adaptive.next_strength(60, &losing_streak), 50);
+    }
+
+    #[test]
+    fn next_strength_should_stay_within_bounds() {
+        let adaptive = AdaptiveStrength::default();
+        assert_eq!(adaptive.next_strength(98, &[Some(Player::Human); 3]), 100);
+        assert_eq!(adaptive.next_strength(3, &[Some(Player::Cpu); 3]), 0);
+    }
+
+    #[test]
+    fn human_score_rate_should_only_consider_recent_window() {
+        let adaptive = AdaptiveStrength {
+            window: 2,
+            ..AdaptiveStrength::default()
+        };
+        let results = [
+            Some(Player::Cpu),
+            Some(Player::Cpu),
+            None,
+            Some(Player::Human),
+        ];
+        assert_eq!(adaptive.human_score_rate(&results), Some(0.75));
+    }
+}
@@ -0,0 +1,934 @@
+use super::clock::TimeoutRule;
+use super::cpu::{CpuProfile, GameRng, Personality};
+use super::editor;
+use super::engine::{EngineError, Opponent};
+use super::game::{GameConfig, Obstacles, RuleVariant};
+use super::grid::{Grid, GridChecker, Marker, Player, Topology};
+use super::vanishing;
+
+use std::time::Duration;
+
+pub const USAGE: &str = "Usage: tictactoe [OPTIONS]
+
+Without options the interactive main menu is shown.
+
+Game options:
+    --difficulty N      CPU strength between 0 and 100
+    --personality P     aggressive, defensive, chaotic or balanced
+    --marker M          marker played by the human, x or o
+    --first P           who opens the game, human or cpu
+    --size N            board size between 3 and 9
+    --variant V         standard, misere, phantom, quantum, sliding, notakto,
+                        order-chaos, numerical, gomoku or vanishing
+    --boards N          number of boards in a notakto game, 3 by default
+    --torus             winning lines wrap around the edges of the board
+    --blocked B         blocked cells: center, corners, or N random cells,
+                        N:SEED replays a random layout
+    --move-time S       seconds allowed for each move, at least 1
+    --game-time S       seconds on each player's clock for the whole game,
+                        at least 1
+    --on-timeout R      loss or random, what happens when time runs out
+    --tutor             explain every CPU move
+    --position TEXT     start from X.O/.X./O.. notation or an array like [[1,0,-1],...]
+    --to-move P         who moves next in that position, human or cpu
+    --seed N            seed for the CPU random choices
+    --engine CMD        let an external engine play instead of the CPU
+    --time-limit MS     time an engine gets for each move, 1000 by default
+
+Headless modes:
+    --moves 5,1,9       play the human moves in order, the CPU answers
+    --games N           play N games between two bots
+    --bot P[:N]         bot personality and strength, given twice with --games
+    --bot engine:CMD    external engine used as a bot
+    --as-engine         answer the engine protocol on stdin and stdout
+    --serve ADDR        serve the HTTP JSON API, for example 127.0.0.1:8080,
+                        a bare port listens on 127.0.0.1 only
+    --cors ORIGIN       let web pages from ORIGIN call the API, off by default
+    --lobby ADDR        host the multi-client lobby, for example 127.0.0.1:7878
+    --help              show this message
+";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BotSpec {
+    Cpu(CpuProfile),
+    Engine(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Menu,
+    Play(GameConfig),
+    Script {
+        config: GameConfig,
+        moves: Vec<usize>,
+    },
+    Simulate {
+        config: GameConfig,
+        games: usize,
+        bots: [BotSpec; 2],
+    },
+    Engine(GameConfig),
+    Serve {
+        address: String,
+        cors: Option<String>,
+    },
+    Lobby(String),
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliOptions {
+    pub command: Command,
+    pub seed: Option<u64>,
+    pub engine: Option<String>,
+    pub time_limit: Duration,
+    pub position: Option<Grid>,
+}
+
+fn parse_bot(spec: &str) -> Result<BotSpec, String> {
+    if let Some(command) = spec.strip_prefix("engine:") {
+        return Ok(BotSpec::Engine(command.to_owned()));
+    }
+    let (name, strength) = spec.split_once(':').unwrap_or((spec, "100"));
+    let personality =
+        Personality::from_name(name).ok_or_else(|| format!("unknown bot personality: {}", name))?;
+    let strength = strength
+        .parse::<u8>()
+        .ok()
+        .filter(|strength| *strength <= 100)
+        .ok_or_else(|| format!("bot strength must be between 0 and 100: {}", strength))?;
+    Ok(BotSpec::Cpu(CpuProfile::with_strength(
+        personality,
+        strength,
+    )))
+}
+
+fn parse_moves(value: &str) -> Result<Vec<usize>, String> {
+    value
+        .split(|glyph: char| glyph == ',' || glyph.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .map(|token| {
+            token
+                .parse::<usize>()
+                .map_err(|_| format!("invalid move: {}", token))
+        })
+        .collect()
+}
+
+// A clock of zero seconds would run out before the first move.
+fn parse_seconds(value: &str) -> Option<Duration> {
+    value
+        .parse()
+        .ok()
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+}
+
+// A random obstacle layout, `N` cells or `N:SEED` to pin the layout.
+fn parse_random_blocks(value: &str) -> Option<(usize, Option<u64>)> {
+    let (count, seed) = match value.split_once(':') {
+        Some((count, seed)) => (count, Some(seed.trim().parse().ok()?)),
+        None => (value, None),
+    };
+    let count = count.trim().parse().ok().filter(|count| *count > 0)?;
+    Some((count, seed))
+}
+
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<CliOptions, String> {
+    let mut args = args.into_iter();
+    let mut config = GameConfig::default();
+    let mut strength = config.profile.strength();
+    let mut seed = None;
+    let mut engine = None;
+    let mut time_limit = Duration::from_millis(1000);
+    let mut as_engine = false;
+    let mut serve = None;
+    let mut cors = None;
+    let mut lobby = None;
+    let mut moves = None;
+    let mut games = None;
+    let mut bots = Vec::new();
+    let mut game_options = false;
+    let mut size_given = false;
+    let mut position = None;
+    let mut to_move = None;
+    let mut random_blocks = None;
+
+    while let Some(flag) = args.next() {
+        if flag == "--help" || flag == "-h" {
+            return Ok(CliOptions {
+                command: Command::Help,
+                seed,
+                engine,
+                time_limit,
+                position: None,
+            });
+        }
+        if flag == "--tutor" {
+            config.tutor = true;
+            game_options = true;
+            continue;
+        }
+        if flag == "--torus" {
+            config.topology = Topology::Torus;
+            game_options = true;
+            continue;
+        }
+        if flag == "--as-engine" {
+            as_engine = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", flag))?;
+        let invalid = || format!("invalid value for {}: {}", flag, value);
+        match flag.as_str() {
+            "--difficulty" => {
+                strength = value
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|strength| *strength <= 100)
+                    .ok_or_else(invalid)?
+            }
+            "--personality" => {
+                config.profile.personality = Personality::from_name(&value).ok_or_else(invalid)?
+            }
+            "--marker" => {
+                config.human_marker = match value.to_lowercase().as_str() {
+                    "x" => Marker::X,
+                    "o" => Marker::O,
+                    _ => return Err(invalid()),
+                }
+            }
+            "--first" => {
+                config.first_player = match value.to_lowercase().as_str() {
+                    "human" => Player::Human,
+                    "cpu" => Player::Cpu,
+                    _ => return Err(invalid()),
+                }
+            }
+            "--size" => {
+                config.size = value
+                    .parse()
+                    .ok()
+                    .filter(|size| (3..=9).contains(size))
+                    .ok_or_else(invalid)?;
+                size_given = true;
+            }
+            "--variant" => config.variant = RuleVariant::from_name(&value).ok_or_else(invalid)?,
+            "--boards" => {
+                config.boards = value
+                    .parse()
+                    .ok()
+                    .filter(|boards| (1..=9).contains(boards))
+                    .ok_or_else(invalid)?
+            }
+            "--blocked" => match Obstacles::from_name(&value) {
+                Some(obstacles) => config.obstacles = obstacles,
+                None => random_blocks = Some(parse_random_blocks(&value).ok_or_else(invalid)?),
+            },
+            "--move-time" => {
+                config.time_control.per_move = Some(parse_seconds(&value).ok_or_else(invalid)?)
+            }
+            "--game-time" => {
+                config.time_control.per_game = Some(parse_seconds(&value).ok_or_else(invalid)?)
+            }
+            "--on-timeout" => {
+                config.time_control.on_timeout =
+                    TimeoutRule::from_name(&value).ok_or_else(invalid)?
+            }
+            "--seed" => {
+                seed = Some(value.parse().map_err(|_| invalid())?);
+                continue;
+            }
+            "--time-limit" => {
+                time_limit = Duration::from_millis(value.parse().map_err(|_| invalid())?);
+                continue;
+            }
+            "--engine" => engine = Some(value.clone()),
+            "--serve" => {
+                serve = Some(match value.parse::<u16>() {
+                    Ok(port) => format!("127.0.0.1:{}", port),
+                    Err(_) => value.clone(),
+                });
+                continue;
+            }
+            "--cors" => {
+                cors = Some(value.clone());
+                continue;
+            }
+            "--lobby" => {
+                lobby = Some(value.clone());
+                continue;
+            }
+            "--position" => position = Some(editor::parse_position_text(&value)?),
+            "--to-move" => {
+                to_move = match value.to_lowercase().as_str() {
+                    "human" => Some(Player::Human),
+                    "cpu" => Some(Player::Cpu),
+                    _ => return Err(invalid()),
+                }
+            }
+            "--moves" => moves = Some(parse_moves(&value)?),
+            "--games" => games = Some(value.parse().map_err(|_| invalid())?),
+            "--bot" => bots.push(parse_bot(&value)?),
+            _ => return Err(format!("unknown option: {}", flag)),
+        }
+        game_options = true;
+    }
+    config.profile = CpuProfile::with_strength(config.profile.personality, strength);
+    if let Some((count, layout_seed)) = random_blocks {
+        config.obstacles = Obstacles::Random {
+            count,
+            seed: layout_seed.or(seed).unwrap_or_else(rand::random),
+        };
+    }
+    match &position {
+        Some(grid) => {
+            let to_move = to_move.unwrap_or_else(|| config.player_to_move(grid));
+            config = editor::setup_config(&config, grid, to_move)
+                .map_err(|error| format!("invalid --position: {}", error))?;
+        }
+        None if to_move.is_some() => return Err("--to-move needs --position".to_owned()),
+        None => (),
+    }
+    if position.is_some() && (moves.is_some() || games.is_some() || as_engine) {
+        return Err("--position only applies to interactive games".to_owned());
+    }
+    match config.variant.fixed_size() {
+        Some(size) if size_given && config.size != size => {
+            let board = match size {
+                0 => "an edgeless board".to_owned(),
+                size => format!("a {}x{} board", size, size),
+            };
+            return Err(format!(
+                "the {} variant is played on {}",
+                config.variant.name(),
+                board
+            ));
+        }
+        _ => (),
+    }
+    // Only the classic game loop runs the clocks and the tutor.
+    let timed = config.time_control.per_move.is_some() || config.time_control.per_game.is_some();
+    if (timed || config.tutor) && !config.variant.is_grid_game() {
+        return Err(format!(
+            "the {} variant does not support --move-time, --game-time or --tutor",
+            config.variant.name()
+        ));
+    }
+    let board_options = config.topology != Topology::Flat || config.obstacles != Obstacles::None;
+    if board_options && !config.variant.uses_config_board() {
+        return Err(format!(
+            "the {} variant does not support --torus or --blocked",
+            config.variant.name()
+        ));
+    }
+    let uses_engine =
+        as_engine || engine.is_some() || bots.iter().any(|bot| matches!(bot, BotSpec::Engine(_)));
+    if board_options && uses_engine {
+        return Err("--torus and --blocked are not part of the engine protocol".to_owned());
+    }
+    if position.is_some() && config.obstacles != Obstacles::None {
+        return Err("--position lays out the board itself, mark blocked cells with #".to_owned());
+    }
+    let free_cells = config.size * config.size - config.obstacles.cells(config.size).len();
+    if free_cells * 2 < config.size * config.size {
+        return Err("--blocked cannot cover more than half of the board".to_owned());
+    }
+    // Both sides' pieces must fit with a cell to spare.
+    let pieces = match config.variant {
+        RuleVariant::Sliding => Some(2 * config.size),
+        RuleVariant::Vanishing => Some(2 * vanishing::MAX_MARKERS),
+        _ => None,
+    };
+    if let Some(pieces) = pieces.filter(|pieces| free_cells <= *pieces) {
+        return Err(format!(
+            "the {} variant needs more than {} free cells",
+            config.variant.name(),
+            pieces
+        ));
+    }
+    let position = position.map(|grid| grid.with_lines(config.variant.lines(&config)));
+    if !config.variant.is_grid_game()
+        && (moves.is_some()
+            || games.is_some()
+            || as_engine
+            || engine.is_some()
+            || position.is_some())
+    {
+        return Err(format!(
+            "the {} variant is only playable interactively against the built-in CPU",
+            config.variant.name()
+        ));
+    }
+
+    let command = match (moves, games) {
+        _ if serve.is_some() && (as_engine || game_options) => {
+            return Err("--serve takes its game options from each request".to_owned())
+        }
+        _ if cors.is_some() && serve.is_none() => {
+            return Err("--cors only applies to --serve".to_owned())
+        }
+        _ if serve.is_some() => Command::Serve {
+            address: serve.unwrap_or_default(),
+            cors,
+        },
+        _ if lobby.is_some() && (as_engine || game_options) => {
+            return Err("--lobby lets each table choose its own options".to_owned())
+        }
+        _ if lobby.is_some() => Command::Lobby(lobby.unwrap_or_default()),
+        _ if as_engine && (engine.is_some() || !bots.is_empty()) => {
+            return Err("--as-engine cannot use another engine or bots".to_owned())
+        }
+        (None, None) if as_engine => Command::Engine(config),
+        _ if as_engine => {
+            return Err("--as-engine cannot be combined with --moves or --games".to_owned())
+        }
+        (Some(_), Some(_)) => return Err("--moves and --games cannot be combined".to_owned()),
+        (Some(moves), None) => Command::Script { config, moves },
+        (None, Some(games)) => match <[BotSpec; 2]>::try_from(bots) {
+            Ok(bots) => Command::Simulate {
+                config,
+                games,
+                bots,
+            },
+            Err(_) => return Err("--games needs exactly two --bot options".to_owned()),
+        },
+        (None, None) if !bots.is_empty() => return Err("--bot needs --games".to_owned()),
+        (None, None) if game_options => Command::Play(config),
+        (None, None) => Command::Menu,
+    };
+    Ok(CliOptions {
+        command,
+        seed,
+        engine,
+        time_limit,
+        position,
+    })
+}
+
+fn format_moves(moves: &[usize]) -> String {
+    moves
+        .iter()
+        .map(|number| number.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn format_winner(winner: Option<Player>, labels: [&str; 2]) -> &str {
+    match winner {
+        Some(Player::Human) => labels[0],
+        Some(Player::Cpu) => labels[1],
+        None => "tie",
+    }
+}
+
+fn format_forfeit(error: &Option<EngineError>) -> String {
+    match error {
+        Some(error) => format!(" forfeit={}", error.code()),
+        None => String::new(),
+    }
+}
+
+fn play_number(grid: &mut Grid, marker: Marker, number: usize) -> Option<(i32, i32)> {
+    let position = *grid.extract_empty_positions().get(&number)?;
+    grid.insert(position, marker);
+    Some(position)
+}
+
+pub fn run_script(
+    config: &GameConfig,
+    moves: &[usize],
+    opponent: &mut Opponent,
+    rng: &mut GameRng,
+) -> Result<String, String> {
+    let mut grid = config.new_grid();
+    let mut scripted_moves = moves.iter().enumerate();
+    let mut played = Vec::new();
+    let mut forfeit = None;
+
+    while !config.is_over(&grid) {
+        match config.player_to_move(&grid) {
+            Player::Human => {
+                let Some((index, number)) = scripted_moves.next() else {
+                    break;
+                };
+                if play_number(&mut grid, config.marker_of(Player::Human), *number).is_none() {
+                    return Err(format!(
+                        "status=error reason=unavailable move={} index={} board={}",
+                        number,
+                        index + 1,
+                        grid.to_notation()
+                    ));
+                }
+                played.push(*number);
+            }
+            Player::Cpu => match opponent.play(config, &grid, Player::Cpu, rng) {
+                Ok((new_grid, cpu_move)) => {
+                    grid = new_grid;
+                    played.push(grid.position_number(cpu_move.position));
+                }
+                Err(error) => {
+                    forfeit = Some(error);
+                    break;
+                }
+            },
+        }
+    }
+
+    if forfeit.is_some() {
+        return Ok(format!(
+            "status=finished winner=human{} moves={} board={}",
+            format_forfeit(&forfeit),
+            format_moves(&played),
+            grid.to_notation()
+        ));
+    }
+    if let Some((index, number)) = scripted_moves.next() {
+        return Err(format!(
+            "status=error reason=game_over move={} index={} board={}",
+            number,
+            index + 1,
+            grid.to_notation()
+        ));
+    }
+
+    let status = if config.is_over(&grid) {
+        format!(
+            "status=finished winner={}",
+            format_winner(config.winner(&grid), ["human", "cpu"])
+        )
+    } else {
+        let to_move = match config.player_to_move(&grid) {
+            Player::Human => "human",
+            Player::Cpu => "cpu",
+        };
+        format!("status=unfinished to_move={}", to_move)
+    };
+    Ok(format!(
+        "{} moves={} board={}",
+        status,
+        format_moves(&played),
+        grid.to_notation()
+    ))
+}
+
+// The first bot takes the human seat of the configuration, so --marker and
+// --first decide which bot plays which side.
+pub fn run_simulation(
+    config: &GameConfig,
+    games: usize,
+    bots: &mut [Opponent; 2],
+    rng: &mut GameRng,
+) -> String {
+    let mut lines = Vec::new();
+    let mut wins = [0, 0, 0];
+
+    for game in 1..=games {
+        let mut grid = config.new_grid();
+        let mut played = Vec::new();
+        let mut forfeit = None;
+        while !config.is_over(&grid) {
+            let player = config.player_to_move(&grid);
+            let bot = match player {
+                Player::Human => &mut bots[0],
+                Player::Cpu => &mut bots[1],
+            };
+            match bot.play(config, &grid, player, rng) {
+                Ok((new_grid, cpu_move)) => {
+                    grid = new_grid;
+                    played.push(grid.position_number(cpu_move.position));
+                }
+                Err(error) => {
+                    forfeit = Some((player, error));
+                    break;
+                }
+            }
+        }
+
+        let winner = match &forfeit {
+            Some((player, _)) => Some(player.opponent()),
+            None => config.winner(&grid),
+        };
+        let forfeit = forfeit.map(|(_, error)| error);
+        wins[match winner {
+            Some(Player::Human) => 0,
+            Some(Player::Cpu) => 1,
+            None => 2,
+        }] += 1;
+        lines.push(format!(
+            "game={} winner={}{} moves={} board={}",
+            game,
+            format_winner(winner, ["a", "b"]),
+            format_forfeit(&forfeit),
+            format_moves(&played),
+            grid.to_notation()
+        ));
+    }
+
+    lines.push(format!(
+        "summary games={} a={} b={} tie={}",
+        games, wins[0], wins[1], wins[2]
+    ));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::seeded_rng;
+    use super::super::engine::{EngineProcess, FakeEngine};
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_owned()).collect()
+    }
+
+    #[test]
+    fn parse_args_should_show_menu_without_arguments() {
+        let options = parse_args(args("")).unwrap();
+        assert_eq!(options.command, Command::Menu);
+        assert_eq!(options.seed, None);
+        assert_eq!(parse_args(args("--seed 12")).unwrap().seed, Some(12));
+        assert_eq!(
+            parse_args(args("--seed 12")).unwrap().command,
+            Command::Menu
+        );
+    }
+
+    #[test]
+    fn parse_args_should_build_game_config() {
+        let options = parse_args(args(
+            "--difficulty 40 --personality chaotic --marker o --first cpu --size 4 --variant misere",
+        ))
+        .unwrap();
+        let Command::Play(config) = options.command else {
+            panic!("expected a direct game");
+        };
+        assert_eq!(
+            config.profile,
+            CpuProfile::with_strength(Personality::Chaotic, 40)
+        );
+        assert_eq!(config.human_marker, Marker::O);
+        assert_eq!(config.first_player, Player::Cpu);
+        assert_eq!(config.size, 4);
+        assert_eq!(config.variant, RuleVariant::Misere);
+        assert!(!config.tutor);
+
+        let options = parse_args(args("--tutor")).unwrap();
+        assert!(matches!(options.command, Command::Play(config) if config.tutor));
+    }
+
+    #[test]
+    fn parse_args_should_reject_invalid_values() {
+        assert!(parse_args(args("--difficulty 101")).is_err());
+        assert!(parse_args(args("--marker z")).is_err());
+        assert!(parse_args(args("--size 2")).is_err());
+        assert!(parse_args(args("--size")).is_err());
+        assert!(parse_args(args("--colour red")).is_err());
+        assert!(parse_args(args("--moves 1,x")).is_err());
+        assert!(parse_args(args("--games 3 --bot aggressive")).is_err());
+        assert!(parse_args(args("--bot chaotic:120 --bot defensive --games 2")).is_err());
+        assert!(parse_args(args("--moves 1 --games 2 --bot a --bot d")).is_err());
+    }
+
+    #[test]
+    fn parse_args_should_read_moves_and_bots() {
+        let options = parse_args(args("--moves 5,1,9")).unwrap();
+        assert_eq!(
+            options.command,
+            Command::Script {
+                config: GameConfig::default(),
+                moves: vec![5, 1, 9]
+            }
+        );
+
+        let options = parse_args(args("--games 3 --bot aggressive:70 --bot c")).unwrap();
+        assert_eq!(
+            options.command,
+            Command::Simulate {
+                config: GameConfig::default(),
+                games: 3,
+                bots: [
+                    BotSpec::Cpu(CpuProfile::with_strength(Personality::Aggressive, 70)),
+                    BotSpec::Cpu(CpuProfile::with_strength(Personality::Chaotic, 100))
+                ]
+            }
+        );
+
+        let options = parse_args(args("--games 1 --bot engine:./bot --bot d")).unwrap();
+        let Command::Simulate { bots, .. } = options.command else {
+            panic!("expected a simulation");
+        };
+        assert_eq!(bots[0], BotSpec::Engine("./bot".to_owned()));
+    }
+
+    #[test]
+    fn parse_args_should_read_engine_options() {
+        let options = parse_args(args("--engine ./bot --time-limit 250")).unwrap();
+        assert_eq!(options.engine, Some("./bot".to_owned()));
+        assert_eq!(options.time_limit, Duration::from_millis(250));
+        assert!(matches!(options.command, Command::Play(_)));
+
+        let options = parse_args(args("--as-engine --personality chaotic")).unwrap();
+        let Command::Engine(config) = options.command else {
+            panic!("expected engine mode");
+        };
+        assert_eq!(config.profile.personality, Personality::Chaotic);
+
+        assert!(parse_args(args("--as-engine --engine ./bot")).is_err());
+        assert!(parse_args(args("--as-engine --moves 5")).is_err());
+        assert!(parse_args(args("--time-limit soon")).is_err());
+    }
+
+    #[test]
+    fn parse_args_should_set_up_a_starting_position() {
+        let options = parse_args(args("--position X.../.O../..../.... --to-move cpu")).unwrap();
+        let Command::Play(config) = options.command else {
+            panic!("expected a direct game");
+        };
+        let grid = options.position.unwrap();
+        assert_eq!(config.size, 4);
+        assert_eq!(config.player_to_move(&grid), Player::Cpu);
+
+        let options = parse_args(args("--position [[1,0,0],[0,0,0],[0,0,1]]"));
+        assert!(options.unwrap_err().starts_with("invalid --position: "));
+        assert!(parse_args(args("--to-move cpu")).is_err());
+        assert!(parse_args(args("--position X../.O./... --moves 5")).is_err());
+    }
+
+    #[test]
+    fn parse_args_should_keep_special_variants_interactive() {
+        let options = parse_args(args("--variant phantom")).unwrap();
+        assert!(matches!(
+            options.command,
+            Command::Play(GameConfig {
+                variant: RuleVariant::Phantom,
+                ..
+            })
+        ));
+        assert!(parse_args(args("--variant quantum --size 3")).is_ok());
+        assert_eq!(
+            parse_args(args("--variant gomoku --size 9")).unwrap_err(),
+            "the gomoku variant is played on an edgeless board"
+        );
+        for line in [
+            "--variant phantom --moves 5",
+            "--variant phantom --as-engine",
+            "--variant phantom --engine ./bot",
+            "--variant phantom --position X../.O./...",
+            "--variant phantom --size 5",
+            "--variant quantum --games 3",
+            "--variant quantum --size 4",
+            "--variant sliding --size 5",
+            "--variant sliding --as-engine",
+            "--variant notakto --boards 0",
+            "--variant notakto --size 4",
+            "--variant order-chaos --moves 1",
+            "--variant numerical --size 4",
+            "--variant gomoku --games 2",
+            "--variant vanishing --size 4",
+            "--variant order-chaos --size 5",
+            "--variant gomoku --size 9",
+            "--variant sliding --tutor",
+            "--variant gomoku --move-time 5",
+            "--variant quantum --game-time 60",
+        ] {
+            assert!(parse_args(args(line)).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parse_args_should_read_board_options() {
+        let options = parse_args(args("--torus --blocked corners --size 4")).unwrap();
+        let Command::Play(config) = options.command else {
+            panic!("expected a game");
+        };
+        assert_eq!(config.topology, Topology::Torus);
+        assert_eq!(config.obstacles, Obstacles::Corners);
+
+        let options = parse_args(args("--blocked 2 --seed 9")).unwrap();
+        let Command::Play(config) = options.command else {
+            panic!("expected a game");
+        };
+        assert_eq!(config.obstacles, Obstacles::Random { count: 2, seed: 9 });
+        let options = parse_args(args("--seed 9 --blocked 3:4")).unwrap();
+        let Command::Play(config) = options.command else {
+            panic!("expected a game");
+        };
+        assert_eq!(config.obstacles, Obstacles::Random { count: 3, seed: 4 });
+
+        for line in [
+            "--blocked walls",
+            "--blocked 0",
+            "--blocked 5",
+            "--blocked 2:x",
+            "--torus --variant gomoku",
+            "--blocked center --variant quantum",
+            "--torus --as-engine",
+            "--blocked center --engine ./bot",
+            "--blocked center --position X../.O./...",
+            "--blocked corners --variant sliding",
+            "--blocked corners --variant vanishing",
+            "--blocked 3:1 --variant vanishing",
+        ] {
+            assert!(parse_args(args(line)).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parse_args_should_read_time_controls() {
+        let options =
+            parse_args(args("--move-time 10 --game-time 60 --on-timeout random")).unwrap();
+        let Command::Play(config) = options.command else {
+            panic!("expected a game");
+        };
+        assert_eq!(config.time_control.per_move, Some(Duration::from_secs(10)));
+        assert_eq!(config.time_control.per_game, Some(Duration::from_secs(60)));
+        assert_eq!(config.time_control.on_timeout, TimeoutRule::RandomMove);
+
+        assert!(parse_args(args("--move-time fast")).is_err());
+        assert!(parse_args(args("--move-time 0")).is_err());
+        assert!(parse_args(args("--game-time 0")).is_err());
+        assert!(parse_args(args("--on-timeout draw")).is_err());
+    }
+
+    #[test]
+    fn parse_args_should_read_serve_address() {
+        let options = parse_args(args("--serve 0.0.0.0:8080")).unwrap();
+        assert_eq!(
+            options.command,
+            Command::Serve {
+                address: "0.0.0.0:8080".to_owned(),
+                cors: None
+            }
+        );
+        let options = parse_args(args("--serve 8080 --cors https://example.org")).unwrap();
+        assert_eq!(
+            options.command,
+            Command::Serve {
+                address: "127.0.0.1:8080".to_owned(),
+                cors: Some("https://example.org".to_owned())
+            }
+        );
+        assert!(parse_args(args("--cors *")).is_err());
+        assert!(parse_args(args("--serve 127.0.0.1:8080 --size 4")).is_err());
+    }
+
+    #[test]
+    fn parse_args_should_read_lobby_address() {
+        let options = parse_args(args("--lobby 127.0.0.1:7878")).unwrap();
+        assert_eq!(options.command, Command::Lobby("127.0.0.1:7878".to_owned()));
+        assert!(parse_args(args("--lobby 127.0.0.1:7878 --marker o")).is_err());
+    }
+
+    #[test]
+    fn run_script_should_report_unfinished_games() {
+        let config = GameConfig::default();
+        let output = run_script(
+            &config,
+            &[5],
+            &mut Opponent::Cpu(config.profile),
+            &mut seeded_rng(0),
+        )
+        .unwrap();
+        assert!(output.starts_with("status=unfinished to_move=human moves=5,"));
+    }
+
+    #[test]
+    fn run_script_should_finish_a_game_against_a_full_strength_cpu() {
+        let config = GameConfig {
+            profile: CpuProfile::with_strength(Personality::Defensive, 100),
+            ..GameConfig::default()
+        };
+        let mut output = Err(String::new());
+        let mut moves = Vec::new();
+        for number in 1..=9 {
+            moves.push(number);
+            output = run_script(
+                &config,
+                &moves,
+                &mut Opponent::Cpu(config.profile),
+                &mut seeded_rng(0),
+            );
+            if let Err(error) = &output {
+                assert!(error.starts_with("status=error reason="));
+                moves.pop();
+            } else if output.as_ref().unwrap().starts_with("status=finished") {
+                break;
+            }
+        }
+        let output = output.unwrap();
+        assert!(output.starts_with("status=finished winner="));
+        assert!(!output.contains("winner=human"));
+    }
+
+    #[test]
+    fn run_script_should_reject_taken_positions() {
+        let config = GameConfig {
+            first_player: Player::Cpu,
+            profile: CpuProfile::with_strength(Personality::Defensive, 100),
+            ..GameConfig::default()
+        };
+        let error = run_script(
+            &config,
+            &[5],
+            &mut Opponent::Cpu(config.profile),
+            &mut seeded_rng(0),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "status=error reason=unavailable move=5 index=1 board=.../.O./..."
+        );
+    }
+
+    #[test]
+    fn run_simulation_should_report_each_game_and_a_summary() {
+        let mut bots = [
+            Opponent::Cpu(CpuProfile::with_strength(Personality::Aggressive, 100)),
+            Opponent::Cpu(CpuProfile::with_strength(Personality::Defensive, 100)),
+        ];
+        let output = run_simulation(&GameConfig::default(), 4, &mut bots, &mut seeded_rng(5));
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("game=1 winner=tie moves="));
+        assert_eq!(lines[4], "summary games=4 a=0 b=0 tie=4");
+    }
+
+    #[test]
+    fn run_simulation_should_be_reproducible_with_same_seed() {
+        let bots = || {
+            [
+                Opponent::Cpu(CpuProfile::with_strength(Personality::Chaotic, 50)),
+                Opponent::Cpu(CpuProfile::with_strength(Personality::Aggressive, 30)),
+            ]
+        };
+        let config = GameConfig {
+            size: 4,
+            ..GameConfig::default()
+        };
+        assert_eq!(
+            run_simulation(&config, 3, &mut bots(), &mut seeded_rng(9)),
+            run_simulation(&config, 3, &mut bots(), &mut seeded_rng(9))
+        );
+    }
+
+    #[test]
+    fn run_simulation_should_report_engine_forfeits() {
+        let fake = FakeEngine::new(
+            "cli_mute",
+            "read line\necho '{\"type\":\"ready\",\"name\":\"mute\"}'\nsleep 1\n",
+        );
+        let engine = EngineProcess::spawn(&fake.command(), Duration::from_millis(20)).unwrap();
+        let mut bots = [
+            Opponent::Cpu(CpuProfile::with_strength(Personality::Defensive, 100)),
+            Opponent::Engine(engine),
+        ];
+
+        let output = run_simulation(&GameConfig::default(), 1, &mut bots, &mut seeded_rng(0));
+
+        assert!(output.starts_with("game=1 winner=a forfeit=timeout moves="));
+        assert!(output.ends_with("summary games=1 a=1 b=0 tie=0"));
+    }
+}
@@ -0,0 +1,192 @@
+use super::grid::Player;
+
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum TimeoutRule {
+    #[default]
+    Loss,
+    RandomMove,
+}
+
+impl TimeoutRule {
+    pub fn from_name(name: &str) -> Option<TimeoutRule> {
+        match name.trim().to_lowercase().as_str() {
+            "loss" | "lose" => Some(TimeoutRule::Loss),
+            "random" => Some(TimeoutRule::RandomMove),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct TimeControl {
+    pub per_move: Option<Duration>,
+    pub per_game: Option<Duration>,
+    pub on_timeout: TimeoutRule,
+}
+
+impl TimeControl {
+    pub fn is_unlimited(&self) -> bool {
+        self.per_move.is_none() && self.per_game.is_none()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ClockRecord {
+    pub human: Duration,
+    pub cpu: Duration,
+    pub flagged: Option<Player>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    control: TimeControl,
+    used: [Duration; 2],
+    flagged: Option<Player>,
+}
+
+fn index(player: Player) -> usize {
+    match player {
+        Player::Human => 0,
+        Player::Cpu => 1,
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        Clock {
+            control,
+            used: [Duration::ZERO; 2],
+            flagged: None,
+        }
+    }
+
+    pub fn remaining(&self, player: Player) -> Option<Duration> {
+        self.control
+            .per_game
+            .map(|per_game| per_game.saturating_sub(self.used[index(player)]))
+    }
+
+    // Time the player may spend on the next move, None when unlimited.
+    pub fn budget(&self, player: Player) -> Option<Duration> {
+        match (self.control.per_move, self.remaining(player)) {
+            (Some(per_move), Some(remaining)) => Some(per_move.min(remaining)),
+            (per_move, remaining) => per_move.or(remaining),
+        }
+    }
+
+    // Returns false when the move took longer than its budget.
+    pub fn charge(&mut self, player: Player, elapsed: Duration) -> bool {
+        let budget = self.budget(player);
+        let within_budget = budget.is_none_or(|budget| elapsed <= budget);
+        self.used[index(player)] += budget.map_or(elapsed, |budget| elapsed.min(budget));
+        if !within_budget {
+            self.flagged.get_or_insert(player);
+        }
+        within_budget
+    }
+
+    // Charges the whole budget, for a player who never answered in time.
+    pub fn flag(&mut self, player: Player) {
+        let budget = self.budget(player).unwrap_or_default();
+        self.used[index(player)] += budget;
+        self.flagged.get_or_insert(player);
+    }
+
+    pub fn status(&self) -> Option<String> {
+        if self.control.is_unlimited() {
+            return None;
+        }
+        let describe = |player| self.budget(player).map_or("-".to_owned(), format_duration);
+        Some(format!(
+            "Time left: you {} | CPU {}",
+            describe(Player::Human),
+            describe(Player::Cpu)
+        ))
+    }
+
+    pub fn record(&self) -> Option<ClockRecord> {
+        if self.control.is_unlimited() {
+            return None;
+        }
+        Some(ClockRecord {
+            human: self.used[index(Player::Human)],
+            cpu: self.used[index(Player::Cpu)],
+            flagged: self.flagged,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn timeout_rule_should_parse_names() {
+        assert_eq!(TimeoutRule::from_name("Loss"), Some(TimeoutRule::Loss));
+        assert_eq!(
+            TimeoutRule::from_name("random"),
+            Some(TimeoutRule::RandomMove)
+        );
+        assert_eq!(TimeoutRule::from_name("draw"), None);
+    }
+
+    #[test]
+    fn unlimited_clock_should_never_flag_or_record() {
+        let mut clock = Clock::new(TimeControl::default());
+        assert_eq!(clock.budget(Player::Human), None);
+        assert!(clock.charge(Player::Human, seconds(3600)));
+        assert_eq!(clock.status(), None);
+        assert_eq!(clock.record(), None);
+    }
+
+    #[test]
+    fn budget_should_be_the_tighter_of_move_and_game_limits() {
+        let mut clock = Clock::new(TimeControl {
+            per_move: Some(seconds(10)),
+            per_game: Some(seconds(15)),
+            ..TimeControl::default()
+        });
+        assert_eq!(clock.budget(Player::Human), Some(seconds(10)));
+        assert!(clock.charge(Player::Human, seconds(8)));
+        assert_eq!(clock.budget(Player::Human), Some(seconds(7)));
+        assert_eq!(clock.budget(Player::Cpu), Some(seconds(10)));
+        assert_eq!(clock.status().unwrap(), "Time left: you 0:07 | CPU 0:10");
+    }
+
+    #[test]
+    fn charge_should_flag_the_first_player_over_budget() {
+        let mut clock = Clock::new(TimeControl {
+            per_game: Some(seconds(60)),
+            ..TimeControl::default()
+        });
+        assert!(clock.charge(Player::Cpu, seconds(1)));
+        assert!(!clock.charge(Player::Human, seconds(90)));
+        assert_eq!(clock.remaining(Player::Human), Some(Duration::ZERO));
+        assert_eq!(
+            clock.record(),
+            Some(ClockRecord {
+                human: seconds(60),
+                cpu: seconds(1),
+                flagged: Some(Player::Human),
+            })
+        );
+    }
+
+    #[test]
+    fn format_duration_should_show_minutes_and_seconds() {
+        assert_eq!(format_duration(seconds(0)), "0:00");
+        assert_eq!(format_duration(seconds(75)), "1:15");
+        assert_eq!(format_duration(Duration::from_millis(9_999)), "0:09");
+    }
+}
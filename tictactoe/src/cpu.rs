@@ -2,99 +2,465 @@ use crate::GridChecker;
 
 use super::grid::{Grid, Marker};
 
-use rand::{seq::SliceRandom, thread_rng};
-use std::{io, usize};
+use crate::error::GameResult;
+use crate::ui::Ui;
+use itertools::Itertools;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
-pub enum Difficulty {
-    Low = 1,
-    Medium = 2,
-    High = 3,
+pub type GameRng = ChaCha8Rng;
+
+pub fn seeded_rng(seed: u64) -> GameRng {
+    GameRng::seed_from_u64(seed)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Personality {
+    Aggressive,
+    Defensive,
+    Chaotic,
+    Balanced,
+}
+
+impl Personality {
+    pub fn from_name(name: &str) -> Option<Personality> {
+        match name.trim().to_lowercase().as_str() {
+            "a" | "1" | "aggressive" => Some(Personality::Aggressive),
+            "d" | "2" | "defensive" => Some(Personality::Defensive),
+            "c" | "3" | "chaotic" => Some(Personality::Chaotic),
+            "b" | "4" | "balanced" => Some(Personality::Balanced),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CpuProfile {
+    pub personality: Personality,
+    pub blunder_rate: f64,
+}
+
+impl CpuProfile {
+    pub fn with_strength(personality: Personality, strength: u8) -> CpuProfile {
+        CpuProfile {
+            personality,
+            blunder_rate: 1.0 - f64::from(strength.min(100)) / 100.0,
+        }
+    }
+
+    pub fn strength(&self) -> u8 {
+        ((1.0 - self.blunder_rate) * 100.0).round() as u8
+    }
+}
+
+impl Default for CpuProfile {
+    fn default() -> CpuProfile {
+        CpuProfile::with_strength(Personality::Defensive, 80)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MoveRule {
+    Win,
+    Block,
+    Fork,
+    BlockFork,
+    Threat,
+    Center,
+    OppositeCorner,
+    EmptyCorner,
+    Edge,
+    Random,
+    Avoid,
+    Blunder,
+    External,
+}
+
+impl std::fmt::Display for MoveRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let rationale = match self {
+            MoveRule::Win => "Win: complete a line",
+            MoveRule::Block => "Block: stop the opponent from completing a line",
+            MoveRule::Fork => "Fork: create two winning threats at once",
+            MoveRule::BlockFork => "Block fork: prevent the opponent from creating two threats",
+            MoveRule::Threat => "Threat: line up two markers to force a reply",
+            MoveRule::Center => "Center: take the center square",
+            MoveRule::OppositeCorner => "Opposite corner: take the corner facing the opponent's",
+            MoveRule::EmptyCorner => "Empty corner: take a free corner",
+            MoveRule::Edge => "Edge: take a free edge square",
+            MoveRule::Random => "Random: pick any free square",
+            MoveRule::Avoid => "Avoid: stay clear of completing a line of its own",
+            MoveRule::Blunder => "Blunder: deliberately pick a weaker square",
+            MoveRule::External => "External: chosen by an external engine",
+        };
+        write!(f, "{}", rationale)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CpuMove {
+    pub position: (i32, i32),
+    pub rule: MoveRule,
 }
 
-fn make_random_move(grid: &Grid) -> Grid {
-    let empty_position = grid.extract_empty_positions();
-    let mut rng = thread_rng();
-    let random_grid_coordinates = empty_position
+fn rule_priorities(personality: Personality) -> &'static [MoveRule] {
+    match personality {
+        Personality::Aggressive => &[
+            MoveRule::Win,
+            MoveRule::Block,
+            MoveRule::Fork,
+            MoveRule::BlockFork,
+            MoveRule::Threat,
+            MoveRule::Center,
+            MoveRule::OppositeCorner,
+            MoveRule::EmptyCorner,
+            MoveRule::Edge,
+        ],
+        Personality::Defensive => &[
+            MoveRule::Win,
+            MoveRule::Block,
+            MoveRule::BlockFork,
+            MoveRule::Center,
+            MoveRule::OppositeCorner,
+            MoveRule::EmptyCorner,
+            MoveRule::Fork,
+            MoveRule::Edge,
+        ],
+        Personality::Chaotic => &[MoveRule::Win, MoveRule::Block, MoveRule::Random],
+        // The classic Expert order.
+        Personality::Balanced => &[
+            MoveRule::Win,
+            MoveRule::Block,
+            MoveRule::Fork,
+            MoveRule::BlockFork,
+            MoveRule::Center,
+            MoveRule::OppositeCorner,
+            MoveRule::EmptyCorner,
+            MoveRule::Edge,
+        ],
+    }
+}
+
+fn extract_sorted_empty_positions(grid: &Grid) -> Vec<(i32, i32)> {
+    grid.extract_empty_positions()
         .values()
-        .collect::<Vec<_>>()
-        .choose(&mut rng)
-        .unwrap()
-        .to_owned();
+        .cloned()
+        .sorted()
+        .collect()
+}
 
-    let mut new_grid = grid.clone();
-    new_grid.insert(*random_grid_coordinates, Marker::O);
-    new_grid
+fn extract_fork_positions(grid: &Grid, marker: &Marker) -> Vec<(i32, i32)> {
+    extract_sorted_empty_positions(grid)
+        .into_iter()
+        .filter(|position| {
+            let mut attempt_grid = grid.clone();
+            attempt_grid.insert(*position, *marker);
+            attempt_grid.extract_winning_positions(marker).len() >= 2
+        })
+        .collect()
+}
+
+// A threat is only worth making when the forced answer does not hand the
+// opponent a fork of their own.
+fn extract_safe_threat_positions(grid: &Grid, marker: &Marker) -> Vec<(i32, i32)> {
+    let opponent = marker.opponent();
+    extract_sorted_empty_positions(grid)
+        .into_iter()
+        .filter(|position| {
+            let mut attempt_grid = grid.clone();
+            attempt_grid.insert(*position, *marker);
+            let forced_answers = attempt_grid.extract_winning_positions(marker);
+            forced_answers.len() == 1 && {
+                attempt_grid.insert(forced_answers[0], opponent);
+                attempt_grid.extract_winning_positions(&opponent).len() < 2
+            }
+        })
+        .collect()
 }
 
-pub fn make_cpu_move(grid: &Grid, difficulty: Difficulty) -> Grid {
-    if difficulty == Difficulty::High {
-        let winning_moves = grid.extract_winning_positions(&Marker::O);
-        if !winning_moves.is_empty() {
-            let mut new_grid = grid.clone();
-            let (x, y) = winning_moves[0];
-            new_grid.insert((x, y), Marker::O);
-            return new_grid;
+fn extract_fork_blocking_positions(grid: &Grid, marker: &Marker) -> Vec<(i32, i32)> {
+    let opponent_forks = extract_fork_positions(grid, &marker.opponent());
+    if opponent_forks.len() <= 1 {
+        return opponent_forks;
+    }
+
+    // With several forks available to the opponent, force them to answer a
+    // threat instead.
+    let forcing_moves = extract_safe_threat_positions(grid, marker);
+    if forcing_moves.is_empty() {
+        opponent_forks
+    } else {
+        forcing_moves
+    }
+}
+
+fn extract_empty_among(grid: &Grid, candidates: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    candidates
+        .iter()
+        .filter(|position| grid.grid.get(position) == Some(&Marker::Null))
+        .cloned()
+        .collect()
+}
+
+pub fn extract_rule_candidates(grid: &Grid, marker: &Marker, rule: MoveRule) -> Vec<(i32, i32)> {
+    let opponent = marker.opponent();
+    let last = grid.size as i32 - 1;
+    let corners = [(0, 0), (0, last), (last, 0), (last, last)];
+
+    match rule {
+        MoveRule::Win => grid.extract_winning_positions(marker),
+        MoveRule::Block => grid.extract_winning_positions(&opponent),
+        MoveRule::Fork => extract_fork_positions(grid, marker),
+        MoveRule::BlockFork => extract_fork_blocking_positions(grid, marker),
+        MoveRule::Threat => extract_safe_threat_positions(grid, marker),
+        MoveRule::Center => extract_empty_among(grid, &[(last / 2, last / 2)]),
+        MoveRule::OppositeCorner => {
+            let opposite_corners = corners
+                .iter()
+                .filter(|corner| grid.grid.get(corner) == Some(&opponent))
+                .map(|(x, y)| (last - x, last - y))
+                .collect::<Vec<_>>();
+            extract_empty_among(grid, &opposite_corners)
+        }
+        MoveRule::EmptyCorner => extract_empty_among(grid, &corners),
+        MoveRule::Edge => extract_sorted_empty_positions(grid)
+            .into_iter()
+            .filter(|position| !corners.contains(position))
+            .collect(),
+        MoveRule::Avoid => {
+            let losing_positions = grid.extract_winning_positions(marker);
+            extract_sorted_empty_positions(grid)
+                .into_iter()
+                .filter(|position| !losing_positions.contains(position))
+                .collect()
+        }
+        MoveRule::Random | MoveRule::Blunder | MoveRule::External => {
+            extract_sorted_empty_positions(grid)
         }
     }
+}
+
+pub fn choose_random_position<R: Rng>(grid: &Grid, rng: &mut R) -> (i32, i32) {
+    *extract_sorted_empty_positions(grid)
+        .choose(rng)
+        .expect("Random move requested on a full grid")
+}
+
+fn choose_by_priorities<R: Rng>(
+    grid: &Grid,
+    marker: &Marker,
+    priorities: &[MoveRule],
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> CpuMove {
+    let (best_position, best_rule) = priorities
+        .iter()
+        .find_map(|rule| {
+            extract_rule_candidates(grid, marker, *rule)
+                .choose(rng)
+                .map(|position| (*position, *rule))
+        })
+        .unwrap_or_else(|| (choose_random_position(grid, rng), MoveRule::Random));
 
-    if difficulty >= Difficulty::Medium {
-        let adversary_winning_moves = grid.extract_winning_positions(&Marker::X);
-        if !adversary_winning_moves.is_empty() {
-            let mut new_grid = grid.clone();
-            let (x, y) = adversary_winning_moves[0];
-            new_grid.insert((x, y), Marker::O);
-            return new_grid;
+    // Every candidate of the best rule is as strong as the one drawn, so a
+    // blunder has to avoid all of them.
+    if rng.gen_bool(profile.blunder_rate.clamp(0.0, 1.0)) {
+        let best_positions = extract_rule_candidates(grid, marker, best_rule);
+        let weaker_positions = extract_sorted_empty_positions(grid)
+            .into_iter()
+            .filter(|position| !best_positions.contains(position))
+            .collect::<Vec<_>>();
+        if let Some(position) = weaker_positions.choose(rng) {
+            return CpuMove {
+                position: *position,
+                rule: MoveRule::Blunder,
+            };
         }
     }
 
-    make_random_move(grid)
+    CpuMove {
+        position: best_position,
+        rule: best_rule,
+    }
+}
+
+pub fn choose_cpu_move<R: Rng>(
+    grid: &Grid,
+    marker: &Marker,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> CpuMove {
+    let priorities = rule_priorities(profile.personality);
+    choose_by_priorities(grid, marker, priorities, profile, rng)
 }
 
-pub fn parse_difficulty<R>(mut reader: R) -> Option<Difficulty>
-where
-    R: io::BufRead,
-{
-    println!(
+// In misère play completing a line loses, so every personality simply steers
+// clear of its own winning squares.
+pub fn choose_misere_move<R: Rng>(
+    grid: &Grid,
+    marker: &Marker,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> CpuMove {
+    choose_by_priorities(grid, marker, &[MoveRule::Avoid], profile, rng)
+}
+
+pub fn make_cpu_move<R: Rng>(
+    grid: &Grid,
+    marker: Marker,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> (Grid, CpuMove) {
+    let cpu_move = choose_cpu_move(grid, &marker, profile, rng);
+    let mut new_grid = grid.clone();
+    new_grid.insert(cpu_move.position, marker);
+    (new_grid, cpu_move)
+}
+
+fn line_name(grid: &Grid, line: &[(i32, i32)]) -> String {
+    let (first, second) = (line[0], line[1]);
+    if first.0 == second.0 {
+        format!("row {}", first.0 + 1)
+    } else if first.1 == second.1 {
+        format!("column {}", first.1 + 1)
+    } else if (second.1 - first.1).rem_euclid(grid.size as i32) == 1 {
+        "diagonal".to_owned()
+    } else {
+        "anti-diagonal".to_owned()
+    }
+}
+
+// Lines through the position where every other square holds the marker.
+fn lines_completed_at(grid: &Grid, position: (i32, i32), marker: &Marker) -> Vec<String> {
+    grid.lines()
+        .iter()
+        .filter(|line| {
+            line.contains(&position)
+                && line
+                    .iter()
+                    .filter(|cell| **cell != position)
+                    .all(|cell| grid.grid.get(cell) == Some(marker))
+        })
+        .map(|line| line_name(grid, line))
+        .collect()
+}
+
+fn join_numbers(grid: &Grid, positions: &[(i32, i32)]) -> String {
+    let numbers = positions
+        .iter()
+        .map(|position| grid.position_number(*position).to_string())
+        .collect::<Vec<_>>();
+    match numbers.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => numbers.join(""),
+    }
+}
+
+// Tutor commentary for a move chosen on `grid`, in the words of the rule that picked it.
+pub fn describe_move(grid: &Grid, marker: &Marker, cpu_move: &CpuMove) -> String {
+    let opponent = marker.opponent();
+    let position = cpu_move.position;
+    let number = grid.position_number(position);
+    let mut after = grid.clone();
+    after.insert(position, *marker);
+    let threats = after.extract_winning_positions(marker);
+    let last = grid.size as i32 - 1;
+
+    match cpu_move.rule {
+        MoveRule::Win => format!(
+            "completed {} to win",
+            lines_completed_at(grid, position, marker).join(" and ")
+        ),
+        MoveRule::Block => format!(
+            "blocked your {}",
+            lines_completed_at(grid, position, &opponent).join(" and ")
+        ),
+        MoveRule::Fork => format!(
+            "created a fork at {}, threatening {}",
+            number,
+            join_numbers(grid, &threats)
+        ),
+        MoveRule::BlockFork if extract_fork_positions(grid, &opponent).contains(&position) => {
+            format!("took {}, where you could have forked", number)
+        }
+        MoveRule::BlockFork => format!(
+            "threatened {} so you must answer instead of forking",
+            join_numbers(grid, &threats)
+        ),
+        MoveRule::Threat => format!(
+            "lined up two with {}, you must answer at {}",
+            number,
+            join_numbers(grid, &threats)
+        ),
+        MoveRule::Center => "took the center".to_owned(),
+        MoveRule::OppositeCorner => format!(
+            "took corner {}, opposite your corner {}",
+            number,
+            grid.position_number((last - position.0, last - position.1))
+        ),
+        MoveRule::EmptyCorner => format!("took corner {}", number),
+        MoveRule::Edge => format!("took edge {}", number),
+        MoveRule::Random => format!("made a random move at {}", number),
+        MoveRule::Avoid => format!("played {} to avoid completing a line of its own", number),
+        MoveRule::Blunder => format!("deliberately played a weaker move at {}", number),
+        MoveRule::External => format!("let the engine choose {}", number),
+    }
+}
+
+pub fn parse_personality<U: Ui + ?Sized>(ui: &mut U) -> GameResult<Option<Personality>> {
+    let answer = ui.prompt(
+        "
+Please select the CPU personality between:
+    1 - Aggressive (a), prefers creating threats
+    2 - Defensive (d), prefers blocking and the center
+    3 - Chaotic (c), plays anywhere once the urgent moves are made
+    4 - Balanced (b), follows the classic expert rules in order
+",
+    )?;
+
+    Ok(Personality::from_name(&answer))
+}
+
+pub fn parse_strength<U: Ui + ?Sized>(ui: &mut U) -> GameResult<Option<u8>> {
+    let answer = ui.prompt(
         "
-Please select difficulty between:
-    1 - Low (l)
-    2 - Medium (m)
-    3 - High (h)
-"
-    );
-    let mut buffer = String::new();
-    reader.read_line(&mut buffer).expect("Unable to read");
-
-    let result = match buffer.trim().to_lowercase().as_str() {
-        "l" | "1" => Some(Difficulty::Low),
-        "m" | "2" => Some(Difficulty::Medium),
-        "h" | "3" => Some(Difficulty::High),
-        _ => return None,
-    };
-    result
+Please select the CPU strength between 0 and 100:
+    0 deliberately avoids the best move, 100 never blunders
+",
+    )?;
+
+    Ok(answer
+        .trim()
+        .parse::<u8>()
+        .ok()
+        .filter(|strength| *strength <= 100))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::grid::from_array;
+    use super::super::grid::{create_grid, from_array};
     use super::*;
+    use crate::ui::RecordingUi;
     use std::collections::HashSet;
 
     #[test]
-    fn make_random_move_should_add_minus_one() {
+    fn make_cpu_move_with_chaotic_personality_should_add_minus_one() {
         #[rustfmt::skip]
         let grid = from_array([
             [0, 0, 1],
             [0, -1, 0],
             [1, 0, 0],
         ]);
-        let new_grid = make_random_move(&grid);
+        let profile = CpuProfile::with_strength(Personality::Chaotic, 100);
+        let (new_grid, _) = make_cpu_move(&grid, Marker::O, &profile, &mut seeded_rng(0));
 
         let zero_delta =
             grid.extract_empty_positions().len() - new_grid.extract_empty_positions().len();
         assert_eq!(zero_delta, 1);
-        assert_eq!(new_grid.grid.values().filter(|x| **x == Marker::O).count(), 2);
+        assert_eq!(
+            new_grid.grid.values().filter(|x| **x == Marker::O).count(),
+            2
+        );
     }
 
     #[test]
@@ -136,6 +502,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn extract_winning_positions_should_be_in_row_col_order() {
+        #[rustfmt::skip]
+        let grid = from_array([
+            [-1, -1, 0],
+            [0, 0, 0],
+            [-1, 0, 0],
+        ]);
+        let positions = grid.extract_winning_positions(&Marker::O);
+        assert_eq!(positions, vec![(0, 2), (1, 0)]);
+    }
+
     #[test]
     fn extract_winning_positions_allows_to_switch_player() {
         #[rustfmt::skip]
@@ -144,20 +522,27 @@ mod tests {
             [0, 0, -1],
             [1, 1, -1],
         ]);
+        // The center completes the anti-diagonal with the corners at 3 and
+        // 7; it was only missed while the X at 9 was counted on that line.
         let positions = grid.extract_winning_positions(&Marker::X);
-        assert_eq!(positions.len(), 1);
-        assert_eq!(positions, [(1, 0)])
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions, [(1, 0), (1, 1)])
     }
 
     #[test]
-    fn make_cpu_move_should_fill_an_empty_slot_on_low_difficulty() {
+    fn make_cpu_move_should_fill_an_empty_slot_at_zero_strength() {
         #[rustfmt::skip]
         let grid = from_array([
             [1, 0, 0],
             [0, 0, 0],
             [0, 0, 0],
         ]);
-        let grid_after_action = make_cpu_move(&grid, Difficulty::Low);
+        let (grid_after_action, _) = make_cpu_move(
+            &grid,
+            Marker::O,
+            &CpuProfile::with_strength(Personality::Defensive, 0),
+            &mut seeded_rng(0),
+        );
         assert_eq!(
             grid_after_action
                 .grid
@@ -169,7 +554,7 @@ mod tests {
     }
 
     #[test]
-    fn make_cpu_move_should_fill_block_auto_win_when_medium_difficulty() {
+    fn make_cpu_move_should_fill_block_auto_win_at_full_strength() {
         #[rustfmt::skip]
         let grid = from_array([
             [1, 0, 0],
@@ -177,52 +562,390 @@ mod tests {
             [0, 0, 0],
         ]);
         let expected = from_array([[1, 0, 0], [1, -1, 0], [-1, 0, 0]]);
-        let grid_after_action = make_cpu_move(&grid, Difficulty::Medium);
-        assert_eq!(grid_after_action, expected);
+        for personality in [
+            Personality::Aggressive,
+            Personality::Defensive,
+            Personality::Chaotic,
+            Personality::Balanced,
+        ] {
+            let profile = CpuProfile::with_strength(personality, 100);
+            let (grid_after_action, _) =
+                make_cpu_move(&grid, Marker::O, &profile, &mut seeded_rng(0));
+            assert_eq!(grid_after_action, expected);
+        }
+    }
+
+    #[test]
+    fn make_cpu_move_should_make_winning_move_at_full_strength() {
+        let grid = from_array([[1, -1, 0], [1, -1, 0], [0, 0, 0]]);
+        let expected = from_array([[1, -1, 0], [1, -1, 0], [0, -1, 0]]);
+        for personality in [
+            Personality::Aggressive,
+            Personality::Defensive,
+            Personality::Chaotic,
+            Personality::Balanced,
+        ] {
+            let profile = CpuProfile::with_strength(personality, 100);
+            let (grid_after_action, _) =
+                make_cpu_move(&grid, Marker::O, &profile, &mut seeded_rng(0));
+            assert_eq!(grid_after_action, expected);
+        }
+    }
+
+    #[test]
+    fn choose_cpu_move_should_never_pick_the_best_move_at_zero_strength() {
+        let grid = from_array([[1, -1, 0], [1, -1, 0], [0, 0, 0]]);
+        let profile = CpuProfile::with_strength(Personality::Aggressive, 0);
+        let mut rng = seeded_rng(0);
+        for _ in 0..20 {
+            let CpuMove { position, rule } = choose_cpu_move(&grid, &Marker::O, &profile, &mut rng);
+            assert_eq!(rule, MoveRule::Blunder);
+            assert_ne!(position, (2, 1));
+        }
+    }
+
+    #[test]
+    fn choose_cpu_move_should_avoid_every_winning_cell_when_blundering() {
+        let grid = from_array([[-1, -1, 0], [-1, 1, 0], [0, 1, 0]]);
+        let winning_positions = grid.extract_winning_positions(&Marker::O);
+        assert_eq!(winning_positions.len(), 2);
+        let profile = CpuProfile {
+            personality: Personality::Aggressive,
+            blunder_rate: 1.0,
+        };
+        let mut rng = seeded_rng(0);
+        for _ in 0..20 {
+            let CpuMove { position, rule } = choose_cpu_move(&grid, &Marker::O, &profile, &mut rng);
+            assert_eq!(rule, MoveRule::Blunder);
+            assert!(!winning_positions.contains(&position));
+        }
     }
 
     #[test]
-    fn make_cpu_move_should_fill_block_auto_win_when_high_difficulty() {
+    fn choose_cpu_move_should_blunder_at_roughly_the_configured_rate() {
+        let grid = create_grid();
+        let profile = CpuProfile {
+            personality: Personality::Defensive,
+            blunder_rate: 0.25,
+        };
+        let mut rng = seeded_rng(0);
+        let blunders = (0..400)
+            .filter(|_| {
+                choose_cpu_move(&grid, &Marker::O, &profile, &mut rng).rule == MoveRule::Blunder
+            })
+            .count();
+        assert!((60..140).contains(&blunders));
+    }
+
+    #[test]
+    fn cpu_profile_strength_should_mirror_blunder_rate() {
+        let profile = CpuProfile::with_strength(Personality::Chaotic, 70);
+        assert!((profile.blunder_rate - 0.3).abs() < 1e-9);
+        assert_eq!(profile.strength(), 70);
+        assert_eq!(
+            CpuProfile::with_strength(Personality::Chaotic, 250).strength(),
+            100
+        );
+    }
+
+    #[test]
+    fn make_cpu_move_should_be_reproducible_with_same_seed() {
+        let grid = from_array([[1, 0, 0], [0, 0, 0], [0, 0, 0]]);
+        for profile in [
+            CpuProfile::with_strength(Personality::Aggressive, 100),
+            CpuProfile::with_strength(Personality::Defensive, 50),
+            CpuProfile::with_strength(Personality::Chaotic, 0),
+        ] {
+            let mut first_rng = seeded_rng(42);
+            let mut second_rng = seeded_rng(42);
+            let first_moves = (0..5)
+                .map(|_| make_cpu_move(&grid, Marker::O, &profile, &mut first_rng))
+                .collect::<Vec<_>>();
+            let second_moves = (0..5)
+                .map(|_| make_cpu_move(&grid, Marker::O, &profile, &mut second_rng))
+                .collect::<Vec<_>>();
+            assert_eq!(first_moves, second_moves);
+        }
+    }
+
+    #[test]
+    fn choose_random_position_should_depend_on_seed() {
+        let grid = create_grid();
+        let moves_for_seed = |seed| {
+            let mut rng = seeded_rng(seed);
+            (0..5)
+                .map(|_| choose_random_position(&grid, &mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(moves_for_seed(7), moves_for_seed(7));
+        assert_ne!(moves_for_seed(7), moves_for_seed(8));
+    }
+
+    #[test]
+    fn choose_cpu_move_should_follow_aggressive_rule_priorities() {
+        #[rustfmt::skip]
+        let cases = [
+            ([[-1, -1, 0], [1, 1, 0], [0, 0, 0]], MoveRule::Win, vec![(0, 2)]),
+            ([[1, 1, 0], [0, -1, 0], [0, 0, 0]], MoveRule::Block, vec![(0, 2)]),
+            ([[-1, 1, 0], [0, 1, 0], [0, -1, 0]], MoveRule::Fork, vec![(2, 0)]),
+            ([[-1, 0, 0], [0, 0, 0], [0, 0, 1]], MoveRule::Threat, vec![(0, 1), (0, 2), (1, 0), (2, 0)]),
+            ([[1, 0, 0], [0, 0, 0], [0, 0, 0]], MoveRule::Center, vec![(1, 1)]),
+            ([[0, 0, 0], [0, 1, 0], [0, 0, 0]], MoveRule::EmptyCorner, vec![(0, 0), (0, 2), (2, 0), (2, 2)]),
+        ];
+        for (array, expected_rule, expected_positions) in cases {
+            let CpuMove { position, rule } = choose_cpu_move(
+                &from_array(array),
+                &Marker::O,
+                &CpuProfile::with_strength(Personality::Aggressive, 100),
+                &mut seeded_rng(0),
+            );
+            assert_eq!(rule, expected_rule);
+            assert!(expected_positions.contains(&position));
+        }
+    }
+
+    #[test]
+    fn choose_cpu_move_should_take_opposite_corner_when_defensive() {
         #[rustfmt::skip]
         let grid = from_array([
             [1, 0, 0],
-            [1, -1, 0],
+            [0, -1, 0],
             [0, 0, 0],
         ]);
-        let expected = from_array([[1, 0, 0], [1, -1, 0], [-1, 0, 0]]);
-        let grid_after_action = make_cpu_move(&grid, Difficulty::High);
-        assert_eq!(grid_after_action, expected);
+        let profile = CpuProfile::with_strength(Personality::Defensive, 100);
+        let CpuMove { position, rule } =
+            choose_cpu_move(&grid, &Marker::O, &profile, &mut seeded_rng(0));
+        assert_eq!(rule, MoveRule::OppositeCorner);
+        assert_eq!(position, (2, 2));
     }
 
     #[test]
-    fn make_cpu_move_should_make_winning_move_on_high_difficulty() {
-        let grid = from_array([[1, -1, 0], [1, -1, 0], [0, 0, 0]]);
-        let expected = from_array([[1, -1, 0], [1, -1, 0], [0, -1, 0]]);
-        let grid_after_action = make_cpu_move(&grid, Difficulty::High);
-        assert_eq!(grid_after_action, expected);
+    fn choose_cpu_move_should_not_defend_opposite_corners_by_taking_a_corner() {
+        #[rustfmt::skip]
+        let grid = from_array([
+            [1, 0, 0],
+            [0, -1, 0],
+            [0, 0, 1],
+        ]);
+        for personality in [Personality::Aggressive, Personality::Defensive] {
+            let profile = CpuProfile::with_strength(personality, 100);
+            let CpuMove { position, rule } =
+                choose_cpu_move(&grid, &Marker::O, &profile, &mut seeded_rng(0));
+            assert_eq!(rule, MoveRule::BlockFork);
+            assert!([(0, 1), (1, 0), (1, 2), (2, 1)].contains(&position));
+        }
     }
 
     #[test]
-    fn parse_difficulty_should_handle_multiple_type_of_user_inputs() {
-        for input in [b"l", b"1", b"L"].iter() {
-            let answer = parse_difficulty(&input[..]);
-            assert_eq!(answer, Some(Difficulty::Low));
+    fn choose_cpu_move_should_block_single_fork() {
+        #[rustfmt::skip]
+        let grid = from_array([
+            [-1, 0, 0],
+            [0, 1, 0],
+            [0, 0, 1],
+        ]);
+        let profile = CpuProfile::with_strength(Personality::Aggressive, 100);
+        let CpuMove { rule, .. } = choose_cpu_move(&grid, &Marker::O, &profile, &mut seeded_rng(0));
+        assert_eq!(rule, MoveRule::BlockFork);
+    }
+
+    #[test]
+    fn full_strength_cpu_should_never_lose_against_random_player() {
+        let mut rng = seeded_rng(3);
+        for game in 0..60 {
+            let profile = CpuProfile::with_strength(
+                [Personality::Aggressive, Personality::Defensive][game % 2],
+                100,
+            );
+            let mut grid = create_grid();
+            while !grid.is_grid_full() && grid.is_winning_grid().is_none() {
+                let empty_positions = grid.extract_empty_positions();
+                let random_position = empty_positions
+                    .values()
+                    .sorted()
+                    .collect::<Vec<_>>()
+                    .choose(&mut rng)
+                    .cloned()
+                    .unwrap();
+                grid.insert(*random_position, Marker::X);
+                if grid.is_grid_full() || grid.is_winning_grid().is_some() {
+                    break;
+                }
+                grid = make_cpu_move(&grid, Marker::O, &profile, &mut rng).0;
+            }
+            assert_ne!(grid.is_winning_grid(), Some(Marker::X));
+        }
+    }
+
+    #[test]
+    fn parse_personality_should_handle_multiple_type_of_user_inputs() {
+        for input in ["a", "1", "A"] {
+            let answer = parse_personality(&mut RecordingUi::new(&[input])).unwrap();
+            assert_eq!(answer, Some(Personality::Aggressive));
+        }
+        for input in ["d", "2", "D"] {
+            let answer = parse_personality(&mut RecordingUi::new(&[input])).unwrap();
+            assert_eq!(answer, Some(Personality::Defensive));
         }
-        for input in [b"m", b"2", b"M"].iter() {
-            let answer = parse_difficulty(&input[..]);
-            assert_eq!(answer, Some(Difficulty::Medium));
+        for input in ["c", "3", "C"] {
+            let answer = parse_personality(&mut RecordingUi::new(&[input])).unwrap();
+            assert_eq!(answer, Some(Personality::Chaotic));
         }
-        for input in [b"h", b"3", b"H"].iter() {
-            let answer = parse_difficulty(&input[..]);
-            assert_eq!(answer, Some(Difficulty::High));
+        for input in ["b", "4", "B"] {
+            let answer = parse_personality(&mut RecordingUi::new(&[input])).unwrap();
+            assert_eq!(answer, Some(Personality::Balanced));
         }
     }
 
     #[test]
-    fn parse_difficulty_should_return_none_on_wrong_input() {
-        for input in [b"x", b"8", b"B"].iter() {
-            let answer = parse_difficulty(&input[..]);
+    fn choose_misere_move_should_avoid_completing_own_line() {
+        let grid = from_array([[-1, -1, 0], [1, 1, 0], [-1, 1, 0]]);
+        let profile = CpuProfile::with_strength(Personality::Aggressive, 100);
+        for seed in 0..10 {
+            let CpuMove { position, rule } =
+                choose_misere_move(&grid, &Marker::O, &profile, &mut seeded_rng(seed));
+            assert_eq!(rule, MoveRule::Avoid);
+            assert_ne!(position, (0, 2));
+        }
+    }
+
+    #[test]
+    fn make_cpu_move_should_play_the_requested_marker() {
+        let grid = from_array([[-1, 0, 0], [0, 0, 0], [0, 0, 0]]);
+        let profile = CpuProfile::with_strength(Personality::Defensive, 100);
+        let (new_grid, cpu_move) = make_cpu_move(&grid, Marker::X, &profile, &mut seeded_rng(0));
+        assert_eq!(new_grid.grid.get(&cpu_move.position), Some(&Marker::X));
+        assert_eq!(cpu_move.rule, MoveRule::Center);
+    }
+
+    #[test]
+    fn describe_move_should_name_lines_and_squares() {
+        let describe = |grid: &Grid, position, rule| {
+            describe_move(grid, &Marker::O, &CpuMove { position, rule })
+        };
+        let grid = from_array([[1, 0, 0], [1, -1, 0], [0, 0, -1]]);
+        assert_eq!(
+            describe(&grid, (2, 0), MoveRule::Block),
+            "blocked your column 1"
+        );
+        let grid = from_array([[0, 1, 0], [1, -1, 0], [0, 0, -1]]);
+        assert_eq!(
+            describe(&grid, (0, 0), MoveRule::Win),
+            "completed diagonal to win"
+        );
+
+        let grid = from_array([[1, 0, 0], [0, 0, 0], [0, 0, 0]]);
+        assert_eq!(describe(&grid, (1, 1), MoveRule::Center), "took the center");
+        assert_eq!(
+            describe(&grid, (2, 2), MoveRule::OppositeCorner),
+            "took corner 9, opposite your corner 1"
+        );
+        assert_eq!(
+            describe(&grid, (0, 1), MoveRule::Random),
+            "made a random move at 2"
+        );
+    }
+
+    #[test]
+    fn describe_move_should_explain_forks_and_threats() {
+        let grid = from_array([[-1, 0, 0], [0, 1, 0], [1, 0, -1]]);
+        assert_eq!(
+            describe_move(
+                &grid,
+                &Marker::O,
+                &CpuMove {
+                    position: (0, 2),
+                    rule: MoveRule::Fork
+                }
+            ),
+            "created a fork at 3, threatening 2 and 6"
+        );
+
+        let grid = from_array([[1, 0, 0], [0, -1, 0], [0, 0, 1]]);
+        assert_eq!(
+            describe_move(
+                &grid,
+                &Marker::O,
+                &CpuMove {
+                    position: (0, 1),
+                    rule: MoveRule::BlockFork
+                }
+            ),
+            "threatened 8 so you must answer instead of forking"
+        );
+    }
+
+    #[test]
+    fn balanced_personality_should_follow_the_expert_rule_order() {
+        assert_eq!(
+            rule_priorities(Personality::Balanced),
+            [
+                MoveRule::Win,
+                MoveRule::Block,
+                MoveRule::Fork,
+                MoveRule::BlockFork,
+                MoveRule::Center,
+                MoveRule::OppositeCorner,
+                MoveRule::EmptyCorner,
+                MoveRule::Edge,
+            ]
+        );
+
+        // With nothing urgent on the board, the center comes next.
+        let profile = CpuProfile::with_strength(Personality::Balanced, 100);
+        let grid = from_array([[1, 0, 0], [0, 0, 0], [0, 0, 0]]);
+        let cpu_move = choose_cpu_move(&grid, &Marker::O, &profile, &mut seeded_rng(0));
+        assert_eq!(cpu_move.position, (1, 1));
+        assert_eq!(cpu_move.rule, MoveRule::Center);
+    }
+
+    #[test]
+    fn personality_from_name_should_accept_full_names() {
+        assert_eq!(
+            Personality::from_name("Aggressive"),
+            Some(Personality::Aggressive)
+        );
+        assert_eq!(
+            Personality::from_name(" chaotic"),
+            Some(Personality::Chaotic)
+        );
+        assert_eq!(
+            Personality::from_name("Balanced"),
+            Some(Personality::Balanced)
+        );
+        assert_eq!(Personality::from_name("lazy"), None);
+    }
+
+    #[test]
+    fn parse_personality_should_return_none_on_wrong_input() {
+        for input in ["x", "8", "E"] {
+            let answer = parse_personality(&mut RecordingUi::new(&[input])).unwrap();
             assert!(answer.is_none());
         }
     }
+
+    #[test]
+    fn parse_strength_should_accept_values_between_0_and_100() {
+        assert_eq!(
+            parse_strength(&mut RecordingUi::new(&["0"])).unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            parse_strength(&mut RecordingUi::new(&["65"])).unwrap(),
+            Some(65)
+        );
+        assert_eq!(
+            parse_strength(&mut RecordingUi::new(&["100"])).unwrap(),
+            Some(100)
+        );
+        assert_eq!(
+            parse_strength(&mut RecordingUi::new(&["101"])).unwrap(),
+            None
+        );
+        assert_eq!(
+            parse_strength(&mut RecordingUi::new(&["-5"])).unwrap(),
+            None
+        );
+        assert_eq!(parse_strength(&mut RecordingUi::new(&["h"])).unwrap(), None);
+    }
 }
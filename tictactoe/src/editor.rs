@@ -0,0 +1,334 @@
+use super::error::GameResult;
+use super::game::GameConfig;
+use super::grid::{Grid, GridChecker, Marker, Player};
+use super::input;
+use super::ui::{Highlights, Ui};
+
+use std::collections::HashMap;
+
+const EDITOR_HELP: &str = "Position editor commands:
+x CELL / o CELL   place a marker, CELL uses any move syntax such as 5, b2 or top left
+clear [CELL]      empty a cell, or the whole board
+load TEXT         load X.O/.X./O.. notation or an array like [[1, 0, -1], [0, 1, 0], [-1, 0, 0]]
+size N            start over on an empty N x N board
+next human|cpu    choose who moves next
+play              validate the position and play it out
+cancel            back to the main menu";
+
+fn parse_array_text(text: &str) -> Option<Grid> {
+    let compact = text
+        .chars()
+        .filter(|glyph| !glyph.is_whitespace())
+        .collect::<String>();
+    let rows = compact
+        .strip_prefix("[[")?
+        .strip_suffix("]]")?
+        .split("],[")
+        .map(|row| {
+            row.split(',')
+                .map(|value| match value {
+                    "1" => Some(Marker::X),
+                    "-1" => Some(Marker::O),
+                    "0" => Some(Marker::Null),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let size = rows.len();
+    let mut cells = HashMap::new();
+    for (ix_row, row) in rows.iter().enumerate() {
+        if row.len() != size {
+            return None;
+        }
+        for (ix_col, marker) in row.iter().enumerate() {
+            cells.insert((ix_row as i32, ix_col as i32), *marker);
+        }
+    }
+    Some(Grid::new(cells, size))
+}
+
+pub fn parse_position_text(text: &str) -> Result<Grid, String> {
+    let grid = match text.trim() {
+        array if array.starts_with('[') => parse_array_text(array),
+        notation => Grid::from_notation(notation),
+    }
+    .ok_or_else(|| {
+        format!(
+            "'{}' is neither notation like X.O/.X./O.. nor an array like [[1, 0, -1], ...]",
+            text.trim()
+        )
+    })?;
+    if !(3..=9).contains(&grid.size) {
+        return Err(format!(
+            "a {}x{} board is not supported, sizes go from 3 to 9",
+            grid.size, grid.size
+        ));
+    }
+    Ok(grid)
+}
+
+pub fn validate_position(grid: &Grid, to_move: Marker) -> Result<(), String> {
+    let (x_count, o_count) = (grid.count_markers(Marker::X), grid.count_markers(Marker::O));
+    if x_count.abs_diff(o_count) > 1 {
+        return Err(format!(
+            "X has {} markers and O has {}, the counts may differ by one at most",
+            x_count, o_count
+        ));
+    }
+    let (more, fewer) = match x_count.cmp(&o_count) {
+        std::cmp::Ordering::Greater => (Marker::X, Marker::O),
+        std::cmp::Ordering::Less => (Marker::O, Marker::X),
+        std::cmp::Ordering::Equal => (Marker::Null, to_move),
+    };
+    if to_move != fewer {
+        return Err(format!(
+            "{} has one marker more, so {} moves next",
+            more.glyph(),
+            fewer.glyph()
+        ));
+    }
+    if let Some(winner) = grid.winning_marker() {
+        return Err(format!("{} has already completed a line", winner.glyph()));
+    }
+    if grid.is_grid_full() {
+        return Err("the board is already full".to_owned());
+    }
+    Ok(())
+}
+
+// Adjusts size and first player so that `to_move` is next on the given grid.
+pub fn setup_config(
+    config: &GameConfig,
+    grid: &Grid,
+    to_move: Player,
+) -> Result<GameConfig, String> {
+    validate_position(grid, config.marker_of(to_move))?;
+    let first_player = if grid.count_markers(Marker::X) == grid.count_markers(Marker::O) {
+        to_move
+    } else {
+        to_move.opponent()
+    };
+    Ok(GameConfig {
+        size: grid.size,
+        first_player,
+        ..*config
+    })
+}
+
+fn parse_player(name: &str) -> Option<Player> {
+    match name {
+        "human" | "you" | "me" => Some(Player::Human),
+        "cpu" => Some(Player::Cpu),
+        _ => None,
+    }
+}
+
+pub fn run_editor<U: Ui + ?Sized>(
+    ui: &mut U,
+    config: &GameConfig,
+) -> GameResult<Option<(GameConfig, Grid)>> {
+    // Boards keep the configured lines and blocked cells at any size.
+    let sized = |size| GameConfig { size, ..*config };
+    let mut grid = config.new_grid();
+    let mut to_move = Player::Human;
+    loop {
+        ui.show_board(&grid, &Highlights::default());
+        let answer = ui.prompt(&format!(
+            "{}\nNext to move: {} ({})",
+            EDITOR_HELP,
+            match to_move {
+                Player::Human => "you",
+                Player::Cpu => "CPU",
+            },
+            config.marker_of(to_move).glyph()
+        ))?;
+        let answer = answer.trim();
+        let (command, argument) = answer
+            .split_once(char::is_whitespace)
+            .map_or((answer, ""), |(command, argument)| {
+                (command, argument.trim())
+            });
+
+        let result = match command.to_lowercase().as_str() {
+            "x" | "o" | "clear" if !argument.is_empty() => {
+                let marker = match command.to_lowercase().as_str() {
+                    "x" => Marker::X,
+                    "o" => Marker::O,
+                    _ => Marker::Null,
+                };
+                match input::parse_coordinates(argument, grid.size) {
+                    Ok(position) if grid.grid.get(&position) == Some(&Marker::Blocked) => Err(
+                        format!("cell {} is blocked", grid.position_number(position)),
+                    ),
+                    Ok(position) => {
+                        grid.insert(position, marker);
+                        Ok(())
+                    }
+                    Err(error) => Err(error.to_string()),
+                }
+            }
+            "clear" => {
+                grid = sized(grid.size).new_grid();
+                Ok(())
+            }
+            "load" => parse_position_text(argument).map(|loaded| {
+                let lines = config.variant.lines(&sized(loaded.size));
+                grid = loaded.with_lines(lines);
+            }),
+            "size" => match argument.parse().ok().filter(|size| (3..=9).contains(size)) {
+                Some(size) => {
+                    grid = sized(size).new_grid();
+                    Ok(())
+                }
+                None => Err("the size must be between 3 and 9".to_owned()),
+            },
+            "next" => match parse_player(&argument.to_lowercase()) {
+                Some(player) => {
+                    to_move = player;
+                    Ok(())
+                }
+                None => Err("next takes human or cpu".to_owned()),
+            },
+            "play" => match setup_config(config, &grid, to_move) {
+                Ok(config) => return Ok(Some((config, grid))),
+                Err(error) => Err(error),
+            },
+            "cancel" | "q" => return Ok(None),
+            _ => Err(format!("unknown editor command: {}", answer)),
+        };
+        if let Err(error) = result {
+            ui.show_message(&format!("Invalid: {}", error));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::error::GameError;
+    use super::super::game::Obstacles;
+    use super::super::grid::from_array;
+    use super::super::grid::{self, Topology};
+    use super::*;
+    use crate::ui::RecordingUi;
+
+    #[test]
+    fn parse_position_text_should_read_notation_and_arrays() {
+        let expected = from_array([[1, 0, -1], [0, 1, 0], [-1, 0, 0]]);
+        assert_eq!(parse_position_text("X.O/.X./O.."), Ok(expected.clone()));
+        assert_eq!(
+            parse_position_text("[[1, 0, -1], [0, 1, 0], [-1, 0, 0]]"),
+            Ok(expected)
+        );
+        assert_eq!(
+            parse_position_text("[[0,0,0,0],[0,1,0,0],[0,0,0,0],[0,0,0,-1]]")
+                .unwrap()
+                .size,
+            4
+        );
+    }
+
+    #[test]
+    fn parse_position_text_should_reject_malformed_text() {
+        assert!(parse_position_text("X.O/.X.").is_err());
+        assert!(parse_position_text("[[1, 0, 2], [0, 1, 0], [-1, 0, 0]]").is_err());
+        assert!(parse_position_text("[[1, 0], [0, 1, 0], [-1, 0, 0]]").is_err());
+        assert!(parse_position_text("X./..").is_err());
+    }
+
+    #[test]
+    fn validate_position_should_check_counts_side_to_move_and_result() {
+        let balanced = from_array([[1, 0, 0], [0, -1, 0], [0, 0, 0]]);
+        assert!(validate_position(&balanced, Marker::X).is_ok());
+        assert!(validate_position(&balanced, Marker::O).is_ok());
+
+        let x_ahead = from_array([[1, 0, 0], [0, -1, 0], [0, 0, 1]]);
+        assert!(validate_position(&x_ahead, Marker::O).is_ok());
+        assert_eq!(
+            validate_position(&x_ahead, Marker::X),
+            Err("X has one marker more, so O moves next".to_owned())
+        );
+
+        let lopsided = from_array([[1, 1, 0], [0, 0, 0], [0, 0, 0]]);
+        assert!(validate_position(&lopsided, Marker::O).is_err());
+
+        let won = from_array([[1, 1, 1], [-1, -1, 0], [0, 0, 0]]);
+        assert_eq!(
+            validate_position(&won, Marker::O),
+            Err("X has already completed a line".to_owned())
+        );
+    }
+
+    #[test]
+    fn setup_config_should_make_the_chosen_player_move_next() {
+        let config = GameConfig::default();
+        let balanced = from_array([[1, 0, 0], [0, -1, 0], [0, 0, 0]]);
+        let cpu_next = setup_config(&config, &balanced, Player::Cpu).unwrap();
+        assert_eq!(cpu_next.player_to_move(&balanced), Player::Cpu);
+
+        let x_ahead = from_array([[1, 0, 0], [0, -1, 0], [0, 0, 1]]);
+        let cpu_next = setup_config(&config, &x_ahead, Player::Cpu).unwrap();
+        assert_eq!(cpu_next.player_to_move(&x_ahead), Player::Cpu);
+        assert!(setup_config(&config, &x_ahead, Player::Human).is_err());
+
+        let large = grid::create_grid_of_size(5);
+        assert_eq!(
+            setup_config(&config, &large, Player::Human).unwrap().size,
+            5
+        );
+    }
+
+    #[test]
+    fn run_editor_should_place_markers_and_return_a_playable_position() {
+        let mut ui = RecordingUi::new(&["x center", "o a1", "x 9", "play", "next cpu", "play"]);
+        let (config, grid) = run_editor(&mut ui, &GameConfig::default())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(grid, from_array([[-1, 0, 0], [0, 1, 0], [0, 0, 1]]));
+        assert_eq!(config.player_to_move(&grid), Player::Cpu);
+        assert_eq!(
+            ui.messages(),
+            vec!["Invalid: X has one marker more, so O moves next"]
+        );
+    }
+
+    #[test]
+    fn run_editor_should_keep_blocked_cells_and_lines_of_the_config() {
+        let config = GameConfig {
+            topology: Topology::Torus,
+            obstacles: Obstacles::Center,
+            ..GameConfig::default()
+        };
+        let inputs = ["x 5", "clear 5", "x 1", "size 5", "clear", "o 1", "play"];
+        let mut ui = RecordingUi::new(&inputs);
+        let (_, grid) = run_editor(&mut ui, &config).unwrap().unwrap();
+
+        assert_eq!(grid.to_notation(), "O..../...../..#../...../.....");
+        assert_eq!(grid.lines(), grid::line_table(5, Topology::Torus));
+        assert_eq!(
+            ui.messages(),
+            vec!["Invalid: cell 5 is blocked", "Invalid: cell 5 is blocked"]
+        );
+    }
+
+    #[test]
+    fn run_editor_should_load_text_and_report_invalid_commands() {
+        let mut ui = RecordingUi::new(&["load XX./O../...", "size 12", "jump", "cancel"]);
+        let result = run_editor(&mut ui, &GameConfig::default()).unwrap();
+
+        assert_eq!(result, None);
+        assert_eq!(
+            ui.messages(),
+            vec![
+                "Invalid: the size must be between 3 and 9",
+                "Invalid: unknown editor command: jump"
+            ]
+        );
+        assert!(matches!(
+            run_editor(&mut RecordingUi::new(&[]), &GameConfig::default()),
+            Err(GameError::EndOfInput)
+        ));
+    }
+}
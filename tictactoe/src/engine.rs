@@ -0,0 +1,539 @@
+//! Line-delimited JSON protocol between the game and external engines.
+//!
+//! The game starts the engine as a subprocess and writes one JSON object per
+//! line on its stdin; the engine answers with one JSON object per line on its
+//! stdout. Anything the engine prints on stderr is left alone.
+//!
+//! ```text
+//! game   -> {"type":"hello","protocol":1}
+//! engine <- {"type":"ready","name":"my-bot"}
+//! game   -> {"type":"move","board":"X../.O./...","size":3,"to_move":"X","variant":"standard","time_limit_ms":1000}
+//! engine <- {"type":"info","message":"thinking"}            (optional, ignored)
+//! engine <- {"type":"move","row":0,"col":2,"comment":"fork"} (comment is optional)
+//! game   -> {"type":"quit"}
+//! ```
+//!
+//! `board` uses the grid notation: rows separated by `/`, `X`, `O` and `.`
+//! for an empty square. Rows and columns are counted from 0. An engine that
+//! answers late, plays a taken square or breaks the protocol forfeits the
+//! game; after a late answer the engine is restarted, so the next request
+//! never reads a reply meant for the previous one. Running
+//! `tictactoe --as-engine` serves the built-in CPU over the same protocol.
+
+use super::cpu::{CpuMove, CpuProfile, GameRng, MoveRule};
+use super::game::{GameConfig, RuleVariant};
+use super::grid::{Grid, GridChecker, Marker, Player};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const ENGINE_NAME: &str = "tictactoe";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Hello {
+        protocol: u32,
+    },
+    Move {
+        board: String,
+        size: usize,
+        to_move: String,
+        variant: String,
+        time_limit_ms: u64,
+    },
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Ready {
+        name: String,
+    },
+    Move {
+        row: i32,
+        col: i32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        comment: Option<String>,
+    },
+    Info {
+        message: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug)]
+pub enum EngineError {
+    Io(io::Error),
+    Timeout,
+    Closed,
+    Protocol(String),
+    IllegalMove((i32, i32)),
+}
+
+impl EngineError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            EngineError::Io(_) => "io",
+            EngineError::Timeout => "timeout",
+            EngineError::Closed => "closed",
+            EngineError::Protocol(_) => "protocol",
+            EngineError::IllegalMove(_) => "illegal_move",
+        }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Io(error) => write!(f, "engine i/o error: {}", error),
+            EngineError::Timeout => write!(f, "engine did not answer in time"),
+            EngineError::Closed => write!(f, "engine exited"),
+            EngineError::Protocol(message) => write!(f, "unexpected engine reply: {}", message),
+            EngineError::IllegalMove(position) => {
+                write!(f, "engine played an unavailable square {:?}", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<io::Error> for EngineError {
+    fn from(error: io::Error) -> EngineError {
+        EngineError::Io(error)
+    }
+}
+
+pub struct EngineProcess {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<io::Result<String>>,
+    command: String,
+    time_limit: Duration,
+    pub name: String,
+}
+
+impl EngineProcess {
+    // The command is split on whitespace, no shell is involved.
+    pub fn spawn(command: &str, time_limit: Duration) -> Result<EngineProcess, EngineError> {
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| EngineError::Protocol("empty engine command".to_owned()))?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or(EngineError::Closed)?;
+        let stdout = child.stdout.take().ok_or(EngineError::Closed)?;
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = EngineProcess {
+            child,
+            stdin,
+            lines,
+            command: command.to_owned(),
+            time_limit,
+            name: program.to_owned(),
+        };
+        engine.send(&Request::Hello {
+            protocol: PROTOCOL_VERSION,
+        })?;
+        match engine.receive(Instant::now() + HANDSHAKE_TIMEOUT)? {
+            Response::Ready { name } => engine.name = name,
+            other => return Err(EngineError::Protocol(format!("{:?}", other))),
+        }
+        Ok(engine)
+    }
+
+    fn send(&mut self, request: &Request) -> Result<(), EngineError> {
+        writeln!(
+            self.stdin,
+            "{}",
+            serde_json::to_string(request).map_err(io::Error::from)?
+        )?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn receive(&mut self, deadline: Instant) -> Result<Response, EngineError> {
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let line = match self.lines.recv_timeout(timeout) {
+                Ok(line) => line?,
+                Err(RecvTimeoutError::Timeout) => return Err(EngineError::Timeout),
+                Err(RecvTimeoutError::Disconnected) => return Err(EngineError::Closed),
+            };
+            match serde_json::from_str(&line) {
+                Ok(Response::Info { .. }) => continue,
+                Ok(response) => return Ok(response),
+                Err(_) if line.trim().is_empty() => continue,
+                Err(_) => return Err(EngineError::Protocol(line)),
+            }
+        }
+    }
+
+    // The timed out engine may still answer, so it is replaced by a fresh one.
+    fn restart(&mut self) {
+        match EngineProcess::spawn(&self.command, self.time_limit) {
+            Ok(engine) => *self = engine,
+            Err(_) => {
+                let _ = self.child.kill();
+            }
+        }
+    }
+
+    pub fn request_move(
+        &mut self,
+        grid: &Grid,
+        marker: Marker,
+        variant: RuleVariant,
+    ) -> Result<(i32, i32), EngineError> {
+        self.send(&Request::Move {
+            board: grid.to_notation(),
+            size: grid.size,
+            to_move: marker.glyph().to_string(),
+            variant: variant.name().to_owned(),
+            time_limit_ms: self.time_limit.as_millis() as u64,
+        })?;
+        let response = match self.receive(Instant::now() + self.time_limit) {
+            Err(EngineError::Timeout) => {
+                self.restart();
+                return Err(EngineError::Timeout);
+            }
+            response => response?,
+        };
+        match response {
+            Response::Move { row, col, .. }
+                if grid.grid.get(&(row, col)) == Some(&Marker::Null) =>
+            {
+                Ok((row, col))
+            }
+            Response::Move { row, col, .. } => Err(EngineError::IllegalMove((row, col))),
+            other => Err(EngineError::Protocol(format!("{:?}", other))),
+        }
+    }
+}
+
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        let _ = self.send(&Request::Quit);
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub enum Opponent {
+    Cpu(CpuProfile),
+    Engine(EngineProcess),
+}
+
+impl Opponent {
+    pub fn play<R: Rng>(
+        &mut self,
+        config: &GameConfig,
+        grid: &Grid,
+        player: Player,
+        rng: &mut R,
+    ) -> Result<(Grid, CpuMove), EngineError> {
+        match self {
+            Opponent::Cpu(profile) => Ok(config.make_cpu_move(grid, player, profile, rng)),
+            Opponent::Engine(engine) => {
+                let marker = config.marker_of(player);
+                let position = engine.request_move(grid, marker, config.variant)?;
+                let mut new_grid = grid.clone();
+                new_grid.insert(position, marker);
+                let cpu_move = CpuMove {
+                    position,
+                    rule: MoveRule::External,
+                };
+                Ok((new_grid, cpu_move))
+            }
+        }
+    }
+}
+
+fn answer_move(
+    board: &str,
+    to_move: &str,
+    variant: &str,
+    profile: &CpuProfile,
+    rng: &mut GameRng,
+) -> Response {
+    let error = |message: &str| Response::Error {
+        message: message.to_owned(),
+    };
+    let Some(grid) = Grid::from_notation(board) else {
+        return error("invalid board");
+    };
+    let marker = match to_move.to_uppercase().as_str() {
+        "X" => Marker::X,
+        "O" => Marker::O,
+        _ => return error("to_move must be X or O"),
+    };
+    let Some(variant) = RuleVariant::from_name(variant) else {
+        return error("unknown variant");
+    };
+    if !variant.is_grid_game() {
+        return error("this variant is not supported by the engine protocol");
+    }
+    let config = GameConfig {
+        size: grid.size,
+        human_marker: marker.opponent(),
+        variant,
+        profile: *profile,
+        ..GameConfig::default()
+    };
+    if config.is_over(&grid) {
+        return error("the game is already over");
+    }
+
+    let (_, cpu_move) = config.make_cpu_move(&grid, Player::Cpu, profile, rng);
+    Response::Move {
+        row: cpu_move.position.0,
+        col: cpu_move.position.1,
+        comment: Some(cpu_move.rule.to_string()),
+    }
+}
+
+pub fn run_engine<R: BufRead, W: Write>(
+    input: R,
+    output: &mut W,
+    profile: &CpuProfile,
+    rng: &mut GameRng,
+) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Hello { .. }) => Response::Ready {
+                name: ENGINE_NAME.to_owned(),
+            },
+            Ok(Request::Move {
+                board,
+                to_move,
+                variant,
+                ..
+            }) => answer_move(&board, &to_move, &variant, profile, rng),
+            Ok(Request::Quit) => return Ok(()),
+            Err(error) => Response::Error {
+                message: error.to_string(),
+            },
+        };
+        writeln!(output, "{}", serde_json::to_string(&response)?)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+// A shell script standing in for an engine, deleted with its state file
+// when dropped. The script gets the state file path as $1.
+#[cfg(test)]
+pub struct FakeEngine {
+    script: std::path::PathBuf,
+}
+
+#[cfg(test)]
+impl FakeEngine {
+    pub fn new(name: &str, script: &str) -> FakeEngine {
+        let path = std::env::temp_dir().join(format!(
+            "tictactoe_engine_{}_{}.sh",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, script).unwrap();
+        FakeEngine { script: path }
+    }
+
+    pub fn command(&self) -> String {
+        format!(
+            "sh {} {}",
+            self.script.display(),
+            self.script.with_extension("state").display()
+        )
+    }
+}
+
+#[cfg(test)]
+impl Drop for FakeEngine {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.script);
+        let _ = std::fs::remove_file(self.script.with_extension("state"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::{seeded_rng, Personality};
+    use super::super::grid::from_array;
+    use super::*;
+
+    fn engine_session(input: &str) -> Vec<Response> {
+        let mut output = Vec::new();
+        let profile = CpuProfile::with_strength(Personality::Aggressive, 100);
+        run_engine(input.as_bytes(), &mut output, &profile, &mut seeded_rng(0)).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn requests_should_serialize_as_tagged_json_lines() {
+        let request = Request::Move {
+            board: "X../.O./...".to_owned(),
+            size: 3,
+            to_move: "X".to_owned(),
+            variant: "standard".to_owned(),
+            time_limit_ms: 500,
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"type":"move","board":"X../.O./...","size":3,"to_move":"X","variant":"standard","time_limit_ms":500}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Response>(r#"{"type":"move","row":0,"col":2}"#).unwrap(),
+            Response::Move {
+                row: 0,
+                col: 2,
+                comment: None
+            }
+        );
+    }
+
+    #[test]
+    fn run_engine_should_answer_handshake_and_moves_until_quit() {
+        let responses = engine_session(concat!(
+            r#"{"type":"hello","protocol":1}"#,
+            "\n",
+            r#"{"type":"move","board":"OO./XX./...","size":3,"to_move":"X","variant":"standard","time_limit_ms":100}"#,
+            "\n",
+            r#"{"type":"quit"}"#,
+            "\n",
+            r#"{"type":"hello","protocol":1}"#,
+            "\n",
+        ));
+        assert_eq!(
+            responses,
+            vec![
+                Response::Ready {
+                    name: ENGINE_NAME.to_owned()
+                },
+                Response::Move {
+                    row: 1,
+                    col: 2,
+                    comment: Some(MoveRule::Win.to_string())
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn run_engine_should_report_invalid_requests() {
+        let responses = engine_session(concat!(
+            "not json\n",
+            r#"{"type":"move","board":"XXX/OO./...","size":3,"to_move":"O","variant":"standard","time_limit_ms":100}"#,
+            "\n",
+            r#"{"type":"move","board":"X../.../...","size":3,"to_move":"O","variant":"gomoku","time_limit_ms":100}"#,
+            "\n",
+            r#"{"type":"move","board":"X../.../...","size":3,"to_move":"O","variant":"phantom","time_limit_ms":100}"#,
+            "\n",
+        ));
+        assert_eq!(responses.len(), 4);
+        assert!(responses
+            .iter()
+            .all(|response| matches!(response, Response::Error { .. })));
+    }
+
+    #[test]
+    fn engine_process_should_play_moves_from_subprocess() {
+        let fake = FakeEngine::new(
+            "center",
+            r#"read line
+echo '{"type":"ready","name":"center-bot"}'
+read line
+echo '{"type":"info","message":"thinking"}'
+echo '{"type":"move","row":1,"col":1}'
+read line
+"#,
+        );
+        let engine = EngineProcess::spawn(&fake.command(), Duration::from_secs(2)).unwrap();
+        assert_eq!(engine.name, "center-bot");
+
+        let config = GameConfig::default();
+        let grid = from_array([[1, 0, 0], [0, 0, 0], [0, 0, 0]]);
+        let mut opponent = Opponent::Engine(engine);
+        let (new_grid, cpu_move) = opponent
+            .play(&config, &grid, Player::Cpu, &mut seeded_rng(0))
+            .unwrap();
+        assert_eq!(cpu_move.rule, MoveRule::External);
+        assert_eq!(new_grid.grid.get(&(1, 1)), Some(&Marker::O));
+    }
+
+    #[test]
+    fn engine_process_should_time_out_and_reject_taken_squares() {
+        // The first run answers late with a free square, the restarted one
+        // at once with a taken square.
+        let fake = FakeEngine::new(
+            "slow",
+            r#"read line
+echo '{"type":"ready","name":"slow-bot"}'
+read line
+if [ -e "$1" ]; then
+echo '{"type":"move","row":0,"col":0}'
+else
+touch "$1"
+sleep 0.3
+echo '{"type":"move","row":2,"col":2}'
+fi
+read line
+"#,
+        );
+        let mut engine = EngineProcess::spawn(&fake.command(), Duration::from_millis(50)).unwrap();
+        let grid = from_array([[1, 0, 0], [0, 0, 0], [0, 0, 0]]);
+
+        let result = engine.request_move(&grid, Marker::O, RuleVariant::Standard);
+        assert!(matches!(result, Err(EngineError::Timeout)));
+
+        engine.time_limit = Duration::from_secs(2);
+        let result = engine.request_move(&grid, Marker::O, RuleVariant::Standard);
+        assert!(matches!(result, Err(EngineError::IllegalMove((0, 0)))));
+    }
+
+    #[test]
+    fn engine_process_should_fail_when_engine_does_not_speak_protocol() {
+        let fake = FakeEngine::new("mute", "read line\necho hello\n");
+        let result = EngineProcess::spawn(&fake.command(), Duration::from_secs(1));
+        assert!(matches!(result, Err(EngineError::Protocol(_))));
+    }
+}
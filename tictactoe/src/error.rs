@@ -0,0 +1,55 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum GameError {
+    EndOfInput,
+    Interrupted,
+    Timeout,
+    Io(io::Error),
+}
+
+pub type GameResult<T> = Result<T, GameError>;
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::EndOfInput => write!(f, "input was closed"),
+            GameError::Interrupted => write!(f, "interrupted by the user"),
+            GameError::Timeout => write!(f, "ran out of time"),
+            GameError::Io(error) => write!(f, "unable to read input: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+impl From<io::Error> for GameError {
+    fn from(error: io::Error) -> GameError {
+        match error.kind() {
+            io::ErrorKind::Interrupted => GameError::Interrupted,
+            io::ErrorKind::UnexpectedEof => GameError::EndOfInput,
+            _ => GameError::Io(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_should_map_to_game_errors() {
+        let interrupted = io::Error::new(io::ErrorKind::Interrupted, "ctrl-c");
+        assert!(matches!(
+            GameError::from(interrupted),
+            GameError::Interrupted
+        ));
+
+        let eof = io::Error::new(io::ErrorKind::UnexpectedEof, "closed");
+        assert!(matches!(GameError::from(eof), GameError::EndOfInput));
+
+        let other = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        assert!(matches!(GameError::from(other), GameError::Io(_)));
+    }
+}
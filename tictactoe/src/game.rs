@@ -0,0 +1,402 @@
+use super::clock::TimeControl;
+use super::cpu::{self, CpuMove, CpuProfile};
+use super::grid::{self, Grid, GridChecker, Marker, Player, Topology};
+use super::order_chaos;
+
+use itertools::Itertools;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RuleVariant {
+    Standard,
+    Misere,
+    Phantom,
+    Quantum,
+    Sliding,
+    Notakto,
+    OrderChaos,
+    Numerical,
+    Gomoku,
+    Vanishing,
+}
+
+impl RuleVariant {
+    pub fn from_name(name: &str) -> Option<RuleVariant> {
+        match name.trim().to_lowercase().as_str() {
+            "standard" | "classic" => Some(RuleVariant::Standard),
+            "misere" | "misère" => Some(RuleVariant::Misere),
+            "phantom" | "fog" => Some(RuleVariant::Phantom),
+            "quantum" => Some(RuleVariant::Quantum),
+            "sliding" | "morris" => Some(RuleVariant::Sliding),
+            "notakto" => Some(RuleVariant::Notakto),
+            "order-chaos" | "order-and-chaos" => Some(RuleVariant::OrderChaos),
+            "numerical" | "sum15" => Some(RuleVariant::Numerical),
+            "gomoku" | "five" => Some(RuleVariant::Gomoku),
+            "vanishing" | "infinite" => Some(RuleVariant::Vanishing),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuleVariant::Standard => "standard",
+            RuleVariant::Misere => "misere",
+            RuleVariant::Phantom => "phantom",
+            RuleVariant::Quantum => "quantum",
+            RuleVariant::Sliding => "sliding",
+            RuleVariant::Notakto => "notakto",
+            RuleVariant::OrderChaos => "order-chaos",
+            RuleVariant::Numerical => "numerical",
+            RuleVariant::Gomoku => "gomoku",
+            RuleVariant::Vanishing => "vanishing",
+        }
+    }
+
+    // Whether every turn places one marker on a board that both players can
+    // see, which engines, scripts and the server assume.
+    pub fn is_grid_game(&self) -> bool {
+        matches!(self, RuleVariant::Standard | RuleVariant::Misere)
+    }
+
+    // The board size a variant always plays on, 0 for an edgeless board.
+    // Variants without one use the configured size.
+    pub fn fixed_size(&self) -> Option<usize> {
+        match self {
+            RuleVariant::Standard | RuleVariant::Misere => None,
+            RuleVariant::Phantom
+            | RuleVariant::Quantum
+            | RuleVariant::Sliding
+            | RuleVariant::Notakto
+            | RuleVariant::Numerical
+            | RuleVariant::Vanishing => Some(3),
+            RuleVariant::OrderChaos => Some(order_chaos::BOARD_SIZE),
+            RuleVariant::Gomoku => Some(0),
+        }
+    }
+
+    // The lines that win a game of this variant. Gomoku has none to list on
+    // its edgeless board, a five is looked for around each new stone.
+    pub fn lines(&self, config: &GameConfig) -> Vec<Vec<(i32, i32)>> {
+        match self {
+            RuleVariant::OrderChaos => {
+                grid::run_table(order_chaos::BOARD_SIZE, order_chaos::RUN_LENGTH)
+            }
+            RuleVariant::Gomoku => Vec::new(),
+            _ => grid::line_table(self.fixed_size().unwrap_or(config.size), config.topology),
+        }
+    }
+
+    // Whether the variant plays on the configured grid, so that topologies
+    // and obstacles apply to it.
+    pub fn uses_config_board(&self) -> bool {
+        matches!(
+            self,
+            RuleVariant::Standard
+                | RuleVariant::Misere
+                | RuleVariant::Sliding
+                | RuleVariant::Vanishing
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Obstacles {
+    #[default]
+    None,
+    Center,
+    Corners,
+    // Cells drawn from an rng seeded with `seed`, so a layout can be replayed.
+    Random {
+        count: usize,
+        seed: u64,
+    },
+}
+
+impl Obstacles {
+    pub fn from_name(name: &str) -> Option<Obstacles> {
+        match name.trim().to_lowercase().as_str() {
+            "none" => Some(Obstacles::None),
+            "center" | "centre" => Some(Obstacles::Center),
+            "corners" => Some(Obstacles::Corners),
+            _ => None,
+        }
+    }
+
+    // The blocked cells on a board of `size`. Even boards block their
+    // central 2x2 square.
+    pub fn cells(&self, size: usize) -> Vec<(i32, i32)> {
+        let last = size as i32 - 1;
+        match self {
+            Obstacles::None => Vec::new(),
+            Obstacles::Center => {
+                let (low, high) = (last / 2, (last + 1) / 2);
+                (low..=high).cartesian_product(low..=high).collect()
+            }
+            Obstacles::Corners => vec![(0, 0), (0, last), (last, 0), (last, last)],
+            Obstacles::Random { count, seed } => (0..=last)
+                .cartesian_product(0..=last)
+                .choose_multiple(&mut cpu::seeded_rng(*seed), *count)
+                .into_iter()
+                .sorted()
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GameConfig {
+    pub size: usize,
+    pub human_marker: Marker,
+    pub first_player: Player,
+    pub variant: RuleVariant,
+    pub profile: CpuProfile,
+    pub time_control: TimeControl,
+    pub tutor: bool,
+    // Number of boards in a Notakto game.
+    pub boards: usize,
+    pub topology: Topology,
+    pub obstacles: Obstacles,
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            size: 3,
+            human_marker: Marker::X,
+            first_player: Player::Human,
+            variant: RuleVariant::Standard,
+            profile: CpuProfile::default(),
+            time_control: TimeControl::default(),
+            tutor: false,
+            boards: 3,
+            topology: Topology::Flat,
+            obstacles: Obstacles::None,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn new_grid(&self) -> Grid {
+        let size = self.variant.fixed_size().unwrap_or(self.size);
+        let mut grid = grid::create_grid_of_size(size).with_lines(self.variant.lines(self));
+        for position in self.obstacles.cells(size) {
+            grid.insert(position, Marker::Blocked);
+        }
+        grid
+    }
+
+    pub fn marker_of(&self, player: Player) -> Marker {
+        match player {
+            Player::Human => self.human_marker,
+            Player::Cpu => self.human_marker.opponent(),
+        }
+    }
+
+    pub fn player_of(&self, marker: Marker) -> Option<Player> {
+        [Player::Human, Player::Cpu]
+            .into_iter()
+            .find(|player| self.marker_of(*player) == marker)
+    }
+
+    pub fn player_to_move(&self, grid: &Grid) -> Player {
+        let first_marker = self.marker_of(self.first_player);
+        if grid.count_markers(first_marker) <= grid.count_markers(first_marker.opponent()) {
+            self.first_player
+        } else {
+            self.first_player.opponent()
+        }
+    }
+
+    pub fn is_over(&self, grid: &Grid) -> bool {
+        grid.is_grid_full() || grid.winning_marker().is_some()
+    }
+
+    pub fn winner(&self, grid: &Grid) -> Option<Player> {
+        let line_marker = grid.winning_marker()?;
+        match self.variant {
+            RuleVariant::Standard
+            | RuleVariant::Phantom
+            | RuleVariant::Quantum
+            | RuleVariant::Sliding
+            | RuleVariant::Notakto
+            | RuleVariant::OrderChaos
+            | RuleVariant::Numerical
+            | RuleVariant::Gomoku
+            | RuleVariant::Vanishing => self.player_of(line_marker),
+            RuleVariant::Misere => self.player_of(line_marker.opponent()),
+        }
+    }
+
+    pub fn make_cpu_move<R: Rng>(
+        &self,
+        grid: &Grid,
+        player: Player,
+        profile: &CpuProfile,
+        rng: &mut R,
+    ) -> (Grid, CpuMove) {
+        let marker = self.marker_of(player);
+        match self.variant {
+            RuleVariant::Standard
+            | RuleVariant::Phantom
+            | RuleVariant::Quantum
+            | RuleVariant::Sliding
+            | RuleVariant::Notakto
+            | RuleVariant::OrderChaos
+            | RuleVariant::Numerical
+            | RuleVariant::Gomoku
+            | RuleVariant::Vanishing => cpu::make_cpu_move(grid, marker, profile, rng),
+            RuleVariant::Misere => {
+                let cpu_move = cpu::choose_misere_move(grid, &marker, profile, rng);
+                let mut new_grid = grid.clone();
+                new_grid.insert(cpu_move.position, marker);
+                (new_grid, cpu_move)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::grid::from_array;
+    use super::*;
+
+    #[test]
+    fn rule_variant_should_parse_names() {
+        assert_eq!(
+            RuleVariant::from_name("Standard"),
+            Some(RuleVariant::Standard)
+        );
+        assert_eq!(RuleVariant::from_name("misere"), Some(RuleVariant::Misere));
+        assert_eq!(RuleVariant::from_name("connect-four"), None);
+        assert_eq!(RuleVariant::from_name("fog"), Some(RuleVariant::Phantom));
+        assert!(!RuleVariant::Phantom.is_grid_game());
+        assert_eq!(RuleVariant::Standard.fixed_size(), None);
+        assert_eq!(RuleVariant::Vanishing.fixed_size(), Some(3));
+        assert_eq!(RuleVariant::OrderChaos.fixed_size(), Some(6));
+        assert_eq!(RuleVariant::Gomoku.fixed_size(), Some(0));
+        for variant in [
+            RuleVariant::Standard,
+            RuleVariant::Misere,
+            RuleVariant::Phantom,
+            RuleVariant::Quantum,
+            RuleVariant::Sliding,
+            RuleVariant::Notakto,
+            RuleVariant::OrderChaos,
+            RuleVariant::Numerical,
+            RuleVariant::Gomoku,
+            RuleVariant::Vanishing,
+        ] {
+            assert_eq!(RuleVariant::from_name(variant.name()), Some(variant));
+        }
+    }
+
+    #[test]
+    fn obstacles_should_lay_out_presets_and_replay_seeds() {
+        assert_eq!(Obstacles::from_name("Corners"), Some(Obstacles::Corners));
+        assert_eq!(Obstacles::from_name("walls"), None);
+        assert_eq!(Obstacles::Center.cells(3), vec![(1, 1)]);
+        assert_eq!(
+            Obstacles::Center.cells(4),
+            vec![(1, 1), (1, 2), (2, 1), (2, 2)]
+        );
+        assert_eq!(
+            Obstacles::Corners.cells(5),
+            vec![(0, 0), (0, 4), (4, 0), (4, 4)]
+        );
+
+        let random = Obstacles::Random { count: 3, seed: 7 };
+        let cells = random.cells(5);
+        assert_eq!(cells.len(), 3);
+        assert_eq!(cells, random.cells(5));
+        assert_ne!(cells, Obstacles::Random { count: 3, seed: 8 }.cells(5));
+    }
+
+    #[test]
+    fn new_grid_should_apply_topology_and_obstacles() {
+        let config = GameConfig {
+            topology: Topology::Torus,
+            obstacles: Obstacles::Center,
+            ..GameConfig::default()
+        };
+        let grid = config.new_grid();
+        assert_eq!(grid.lines(), grid::line_table(3, Topology::Torus));
+        assert_eq!(grid.to_notation(), ".../.#./...");
+        assert_eq!(grid.extract_empty_positions().len(), 8);
+        assert!(RuleVariant::Vanishing.uses_config_board());
+        assert!(!RuleVariant::Gomoku.uses_config_board());
+    }
+
+    #[test]
+    fn new_grid_should_take_the_lines_of_the_variant() {
+        let config = GameConfig {
+            variant: RuleVariant::OrderChaos,
+            ..GameConfig::default()
+        };
+        let grid = config.new_grid();
+        assert_eq!(grid.size, 6);
+        assert_eq!(grid.lines().len(), 32);
+
+        let config = GameConfig {
+            variant: RuleVariant::Gomoku,
+            ..GameConfig::default()
+        };
+        let grid = config.new_grid();
+        assert!(grid.grid.is_empty());
+        assert!(grid.lines().is_empty());
+    }
+
+    #[test]
+    fn markers_should_follow_the_human_choice() {
+        let config = GameConfig {
+            human_marker: Marker::O,
+            ..GameConfig::default()
+        };
+        assert_eq!(config.marker_of(Player::Human), Marker::O);
+        assert_eq!(config.marker_of(Player::Cpu), Marker::X);
+        assert_eq!(config.player_of(Marker::X), Some(Player::Cpu));
+        assert_eq!(config.player_of(Marker::Null), None);
+    }
+
+    #[test]
+    fn player_to_move_should_start_with_first_player_then_alternate() {
+        let config = GameConfig {
+            human_marker: Marker::O,
+            first_player: Player::Cpu,
+            ..GameConfig::default()
+        };
+        let grid = config.new_grid();
+        assert_eq!(config.player_to_move(&grid), Player::Cpu);
+
+        let grid = from_array([[1, 0, 0], [0, 0, 0], [0, 0, 0]]);
+        assert_eq!(config.player_to_move(&grid), Player::Human);
+    }
+
+    #[test]
+    fn winner_should_depend_on_variant() {
+        let grid = from_array([[1, 1, 1], [-1, -1, 0], [0, 0, 0]]);
+        let standard = GameConfig::default();
+        let misere = GameConfig {
+            variant: RuleVariant::Misere,
+            ..GameConfig::default()
+        };
+        assert_eq!(standard.winner(&grid), Some(Player::Human));
+        assert_eq!(misere.winner(&grid), Some(Player::Cpu));
+        assert!(misere.is_over(&grid));
+    }
+
+    #[test]
+    fn make_cpu_move_should_play_the_cpu_marker() {
+        let config = GameConfig {
+            human_marker: Marker::O,
+            ..GameConfig::default()
+        };
+        let (grid, cpu_move) = config.make_cpu_move(
+            &config.new_grid(),
+            Player::Cpu,
+            &config.profile,
+            &mut cpu::seeded_rng(0),
+        );
+        assert_eq!(grid.grid.get(&cpu_move.position), Some(&Marker::X));
+    }
+}
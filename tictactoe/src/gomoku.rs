@@ -0,0 +1,493 @@
+//! Gomoku without edges: five in a row on an unbounded board. Stones live in
+//! a sparse `Grid`, any cell close to an existing stone is playable, and the
+//! screen only shows a scrolling window around the action.
+
+use super::cpu::CpuProfile;
+use super::error::GameResult;
+use super::game::GameConfig;
+use super::grid::{self, Grid, GridChecker, Marker, Player};
+use super::input;
+use super::ui::Ui;
+
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+pub const RUN_LENGTH: usize = 5;
+// How far from the nearest stone a new stone may land.
+const REACH: i32 = 2;
+const MIN_VIEW: usize = 7;
+const MAX_VIEW: usize = 15;
+const SCROLL_STEP: i32 = 3;
+const MAX_STONES: usize = 400;
+const SEARCH_DEPTH: usize = 6;
+const DEFENCE_CANDIDATES: usize = 12;
+const RUN_WEIGHTS: [u64; RUN_LENGTH] = [1, 10, 100, 1_000, 100_000];
+
+pub fn playable_cells(grid: &Grid) -> Vec<(i32, i32)> {
+    if grid.grid.is_empty() {
+        return vec![(0, 0)];
+    }
+    grid.grid
+        .keys()
+        .flat_map(|(row, col)| {
+            (-REACH..=REACH)
+                .cartesian_product(-REACH..=REACH)
+                .map(move |(d_row, d_col)| (row + d_row, col + d_col))
+        })
+        .filter(|cell| !grid.grid.contains_key(cell))
+        .sorted()
+        .dedup()
+        .collect()
+}
+
+fn with_stone(grid: &Grid, cell: (i32, i32), marker: Marker) -> Grid {
+    let mut after = grid.clone();
+    after.insert(cell, marker);
+    after
+}
+
+// Cells that would give `marker` five in a row right away.
+pub fn winning_cells(grid: &Grid, marker: Marker) -> Vec<(i32, i32)> {
+    playable_cells(grid)
+        .into_iter()
+        .filter(|cell| {
+            with_stone(grid, *cell, marker)
+                .run_through(*cell, RUN_LENGTH)
+                .is_some()
+        })
+        .collect()
+}
+
+// Threat-space search over fours: every attacking move must leave a five
+// threat, so the defence is forced, until a double threat cannot be stopped.
+// Returns the first move of such a sequence.
+pub fn find_forced_win(grid: &Grid, marker: Marker, depth: usize) -> Option<(i32, i32)> {
+    if depth == 0 {
+        return None;
+    }
+    playable_cells(grid).into_iter().find(|cell| {
+        let after = with_stone(grid, *cell, marker);
+        match winning_cells(&after, marker)[..] {
+            [] => false,
+            [block] => {
+                let defended = with_stone(&after, block, marker.opponent());
+                winning_cells(&defended, marker.opponent()).is_empty()
+                    && find_forced_win(&defended, marker, depth - 1).is_some()
+            }
+            _ => true,
+        }
+    })
+}
+
+// Sums the weight of every window of five through the cell that is still
+// free of the other colour, for the attacker and a little less for the
+// defender.
+fn cell_score(grid: &Grid, cell: (i32, i32), marker: Marker) -> u64 {
+    let length = RUN_LENGTH as i32;
+    [(0, 1), (1, 0), (1, 1), (1, -1)]
+        .into_iter()
+        .cartesian_product(0..length)
+        .map(|((d_row, d_col), offset)| {
+            let window = (0..length)
+                .map(|step| {
+                    let shift = step - offset;
+                    grid.grid
+                        .get(&(cell.0 + shift * d_row, cell.1 + shift * d_col))
+                })
+                .collect::<Vec<_>>();
+            let count = |side: Marker| window.iter().filter(|stone| **stone == Some(&side)).count();
+            match (count(marker), count(marker.opponent())) {
+                (own, 0) => RUN_WEIGHTS[own] * 10,
+                (0, theirs) => RUN_WEIGHTS[theirs] * 9,
+                _ => 0,
+            }
+        })
+        .sum()
+}
+
+fn ranked_cells(grid: &Grid, marker: Marker) -> Vec<(i32, i32)> {
+    playable_cells(grid)
+        .into_iter()
+        .sorted_by_key(|cell| std::cmp::Reverse(cell_score(grid, *cell, marker)))
+        .collect()
+}
+
+pub fn choose_gomoku_move<R: Rng>(
+    grid: &Grid,
+    marker: Marker,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> (i32, i32) {
+    let cells = playable_cells(grid);
+    if rng.gen_bool(profile.blunder_rate.clamp(0.0, 1.0)) {
+        return *cells.choose(rng).expect("Gomoku boards never fill up");
+    }
+    if let Some(cell) = winning_cells(grid, marker).first() {
+        return *cell;
+    }
+    if let Some(cell) = winning_cells(grid, marker.opponent()).first() {
+        return *cell;
+    }
+    if let Some(cell) = find_forced_win(grid, marker, SEARCH_DEPTH) {
+        return cell;
+    }
+    let ranked = ranked_cells(grid, marker);
+    if find_forced_win(grid, marker.opponent(), SEARCH_DEPTH).is_some() {
+        if let Some(cell) = ranked.iter().take(DEFENCE_CANDIDATES).find(|cell| {
+            find_forced_win(
+                &with_stone(grid, **cell, marker),
+                marker.opponent(),
+                SEARCH_DEPTH,
+            )
+            .is_none()
+        }) {
+            return *cell;
+        }
+    }
+    ranked[0]
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    pub top: i32,
+    pub left: i32,
+    pub side: usize,
+}
+
+impl Viewport {
+    // Frames every stone with a margin when they fit on screen, otherwise
+    // crops to a window centred on the focus.
+    pub fn follow(grid: &Grid, focus: Option<(i32, i32)>) -> Viewport {
+        let (rows, cols): (Vec<i32>, Vec<i32>) = grid.grid.keys().copied().unzip();
+        let span = |values: &[i32]| match values.iter().minmax().into_option() {
+            Some((low, high)) => (low - REACH, high + REACH),
+            None => (0, 0),
+        };
+        let (row_span, col_span) = (span(&rows), span(&cols));
+        let extent = |(low, high): (i32, i32)| (high - low + 1) as usize;
+        let side = extent(row_span)
+            .max(extent(col_span))
+            .clamp(MIN_VIEW, MAX_VIEW);
+        let centre = |(low, high): (i32, i32), focus: Option<i32>| {
+            if extent((low, high)) <= side {
+                low - (side - extent((low, high))) as i32 / 2
+            } else {
+                focus.unwrap_or((low + high) / 2) - side as i32 / 2
+            }
+        };
+        Viewport {
+            top: centre(row_span, focus.map(|cell| cell.0)),
+            left: centre(col_span, focus.map(|cell| cell.1)),
+            side,
+        }
+    }
+
+    pub fn scrolled(self, d_row: i32, d_col: i32) -> Viewport {
+        Viewport {
+            top: self.top + d_row,
+            left: self.left + d_col,
+            ..self
+        }
+    }
+
+    pub fn contains(&self, (row, col): (i32, i32)) -> bool {
+        let side = self.side as i32;
+        (self.top..self.top + side).contains(&row) && (self.left..self.left + side).contains(&col)
+    }
+
+    pub fn to_board(self, (row, col): (i32, i32)) -> (i32, i32) {
+        (self.top + row, self.left + col)
+    }
+
+    // Highlighted stones are bracketed, columns are lettered and rows
+    // numbered from the top of the window.
+    pub fn render(&self, grid: &Grid, highlighted: &[(i32, i32)]) -> String {
+        let header = (0..self.side)
+            .map(|col| format!(" {} ", (b'a' + col as u8) as char))
+            .join("");
+        let rows = (0..self.side as i32).map(|row| {
+            let cells = (0..self.side as i32)
+                .map(|col| {
+                    let cell = self.to_board((row, col));
+                    let glyph = grid
+                        .grid
+                        .get(&cell)
+                        .copied()
+                        .unwrap_or(Marker::Null)
+                        .glyph();
+                    if highlighted.contains(&cell) {
+                        format!("[{}]", glyph)
+                    } else {
+                        format!(" {} ", glyph)
+                    }
+                })
+                .join("");
+            format!("{:>2} {}", row + 1, cells)
+        });
+        let hidden = grid
+            .grid
+            .keys()
+            .filter(|cell| !self.contains(**cell))
+            .count();
+        let mut lines = vec![format!("   {}", header)];
+        lines.extend(rows);
+        match hidden {
+            0 => {}
+            1 => lines.push("(1 stone outside the view)".to_string()),
+            _ => lines.push(format!("({} stones outside the view)", hidden)),
+        }
+        lines.join("\n")
+    }
+}
+
+fn prompt_for_stone<U: Ui + ?Sized>(
+    grid: &Grid,
+    last_move: Option<(i32, i32)>,
+    ui: &mut U,
+) -> GameResult<(i32, i32)> {
+    let playable = playable_cells(grid);
+    let mut view = Viewport::follow(grid, last_move);
+    loop {
+        ui.show_message(&view.render(grid, &last_move.into_iter().collect::<Vec<_>>()));
+        let answer =
+            ui.prompt("Please place your stone, e.g. c4, or scroll with up, down, left or right:")?;
+        view = match answer.trim().to_lowercase().as_str() {
+            "up" => view.scrolled(-SCROLL_STEP, 0),
+            "down" => view.scrolled(SCROLL_STEP, 0),
+            "left" => view.scrolled(0, -SCROLL_STEP),
+            "right" => view.scrolled(0, SCROLL_STEP),
+            _ => {
+                match input::parse_coordinates(&answer, view.side).map(|cell| view.to_board(cell)) {
+                    Ok(cell) if playable.contains(&cell) => return Ok(cell),
+                    Ok(cell) if grid.grid.contains_key(&cell) => {
+                        ui.show_message("That cell is already taken.")
+                    }
+                    Ok(_) => ui.show_message("That cell is too far from the other stones."),
+                    Err(error) => ui.show_message(&error.to_string()),
+                }
+                view
+            }
+        };
+    }
+}
+
+pub fn play_gomoku<U: Ui + ?Sized, R: Rng>(
+    config: &GameConfig,
+    ui: &mut U,
+    rng: &mut R,
+) -> GameResult<Option<Player>> {
+    ui.show_message(&format!(
+        "Starting gomoku! The board has no edges, get {} in a row to win.",
+        RUN_LENGTH
+    ));
+    let mut grid = grid::create_unbounded_grid();
+    let mut player = config.first_player;
+    let mut last_move = None;
+
+    while grid.grid.len() < MAX_STONES {
+        let marker = config.marker_of(player);
+        let cell = match player {
+            Player::Human => prompt_for_stone(&grid, last_move, ui)?,
+            Player::Cpu => {
+                let cell = choose_gomoku_move(&grid, marker, &config.profile, rng);
+                ui.show_message("The CPU has played.");
+                cell
+            }
+        };
+        grid.insert(cell, marker);
+        last_move = Some(cell);
+        if let Some(run) = grid.run_through(cell, RUN_LENGTH) {
+            ui.show_message(&Viewport::follow(&grid, last_move).render(&grid, &run));
+            return Ok(Some(player));
+        }
+        player = player.opponent();
+    }
+    ui.show_message(&Viewport::follow(&grid, last_move).render(&grid, &[]));
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::{seeded_rng, Personality};
+    use super::*;
+    use crate::ui::RecordingUi;
+
+    fn stones(cells: &[((i32, i32), Marker)]) -> Grid {
+        let mut grid = grid::create_unbounded_grid();
+        for (cell, marker) in cells {
+            grid.insert(*cell, *marker);
+        }
+        grid
+    }
+
+    fn strong() -> CpuProfile {
+        CpuProfile::with_strength(Personality::Defensive, 100)
+    }
+
+    #[test]
+    fn playable_cells_should_surround_existing_stones() {
+        assert_eq!(playable_cells(&grid::create_unbounded_grid()), vec![(0, 0)]);
+        let grid = stones(&[((-10, 4), Marker::X)]);
+        let cells = playable_cells(&grid);
+        assert_eq!(cells.len(), 24);
+        assert!(cells.contains(&(-12, 2)) && cells.contains(&(-8, 6)));
+        assert!(!cells.contains(&(-10, 4)) && !cells.contains(&(-7, 4)));
+    }
+
+    #[test]
+    fn winning_cells_should_find_gaps_and_ends() {
+        let grid = stones(&[
+            ((3, -1), Marker::O),
+            ((3, 0), Marker::O),
+            ((3, 2), Marker::O),
+            ((3, 3), Marker::O),
+            ((0, 0), Marker::X),
+        ]);
+        assert_eq!(winning_cells(&grid, Marker::O), vec![(3, 1)]);
+        assert!(winning_cells(&grid, Marker::X).is_empty());
+    }
+
+    #[test]
+    fn choose_gomoku_move_should_win_then_block() {
+        let four = stones(&[
+            ((0, 0), Marker::X),
+            ((0, 1), Marker::X),
+            ((0, 2), Marker::X),
+            ((0, 3), Marker::X),
+            ((1, 0), Marker::O),
+        ]);
+        assert!([(0, -1), (0, 4)].contains(&choose_gomoku_move(
+            &four,
+            Marker::X,
+            &strong(),
+            &mut seeded_rng(1)
+        )));
+
+        let mut blocked = four.clone();
+        blocked.insert((0, -1), Marker::O);
+        assert_eq!(
+            choose_gomoku_move(&blocked, Marker::O, &strong(), &mut seeded_rng(1)),
+            (0, 4)
+        );
+    }
+
+    #[test]
+    fn find_forced_win_should_chain_fours_into_a_double_threat() {
+        // The four at (0, 4) forces a block at (0, 5), then (1, 4) makes an
+        // open four on the column.
+        let grid = stones(&[
+            ((0, 0), Marker::O),
+            ((0, 1), Marker::X),
+            ((0, 2), Marker::X),
+            ((0, 3), Marker::X),
+            ((2, 4), Marker::X),
+            ((3, 4), Marker::X),
+        ]);
+        assert_eq!(find_forced_win(&grid, Marker::X, 1), None);
+        assert_eq!(find_forced_win(&grid, Marker::X, 2), Some((0, 4)));
+        assert_eq!(find_forced_win(&grid, Marker::O, SEARCH_DEPTH), None);
+    }
+
+    #[test]
+    fn choose_gomoku_move_should_spoil_a_forced_win() {
+        let grid = stones(&[
+            ((0, 0), Marker::X),
+            ((0, 1), Marker::X),
+            ((0, 2), Marker::X),
+            ((1, 1), Marker::O),
+            ((2, 2), Marker::O),
+            ((-5, 0), Marker::O),
+        ]);
+        assert!(find_forced_win(&grid, Marker::X, SEARCH_DEPTH).is_some());
+        let cell = choose_gomoku_move(&grid, Marker::O, &strong(), &mut seeded_rng(3));
+        let after = with_stone(&grid, cell, Marker::O);
+        assert_eq!(find_forced_win(&after, Marker::X, SEARCH_DEPTH), None);
+    }
+
+    #[test]
+    fn viewport_should_frame_small_games_and_crop_large_ones() {
+        let empty = Viewport::follow(&grid::create_unbounded_grid(), None);
+        assert_eq!(
+            empty,
+            Viewport {
+                top: -3,
+                left: -3,
+                side: 7
+            }
+        );
+
+        let spread = stones(&[((0, 0), Marker::X), ((0, 30), Marker::O)]);
+        let view = Viewport::follow(&spread, Some((0, 30)));
+        assert_eq!(
+            view,
+            Viewport {
+                top: -7,
+                left: 23,
+                side: 15
+            }
+        );
+        assert!(view.contains((0, 30)) && !view.contains((0, 0)));
+        assert_eq!(view.scrolled(3, -3).to_board((0, 0)), (-4, 20));
+    }
+
+    #[test]
+    fn render_should_letter_columns_and_bracket_highlights() {
+        let grid = stones(&[
+            ((0, 0), Marker::X),
+            ((1, 1), Marker::O),
+            ((40, 40), Marker::X),
+        ]);
+        let view = Viewport {
+            top: -1,
+            left: -1,
+            side: 3,
+        };
+        assert_eq!(
+            view.render(&grid, &[(1, 1)]),
+            "    a  b  c \n 1  .  .  . \n 2  .  X  . \n 3  .  . [O]\n(1 stone outside the view)"
+        );
+
+        let grid = stones(&[
+            ((1, 1), Marker::O),
+            ((40, 40), Marker::X),
+            ((-9, 3), Marker::O),
+        ]);
+        assert!(view
+            .render(&grid, &[])
+            .ends_with("\n(2 stones outside the view)"));
+    }
+
+    #[test]
+    fn play_gomoku_should_scroll_and_reject_far_cells() {
+        let config = GameConfig::default();
+        let mut ui = RecordingUi::new(&["a1", "d4", "right", "zz"]);
+
+        let result = play_gomoku(&config, &mut ui, &mut seeded_rng(1));
+
+        assert!(result.is_err());
+        let messages = ui.messages();
+        assert!(messages.contains(&"That cell is too far from the other stones."));
+        assert!(messages.contains(&"The CPU has played."));
+        assert!(messages
+            .iter()
+            .any(|message| message.starts_with("'zz' is not a position")));
+    }
+
+    #[test]
+    fn play_gomoku_should_end_when_the_cpu_gets_five() {
+        let config = GameConfig {
+            first_player: Player::Cpu,
+            profile: strong(),
+            ..GameConfig::default()
+        };
+        // The human answers somewhere around the middle of the window.
+        let inputs = ["c3", "e5", "c5", "e3", "d3", "d5", "c4", "e4", "b2", "f6"].repeat(10);
+        let mut ui = RecordingUi::new(&inputs);
+
+        let winner = play_gomoku(&config, &mut ui, &mut seeded_rng(2)).unwrap();
+
+        assert_eq!(winner, Some(Player::Cpu));
+        assert!(ui.messages().last().unwrap().contains('['));
+    }
+}
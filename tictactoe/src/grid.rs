@@ -2,21 +2,46 @@ use itertools::*;
 
 use std::collections::HashMap;
 use std::convert::From;
-use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, PartialEq)]
+use crate::error::{GameError, GameResult};
+use crate::input;
+use crate::ui::{Highlights, Ui};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Player {
     Human,
     Cpu,
 }
 
-type Winner = Player;
+impl Player {
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::Human => Player::Cpu,
+            Player::Cpu => Player::Human,
+        }
+    }
+}
+
 type ManualGrid = [[i32; 3]; 3];
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Grid {
     pub grid: HashMap<(i32, i32), Marker>,
     pub size: usize,
+    // Cells filled through `place_tracked`, oldest first.
+    pub history: Vec<(i32, i32)>,
+    // The lines that win on this board, shared by every copy of it.
+    pub lines: Arc<Vec<Vec<(i32, i32)>>>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Topology {
+    #[default]
+    Flat,
+    // Lines leaving one edge come back in on the opposite one.
+    Torus,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
@@ -24,14 +49,26 @@ pub enum Marker {
     X = 1,
     O = -1,
     Null = 0,
+    // A cell nobody can play, which breaks every line through it.
+    Blocked = 2,
 }
 
 impl Marker {
-    pub fn to_int(self) -> i32 {
+    pub fn opponent(self) -> Marker {
         match self {
-            Marker::X => 1,
-            Marker::O => -1,
-            Marker::Null => 0,
+            Marker::X => Marker::O,
+            Marker::O => Marker::X,
+            Marker::Null => Marker::Null,
+            Marker::Blocked => Marker::Blocked,
+        }
+    }
+
+    pub fn glyph(self) -> char {
+        match self {
+            Marker::X => 'X',
+            Marker::O => 'O',
+            Marker::Null => '.',
+            Marker::Blocked => '#',
         }
     }
 }
@@ -44,13 +81,30 @@ impl From<ManualGrid> for Grid {
 
 pub trait GridChecker {
     fn is_grid_full(&self) -> bool;
-    fn is_winning_grid(&self) -> Option<Winner>;
+    fn is_winning_grid(&self) -> Option<Marker>;
     fn insert(&mut self, key: (i32, i32), value: Marker);
     fn extract_winning_positions(&self, marker: &Marker) -> Vec<(i32, i32)>;
     fn extract_empty_positions(&self) -> HashMap<usize, (i32, i32)>;
+    // A completed line of any marker closes the board, as in Notakto.
+    fn is_dead(&self) -> bool;
 }
 
 impl Grid {
+    // A flat board with the classic lines, as most boards use.
+    pub fn new(grid: HashMap<(i32, i32), Marker>, size: usize) -> Grid {
+        Grid {
+            grid,
+            size,
+            history: Vec::new(),
+            lines: Arc::new(line_table(size, Topology::Flat)),
+        }
+    }
+
+    pub fn with_lines(mut self, lines: Vec<Vec<(i32, i32)>>) -> Grid {
+        self.lines = Arc::new(lines);
+        self
+    }
+
     pub fn render(&self) -> String {
         let glyph_list_by_row = self.regroup_glyphs_by_row();
 
@@ -59,11 +113,155 @@ impl Grid {
             .sorted_by_key(|(key, _)| *key)
             .map(|(_, glyphs)| glyphs.join("|"))
             .collect::<Vec<_>>()
-            .join("\n---+---+---\n");
+            .join(&format!("\n{}\n", vec!["---"; self.size].join("+")));
 
         joined_row_glyphs
     }
 
+    pub fn lines(&self) -> &[Vec<(i32, i32)>] {
+        &self.lines
+    }
+
+    fn is_complete(&self, line: &[(i32, i32)]) -> bool {
+        let first_marker = self.grid.get(&line[0]);
+        matches!(first_marker, Some(Marker::X | Marker::O))
+            && line
+                .iter()
+                .all(|position| self.grid.get(position) == first_marker)
+    }
+
+    pub fn winning_line(&self) -> Option<Vec<(i32, i32)>> {
+        self.lines
+            .iter()
+            .find(|line| self.is_complete(line))
+            .cloned()
+    }
+
+    // The longest line of one marker through `position`, when it reaches
+    // `length`. Missing cells count as empty, so this also works off the edges.
+    pub fn run_through(&self, position: (i32, i32), length: usize) -> Option<Vec<(i32, i32)>> {
+        let marker = *self
+            .grid
+            .get(&position)
+            .filter(|marker| **marker != Marker::Null)?;
+        [(0, 1), (1, 0), (1, 1), (1, -1)]
+            .into_iter()
+            .map(|(d_row, d_col)| {
+                let stretch = |sign: i32| {
+                    (1..)
+                        .map(move |step| {
+                            (
+                                position.0 + sign * step * d_row,
+                                position.1 + sign * step * d_col,
+                            )
+                        })
+                        .take_while(|cell| self.grid.get(cell) == Some(&marker))
+                };
+                stretch(-1)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()
+                    .chain([position])
+                    .chain(stretch(1))
+                    .collect::<Vec<_>>()
+            })
+            .find(|run| run.len() >= length)
+    }
+
+    pub fn winning_marker(&self) -> Option<Marker> {
+        self.winning_line()
+            .and_then(|line| self.grid.get(&line[0]).copied())
+    }
+
+    pub fn position_number(&self, position: (i32, i32)) -> usize {
+        (position.0 * self.size as i32 + position.1) as usize + 1
+    }
+
+    pub fn to_notation(&self) -> String {
+        let size = self.size as i32;
+        (0..size)
+            .map(|row| {
+                (0..size)
+                    .map(|col| self.grid.get(&(row, col)).unwrap_or(&Marker::Null).glyph())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    pub fn from_notation(notation: &str) -> Option<Grid> {
+        let rows = notation.trim().split('/').collect::<Vec<_>>();
+        let size = rows.len();
+        let mut grid = HashMap::new();
+        for (ix_row, row) in rows.iter().enumerate() {
+            if row.chars().count() != size {
+                return None;
+            }
+            for (ix_col, glyph) in row.chars().enumerate() {
+                let marker = match glyph.to_ascii_uppercase() {
+                    'X' => Marker::X,
+                    'O' => Marker::O,
+                    '.' | '-' | '_' => Marker::Null,
+                    '#' => Marker::Blocked,
+                    _ => return None,
+                };
+                grid.insert((ix_row as i32, ix_col as i32), marker);
+            }
+        }
+        Some(Grid::new(grid, size))
+    }
+
+    // Same as render, with the marker at `fading` in lower case.
+    pub fn render_fading(&self, fading: (i32, i32)) -> String {
+        let separator = format!("\n{}\n", vec!["---"; self.size].join("+"));
+        self.render()
+            .split(&separator)
+            .enumerate()
+            .map(|(row, glyphs)| {
+                glyphs
+                    .split('|')
+                    .enumerate()
+                    .map(|(col, glyph)| {
+                        if (row as i32, col as i32) == fading {
+                            glyph.to_lowercase()
+                        } else {
+                            glyph.to_owned()
+                        }
+                    })
+                    .join("|")
+            })
+            .join(&separator)
+    }
+
+    pub fn place_tracked(&mut self, position: (i32, i32), marker: Marker) {
+        self.insert(position, marker);
+        self.history.retain(|cell| *cell != position);
+        self.history.push(position);
+    }
+
+    pub fn remove(&mut self, position: (i32, i32)) {
+        self.insert(position, Marker::Null);
+        self.history.retain(|cell| *cell != position);
+    }
+
+    pub fn oldest(&self, marker: Marker) -> Option<(i32, i32)> {
+        self.history
+            .iter()
+            .find(|cell| self.grid.get(cell) == Some(&marker))
+            .copied()
+    }
+
+    pub fn count_markers(&self, marker: Marker) -> usize {
+        self.grid.values().filter(|value| **value == marker).count()
+    }
+
+    pub fn newly_filled_position(&self, before: &Grid) -> Option<(i32, i32)> {
+        before
+            .extract_empty_positions()
+            .into_values()
+            .find(|position| self.grid.get(position) != Some(&Marker::Null))
+    }
+
     fn regroup_glyphs_by_row(&self) -> HashMap<i32, Vec<String>> {
         self.grid
             .iter()
@@ -74,9 +272,10 @@ impl Grid {
                     x,
                     y,
                     match val {
-                        Marker::Null => format!(" {glyph} ", glyph = idx + 1),
+                        Marker::Null => format!("{glyph:^3}", glyph = idx + 1),
                         Marker::X => " X ".to_owned(),
                         Marker::O => " O ".to_owned(),
+                        Marker::Blocked => " # ".to_owned(),
                     },
                 )
             })
@@ -102,63 +301,79 @@ pub fn from_array(array: ManualGrid) -> Grid {
             );
         }
     }
-    Grid { grid, size: 3 }
+    Grid::new(grid, 3)
+}
+
+// Every line of `size` cells that wins on a board of this topology. A torus
+// keeps the rows and columns and adds every broken diagonal.
+pub fn line_table(size: usize, topology: Topology) -> Vec<Vec<(i32, i32)>> {
+    let size = size as i32;
+    let mut lines = Vec::new();
+    for idx in 0..size {
+        lines.push((0..size).map(|col| (idx, col)).collect());
+        lines.push((0..size).map(|row| (row, idx)).collect());
+    }
+    let offsets = match topology {
+        Topology::Flat => 0..1,
+        Topology::Torus => 0..size,
+    };
+    for offset in offsets {
+        lines.push((0..size).map(|row| (row, (row + offset) % size)).collect());
+        lines.push(
+            (0..size)
+                .map(|row| (row, (size - 1 - row + offset) % size))
+                .collect(),
+        );
+    }
+    lines
 }
 
+// Every straight segment of `length` cells on a board of `size`, in all
+// four directions.
+pub fn run_table(size: usize, length: usize) -> Vec<Vec<(i32, i32)>> {
+    let (size, length) = (size as i32, length as i32);
+    let mut runs = Vec::new();
+    for (d_row, d_col) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+        for (row, col) in (0..size).cartesian_product(0..size) {
+            let end = (row + d_row * (length - 1), col + d_col * (length - 1));
+            if (0..size).contains(&end.0) && (0..size).contains(&end.1) {
+                runs.push(
+                    (0..length)
+                        .map(|step| (row + d_row * step, col + d_col * step))
+                        .collect(),
+                );
+            }
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
 pub fn create_grid() -> Grid {
+    create_grid_of_size(3)
+}
+
+// An edgeless board only stores the cells that hold a marker.
+pub fn create_unbounded_grid() -> Grid {
+    Grid::new(HashMap::new(), 0)
+}
+
+pub fn create_grid_of_size(size: usize) -> Grid {
+    let side = size as i32;
     let mut grid = HashMap::new();
-    for x in 0..=8 {
-        grid.insert((x / 3, x % 3), Marker::Null);
+    for x in 0..side * side {
+        grid.insert((x / side, x % side), Marker::Null);
     }
-    Grid { grid, size: 3 }
+    Grid::new(grid, size)
 }
 
 impl GridChecker for Grid {
-    fn is_winning_grid(&self) -> Option<Winner> {
-        let sum_per_row: Vec<i32> = self
-            .grid
-            .iter()
-            .into_grouping_map_by(|((row, _), _)| row)
-            .fold(0, |acc, _key, (_, mk)| acc + mk.to_int())
-            .into_values()
-            .collect();
-        let sum_per_column: Vec<i32> = self
-            .grid
-            .iter()
-            .into_grouping_map_by(|((_, col), _)| col)
-            .fold(0, |acc, _key, (_, mk)| acc + mk.to_int())
-            .into_values()
-            .collect();
-
-        let diagonal_sum = self
-            .grid
-            .iter()
-            .filter(|((row, col), _)| row == col)
-            .map(|((_, _), mk)| mk.to_int())
-            .sum();
-
-        let anti_diagonal_sum = self
-            .grid
-            .iter()
-            .filter(|((row, col), _)| [2, 4, 6].contains(&(row + col)))
-            .map(|(_, mk)| mk.to_int())
-            .sum();
-
-        for val in [
-            sum_per_row,
-            sum_per_column,
-            vec![diagonal_sum, anti_diagonal_sum],
-        ]
-        .concat()
-        {
-            match val {
-                3 => return Some(Winner::Human),
-                -3 => return Some(Winner::Cpu),
-                _ => (),
-            }
-        }
+    fn is_winning_grid(&self) -> Option<Marker> {
+        self.winning_marker()
+    }
 
-        None
+    fn is_dead(&self) -> bool {
+        self.winning_line().is_some()
     }
 
     fn is_grid_full(&self) -> bool {
@@ -174,7 +389,12 @@ impl GridChecker for Grid {
     fn extract_winning_positions(&self, marker: &Marker) -> Vec<(i32, i32)> {
         let mut winning_position = Vec::<(i32, i32)>::new();
 
-        for ((x, y), _) in self.grid.iter().filter(|(_, val)| **val == Marker::Null) {
+        for ((x, y), _) in self
+            .grid
+            .iter()
+            .filter(|(_, val)| **val == Marker::Null)
+            .sorted_by_key(|(position, _)| **position)
+        {
             let mut attempt_grid = self.clone();
             attempt_grid.insert((*x, *y), *marker);
             if attempt_grid.is_winning_grid().is_some() {
@@ -201,37 +421,77 @@ impl GridChecker for Grid {
     }
 }
 
-pub fn make_user_turn<R, G>(grid: &Grid, mut reader: G) -> Grid
-where
-    G: FnMut() -> R,
-    R: io::BufRead,
-{
-    let empty_postions = &grid.extract_empty_positions();
-    let list_of_choices = &empty_postions
+pub fn prompt_for_position<U: Ui + ?Sized>(grid: &Grid, ui: &mut U) -> GameResult<(i32, i32)> {
+    prompt_for_position_showing(grid, ui, &Highlights::default())
+}
+
+pub fn prompt_for_position_showing<U: Ui + ?Sized>(
+    grid: &Grid,
+    ui: &mut U,
+    highlights: &Highlights,
+) -> GameResult<(i32, i32)> {
+    let list_of_choices = &grid
+        .extract_empty_positions()
         .keys()
-        .map(|x| x.to_string())
         .sorted()
+        .map(|x| x.to_string())
         .collect::<Vec<_>>()
         .join(", ");
-    let mut positions: Option<(i32, i32)> = None;
-    while positions.is_none() {
-        println!("\n{}\n", grid.render());
-        println!(
+    loop {
+        ui.show_board(grid, highlights);
+        let answer = ui.prompt(&format!(
             "Please select one of the available positions:\n{}",
             list_of_choices
-        );
-        let mut string_buffer = String::new();
-        reader()
-            .read_line(&mut string_buffer)
-            .expect("Unable to read user input during play turn");
-        println!("Candidate: {}", string_buffer.trim());
-        let candidate: usize = string_buffer.trim().parse().unwrap_or(0);
-        positions = empty_postions.get(&candidate).cloned();
-    }
-    let final_coordinates: (i32, i32) = positions.unwrap();
+        ))?;
+        ui.show_message(&format!("Candidate: {}", answer.trim()));
+        match input::parse_position(grid, &answer) {
+            Ok(position) => return Ok(position),
+            Err(error) => ui.show_message(&error.to_string()),
+        }
+    }
+}
+
+pub fn make_user_turn<U: Ui + ?Sized>(
+    grid: &Grid,
+    ui: &mut U,
+    last_move: Option<(i32, i32)>,
+    marker: Marker,
+    time_limit: Option<Duration>,
+) -> GameResult<Grid> {
+    let started = Instant::now();
+    ui.set_deadline(time_limit.map(|limit| started + limit));
+    let choice = ui.choose_position(grid, last_move);
+    ui.set_deadline(None);
+    let final_coordinates = choice?;
+    if time_limit.is_some_and(|limit| started.elapsed() > limit) {
+        return Err(GameError::Timeout);
+    }
     let mut grid_after_move = grid.clone();
-    grid_after_move.insert(final_coordinates, Marker::X);
-    grid_after_move
+    grid_after_move.insert(final_coordinates, marker);
+    Ok(grid_after_move)
+}
+
+pub fn prompt_for_marker<U: Ui + ?Sized>(ui: &mut U) -> GameResult<Marker> {
+    loop {
+        let answer = ui.prompt("Which marker do you place, X or O?")?;
+        match answer.trim().to_uppercase().as_str() {
+            "X" => return Ok(Marker::X),
+            "O" => return Ok(Marker::O),
+            other => ui.show_message(&format!("'{}' is not a marker, answer X or O.", other)),
+        }
+    }
+}
+
+// For variants where either side may place either marker.
+pub fn make_user_choice_turn<U: Ui + ?Sized>(
+    grid: &Grid,
+    ui: &mut U,
+    last_move: Option<(i32, i32)>,
+    time_limit: Option<Duration>,
+) -> GameResult<(Grid, Marker)> {
+    let marker = prompt_for_marker(ui)?;
+    let grid_after_move = make_user_turn(grid, ui, last_move, marker, time_limit)?;
+    Ok((grid_after_move, marker))
 }
 
 #[cfg(test)]
@@ -239,13 +499,7 @@ mod tests {
     use std::collections::HashSet;
 
     use super::*;
-
-    #[test]
-    fn markers_shoud_be_convertible_to_integers() {
-        assert_eq!(Marker::X.to_int(), 1);
-        assert_eq!(Marker::O.to_int(), -1);
-        assert_eq!(Marker::Null.to_int(), 0);
-    }
+    use crate::ui::{RecordingUi, UiEvent};
 
     #[test]
     fn creates_an_empty_grid() {
@@ -265,43 +519,275 @@ mod tests {
     fn is_wining_grid_should_return_winner_on_lines() {
         let grid = Grid::from([[0, 0, 0], [1, 1, 1], [0, 0, 0]]);
         let result = grid.is_winning_grid();
-        assert_eq!(result.unwrap(), Winner::Human);
+        assert_eq!(result.unwrap(), Marker::X);
 
         let grid = from_array([[0, 0, 0], [-1, -1, -1], [0, 0, 0]]);
         let result = grid.is_winning_grid();
-        assert_eq!(result.unwrap(), Winner::Cpu);
+        assert_eq!(result.unwrap(), Marker::O);
     }
     #[test]
     fn is_wining_grid_should_return_winner_on_columns() {
         let grid = from_array([[1, 0, 0], [1, 0, 0], [1, 0, 0]]);
         let result = grid.is_winning_grid();
-        assert_eq!(result.unwrap(), Winner::Human);
+        assert_eq!(result.unwrap(), Marker::X);
 
         let grid = from_array([[-1, 0, 0], [-1, 0, 0], [-1, 0, 0]]);
         let result = grid.is_winning_grid();
-        assert_eq!(result.unwrap(), Winner::Cpu);
+        assert_eq!(result.unwrap(), Marker::O);
     }
 
     #[test]
     fn is_wining_grid_should_return_winner_on_diagonal() {
         let grid = from_array([[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
         let result = grid.is_winning_grid();
-        assert_eq!(result.unwrap(), Winner::Human);
+        assert_eq!(result.unwrap(), Marker::X);
 
         let grid = from_array([[-1, 0, 0], [0, -1, 0], [0, 0, -1]]);
         let result = grid.is_winning_grid();
-        assert_eq!(result.unwrap(), Winner::Cpu);
+        assert_eq!(result.unwrap(), Marker::O);
     }
 
     #[test]
     fn is_wining_grid_should_return_winner_on_antidiagonal() {
         let grid = from_array([[0, 0, 1], [0, 1, 0], [1, 0, 0]]);
         let result = grid.is_winning_grid();
-        assert_eq!(result.unwrap(), Winner::Human);
+        assert_eq!(result.unwrap(), Marker::X);
 
         let grid = from_array([[0, 0, -1], [0, -1, 0], [-1, 0, 0]]);
         let result = grid.is_winning_grid();
-        assert_eq!(result.unwrap(), Winner::Cpu);
+        assert_eq!(result.unwrap(), Marker::O);
+    }
+
+    #[test]
+    fn is_wining_grid_should_not_count_bottom_right_corner_in_antidiagonal() {
+        let grid = from_array([[0, 0, -1], [0, -1, 0], [0, 0, -1]]);
+        assert!(grid.is_winning_grid().is_none());
+    }
+
+    #[test]
+    fn is_wining_grid_should_find_antidiagonal_next_to_bottom_right_marker() {
+        // Summing every cell with row + col in [2, 4, 6] added the corner
+        // at (2, 2) to the anti-diagonal, so this line was missed.
+        let grid = from_array([[0, 0, 1], [0, 1, 0], [1, 0, 1]]);
+        assert_eq!(grid.is_winning_grid(), Some(Marker::X));
+    }
+
+    #[test]
+    fn opponent_should_swap_markers() {
+        assert_eq!(Marker::X.opponent(), Marker::O);
+        assert_eq!(Marker::O.opponent(), Marker::X);
+        assert_eq!(Marker::Null.opponent(), Marker::Null);
+    }
+
+    #[test]
+    fn winning_line_should_return_positions_of_completed_line() {
+        let grid = from_array([[0, 0, -1], [1, -1, 1], [-1, 1, 0]]);
+        assert_eq!(grid.winning_line(), Some(vec![(0, 2), (1, 1), (2, 0)]));
+
+        let grid = from_array([[1, 0, -1], [1, -1, 0], [1, 0, 0]]);
+        assert_eq!(grid.winning_line(), Some(vec![(0, 0), (1, 0), (2, 0)]));
+    }
+
+    #[test]
+    fn winning_line_should_return_none_without_winner() {
+        let grid = from_array([[1, -1, 1], [1, -1, -1], [-1, 1, 1]]);
+        assert_eq!(grid.winning_line(), None);
+        assert_eq!(create_grid().winning_line(), None);
+    }
+
+    #[test]
+    fn newly_filled_position_should_find_the_move_between_two_grids() {
+        let before = from_array([[1, 0, 0], [0, -1, 0], [0, 0, 0]]);
+        let after = from_array([[1, 0, 0], [0, -1, 0], [0, 1, 0]]);
+        assert_eq!(after.newly_filled_position(&before), Some((2, 1)));
+        assert_eq!(before.newly_filled_position(&before), None);
+    }
+
+    #[test]
+    fn notation_should_round_trip() {
+        let grid = from_array([[1, 0, -1], [0, 1, 0], [-1, 0, 0]]);
+        assert_eq!(grid.to_notation(), "X.O/.X./O..");
+        assert_eq!(Grid::from_notation("X.O/.X./O.."), Some(grid));
+        assert_eq!(Grid::from_notation("..."), None);
+        assert_eq!(Grid::from_notation("X.Z/.../..."), None);
+    }
+
+    #[test]
+    fn create_grid_of_size_should_build_larger_boards() {
+        let grid = create_grid_of_size(4);
+        assert_eq!(grid.grid.len(), 16);
+        assert_eq!(grid.extract_empty_positions().get(&16), Some(&(3, 3)));
+        assert_eq!(grid.position_number((3, 3)), 16);
+        assert!(grid.render().contains("13 |14 |15 |16 "));
+        assert!(grid.render().contains("\n---+---+---+---\n"));
+    }
+
+    #[test]
+    fn run_table_should_cover_every_segment_of_the_given_length() {
+        assert_eq!(run_table(6, 5).len(), 32);
+        assert_eq!(run_table(6, 6).len(), 14);
+        assert!(run_table(6, 5).contains(&vec![(0, 5), (1, 4), (2, 3), (3, 2), (4, 1)]));
+
+        let mut grid = create_grid_of_size(6);
+        for row in 1..6 {
+            grid.insert((row, 2), Marker::O);
+        }
+        assert_eq!(grid.winning_line(), None);
+        assert_eq!(
+            grid.with_lines(run_table(6, 5)).winning_line(),
+            Some((1..6).map(|row| (row, 2)).collect())
+        );
+    }
+
+    #[test]
+    fn make_user_choice_turn_should_ask_for_the_marker_first() {
+        let mut ui = RecordingUi::new(&["z", "o", "5"]);
+        let (grid, marker) = make_user_choice_turn(&create_grid(), &mut ui, None, None).unwrap();
+
+        assert_eq!(marker, Marker::O);
+        assert_eq!(grid.grid.get(&(1, 1)), Some(&Marker::O));
+        assert_eq!(
+            ui.messages(),
+            vec!["'Z' is not a marker, answer X or O.", "Candidate: 5"]
+        );
+    }
+
+    #[test]
+    fn is_winning_grid_should_require_full_line_on_larger_boards() {
+        let mut grid = create_grid_of_size(4);
+        for col in 0..3 {
+            grid.insert((1, col), Marker::O);
+        }
+        assert!(grid.is_winning_grid().is_none());
+
+        grid.insert((1, 3), Marker::O);
+        assert_eq!(grid.is_winning_grid(), Some(Marker::O));
+        assert_eq!(grid.winning_marker(), Some(Marker::O));
+    }
+
+    #[test]
+    fn make_user_turn_should_place_the_requested_marker() {
+        let grid = make_user_turn(
+            &create_grid(),
+            &mut RecordingUi::new(&["5"]),
+            None,
+            Marker::O,
+            None,
+        )
+        .unwrap();
+        assert_eq!(grid.grid.get(&(1, 1)), Some(&Marker::O));
+    }
+
+    #[test]
+    fn make_user_turn_should_time_out_after_the_limit() {
+        let result = make_user_turn(
+            &create_grid(),
+            &mut RecordingUi::new(&["5"]),
+            None,
+            Marker::X,
+            Some(Duration::ZERO),
+        );
+        assert!(matches!(result, Err(GameError::Timeout)));
+
+        let grid = make_user_turn(
+            &create_grid(),
+            &mut RecordingUi::new(&["5"]),
+            None,
+            Marker::X,
+            Some(Duration::from_secs(60)),
+        )
+        .unwrap();
+        assert_eq!(grid.grid.get(&(1, 1)), Some(&Marker::X));
+    }
+
+    #[test]
+    fn run_through_should_follow_markers_past_the_origin() {
+        let mut grid = create_unbounded_grid();
+        for step in -2..2 {
+            grid.insert((step, -step), Marker::O);
+        }
+        grid.insert((5, 5), Marker::X);
+        assert_eq!(grid.run_through((0, 0), 5), None);
+        assert_eq!(grid.run_through((5, 5), 1), Some(vec![(5, 5)]));
+        assert_eq!(grid.run_through((9, 9), 1), None);
+
+        grid.insert((2, -2), Marker::O);
+        assert_eq!(
+            grid.run_through((0, 0), 5),
+            Some(vec![(-2, 2), (-1, 1), (0, 0), (1, -1), (2, -2)])
+        );
+    }
+
+    #[test]
+    fn line_table_should_add_broken_diagonals_on_a_torus() {
+        assert_eq!(line_table(3, Topology::Flat), create_grid().lines());
+        assert_eq!(line_table(4, Topology::Flat).len(), 10);
+
+        let torus = line_table(3, Topology::Torus);
+        assert_eq!(torus.len(), 12);
+        assert!(torus.contains(&vec![(0, 1), (1, 2), (2, 0)]));
+        assert!(torus.contains(&vec![(0, 0), (1, 2), (2, 1)]));
+    }
+
+    #[test]
+    fn is_winning_grid_should_follow_the_topology() {
+        let grid = from_array([[0, 1, 0], [0, 0, 1], [1, 0, 0]]);
+        assert_eq!(grid.is_winning_grid(), None);
+        let grid = grid.with_lines(line_table(3, Topology::Torus));
+        assert_eq!(grid.is_winning_grid(), Some(Marker::X));
+        assert_eq!(grid.winning_line(), Some(vec![(0, 1), (1, 2), (2, 0)]));
+    }
+
+    #[test]
+    fn blocked_cells_should_break_lines_and_never_be_empty() {
+        let mut grid = Grid::from_notation("XX#/O#./#..").unwrap();
+        assert_eq!(grid.grid.get(&(0, 2)), Some(&Marker::Blocked));
+        assert_eq!(grid.to_notation(), "XX#/O#./#..");
+        assert_eq!(grid.extract_empty_positions().len(), 3);
+        assert_eq!(grid.extract_winning_positions(&Marker::X), Vec::new());
+        assert!(grid.render().starts_with(" X | X | # "));
+
+        for position in [(1, 2), (2, 1), (2, 2)] {
+            grid.insert(position, Marker::O);
+        }
+        assert!(grid.is_grid_full());
+        assert_eq!(grid.is_winning_grid(), None);
+        assert_eq!(
+            Grid::from_notation("###/.../...").unwrap().winning_line(),
+            None
+        );
+    }
+
+    #[test]
+    fn place_tracked_should_remember_the_order_until_removed() {
+        let mut grid = create_grid();
+        grid.place_tracked((0, 0), Marker::X);
+        grid.place_tracked((1, 1), Marker::O);
+        grid.place_tracked((2, 2), Marker::X);
+        assert_eq!(grid.history, vec![(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(grid.oldest(Marker::X), Some((0, 0)));
+        assert_eq!(grid.oldest(Marker::O), Some((1, 1)));
+
+        grid.remove((0, 0));
+        assert_eq!(grid.grid.get(&(0, 0)), Some(&Marker::Null));
+        assert_eq!(grid.oldest(Marker::X), Some((2, 2)));
+        assert_eq!(grid.history, vec![(1, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn render_fading_should_lower_the_marked_glyph() {
+        let grid = from_array([[1, 0, 0], [0, -1, 0], [0, 0, 0]]);
+        assert_eq!(
+            grid.render_fading((1, 1)),
+            grid.render().replace(" O ", " o ")
+        );
+    }
+
+    #[test]
+    fn is_dead_should_detect_any_completed_line() {
+        assert!(from_array([[1, 1, 1], [0, 0, 0], [0, 0, 0]]).is_dead());
+        assert!(from_array([[-1, 0, 0], [0, -1, 0], [1, 0, -1]]).is_dead());
+        assert!(!from_array([[1, 1, 0], [0, 0, 1], [1, 0, 0]]).is_dead());
     }
 
     #[test]
@@ -377,9 +863,10 @@ mod tests {
     fn make_user_turn_should_fill_one_empty_position_in_grid() {
         let original_grid = from_array([[1, -1, 0], [1, -1, 0], [0, 0, 0]]);
 
-        let get_mock_reader = || std::io::Cursor::new("3".as_bytes());
+        let mut ui = RecordingUi::new(&["3"]);
 
-        let grid_after_turn = make_user_turn(&original_grid, get_mock_reader);
+        let grid_after_turn =
+            make_user_turn(&original_grid, &mut ui, None, Marker::X, None).unwrap();
         assert!(
             grid_after_turn.extract_empty_positions().len()
                 < original_grid.extract_empty_positions().len()
@@ -388,15 +875,10 @@ mod tests {
 
     #[test]
     fn make_user_turn_should_prompt_until_user_selects_an_empty_position() {
-        let mut mock_inputs = vec!["invalid", "l", "1"];
-
-        let get_mock_reader = || {
-            let input_str = mock_inputs.remove(0);
-            std::io::Cursor::new(input_str.as_bytes())
-        };
+        let mut ui = RecordingUi::new(&["invalid", "l", "1"]);
 
         let grid = from_array([[0, -1, 0], [1, -1, 0], [0, 0, 0]]);
-        let filled_grid = make_user_turn(&grid, get_mock_reader);
+        let filled_grid = make_user_turn(&grid, &mut ui, None, Marker::X, None).unwrap();
 
         let new_empty_positions = filled_grid
             .extract_empty_positions()
@@ -406,4 +888,39 @@ mod tests {
             .collect::<Vec<usize>>();
         assert_eq!(new_empty_positions, vec![3, 6, 7, 8, 9]);
     }
+
+    #[test]
+    fn make_user_turn_should_explain_rejected_input_and_accept_other_syntaxes() {
+        let mut ui = RecordingUi::new(&["b1", "d4", "top right"]);
+        let grid = from_array([[0, -1, 0], [1, -1, 0], [0, 0, 0]]);
+
+        let filled_grid = make_user_turn(&grid, &mut ui, None, Marker::X, None).unwrap();
+
+        assert_eq!(filled_grid.grid.get(&(0, 2)), Some(&Marker::X));
+        assert_eq!(
+            ui.messages(),
+            vec![
+                "Candidate: b1",
+                "Position 2 is already taken.",
+                "Candidate: d4",
+                "d4 is outside the board.",
+                "Candidate: top right"
+            ]
+        );
+    }
+
+    #[test]
+    fn make_user_turn_should_show_board_and_available_positions() {
+        let mut ui = RecordingUi::new(&["9"]);
+        let grid = from_array([[0, -1, 0], [1, -1, 0], [0, 0, 0]]);
+
+        make_user_turn(&grid, &mut ui, None, Marker::X, None).unwrap();
+
+        assert_eq!(ui.events[0], UiEvent::Board(grid.render()));
+        assert_eq!(
+            ui.prompts(),
+            vec!["Please select one of the available positions:\n1, 3, 6, 7, 8, 9"]
+        );
+        assert_eq!(ui.messages(), vec!["Candidate: 9"]);
+    }
 }
@@ -0,0 +1,244 @@
+//! Accepted move syntaxes, with rows counted from the top of the board:
+//!
+//! - position numbers as shown on the board: `5`, also `phone 5`
+//! - algebraic coordinates, column letter then row: `a1`, `b2`
+//! - row and column pairs: `2 3`, `2,3`
+//! - the numeric keypad layout with 7-8-9 on top: `numpad 7`, `n7`
+//! - words: `center`, `top left`, `bottom-right`, `left`
+
+use super::grid::{Grid, Marker};
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveInputError {
+    Empty,
+    Unrecognized(String),
+    OutsideBoard(String),
+    Unsupported(String),
+    Taken(usize),
+}
+
+impl fmt::Display for MoveInputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveInputError::Empty => write!(f, "Please enter a position."),
+            MoveInputError::Unrecognized(input) => write!(
+                f,
+                "'{}' is not a position, try 5, b2, '2 3', 'numpad 7' or 'top left'.",
+                input
+            ),
+            MoveInputError::OutsideBoard(what) => write!(f, "{} is outside the board.", what),
+            MoveInputError::Unsupported(reason) => write!(f, "{}.", reason),
+            MoveInputError::Taken(number) => write!(f, "Position {} is already taken.", number),
+        }
+    }
+}
+
+fn check_range(value: usize, size: usize, what: &str) -> Result<i32, MoveInputError> {
+    if (1..=size).contains(&value) {
+        Ok(value as i32 - 1)
+    } else {
+        Err(MoveInputError::OutsideBoard(format!(
+            "{} {} (1-{})",
+            what, value, size
+        )))
+    }
+}
+
+fn from_number(number: usize, size: usize) -> Result<(i32, i32), MoveInputError> {
+    let index = check_range(number, size * size, "Position")?;
+    Ok((index / size as i32, index % size as i32))
+}
+
+fn from_numpad(key: usize, size: usize) -> Result<(i32, i32), MoveInputError> {
+    if size != 3 {
+        return Err(MoveInputError::Unsupported(
+            "The numpad layout only fits 3x3 boards".to_owned(),
+        ));
+    }
+    let index = check_range(key, 9, "Numpad key")?;
+    Ok((2 - index / 3, index % 3))
+}
+
+fn from_algebraic(word: &str, size: usize) -> Option<Result<(i32, i32), MoveInputError>> {
+    let mut glyphs = word.chars();
+    let column = glyphs.next()? as usize - 'a' as usize + 1;
+    let row = glyphs.as_str().parse::<usize>().ok()?;
+    if !(1..=size).contains(&column) || !(1..=size).contains(&row) {
+        return Some(Err(MoveInputError::OutsideBoard(word.to_owned())));
+    }
+    Some(Ok((row as i32 - 1, column as i32 - 1)))
+}
+
+fn middle(size: usize) -> Result<i32, MoveInputError> {
+    if size % 2 == 1 {
+        Ok(size as i32 / 2)
+    } else {
+        Err(MoveInputError::Unsupported(format!(
+            "A {}x{} board has no single middle cell",
+            size, size
+        )))
+    }
+}
+
+fn from_words(words: &[&str], size: usize) -> Option<Result<(i32, i32), MoveInputError>> {
+    let last = size as i32 - 1;
+    let vertical = |word: &str| match word {
+        "top" | "upper" => Some(Ok(0)),
+        "bottom" | "lower" => Some(Ok(last)),
+        "middle" | "center" | "centre" => Some(middle(size)),
+        _ => None,
+    };
+    let horizontal = |word: &str| match word {
+        "left" => Some(Ok(0)),
+        "right" => Some(Ok(last)),
+        "middle" | "center" | "centre" => Some(middle(size)),
+        _ => None,
+    };
+    let (row, col) = match words {
+        [word] => match (vertical(word), horizontal(word)) {
+            (Some(row), Some(col)) => (row, col),
+            (Some(row), None) => (row, middle(size)),
+            (None, Some(col)) => (middle(size), col),
+            (None, None) => return None,
+        },
+        [first, second] => match (vertical(first), horizontal(second)) {
+            (Some(row), Some(col)) => (row, col),
+            _ => (vertical(second)?, horizontal(first)?),
+        },
+        _ => return None,
+    };
+    Some(row.and_then(|row| col.map(|col| (row, col))))
+}
+
+pub fn parse_coordinates(input: &str, size: usize) -> Result<(i32, i32), MoveInputError> {
+    let normalized = input.trim().to_lowercase();
+    let words = normalized
+        .split(|glyph: char| glyph.is_whitespace() || glyph == ',' || glyph == '-')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let unrecognized = || MoveInputError::Unrecognized(input.trim().to_owned());
+    let number = |word: &str| word.parse::<usize>().map_err(|_| unrecognized());
+
+    match words[..] {
+        [] => Err(MoveInputError::Empty),
+        [word] if word.chars().all(|glyph| glyph.is_ascii_digit()) => {
+            from_number(number(word)?, size)
+        }
+        ["phone", key] => from_number(number(key)?, size),
+        ["numpad", key] => from_numpad(number(key)?, size),
+        [word] if word.starts_with('n') && word.len() > 1 => from_numpad(number(&word[1..])?, size),
+        [row, col] if row.parse::<usize>().is_ok() => Ok((
+            check_range(number(row)?, size, "Row")?,
+            check_range(number(col)?, size, "Column")?,
+        )),
+        [word] if word.starts_with(|glyph: char| glyph.is_ascii_lowercase()) => {
+            from_algebraic(word, size)
+                .or_else(|| from_words(&words, size))
+                .unwrap_or_else(|| Err(unrecognized()))
+        }
+        _ => from_words(&words, size).unwrap_or_else(|| Err(unrecognized())),
+    }
+}
+
+pub fn parse_position(grid: &Grid, input: &str) -> Result<(i32, i32), MoveInputError> {
+    let position = parse_coordinates(input, grid.size)?;
+    match grid.grid.get(&position) {
+        Some(Marker::Null) => Ok(position),
+        _ => Err(MoveInputError::Taken(grid.position_number(position))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::grid::{create_grid, create_grid_of_size, from_array};
+    use super::*;
+
+    fn parse(input: &str) -> Result<(i32, i32), MoveInputError> {
+        parse_position(&create_grid(), input)
+    }
+
+    #[test]
+    fn parse_position_should_read_position_numbers() {
+        assert_eq!(parse("1"), Ok((0, 0)));
+        assert_eq!(parse(" 6\n"), Ok((1, 2)));
+        assert_eq!(parse("phone 7"), Ok((2, 0)));
+        assert_eq!(
+            parse("10"),
+            Err(MoveInputError::OutsideBoard("Position 10 (1-9)".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_position_should_read_algebraic_coordinates() {
+        assert_eq!(parse("a1"), Ok((0, 0)));
+        assert_eq!(parse("B2"), Ok((1, 1)));
+        assert_eq!(parse("c1"), Ok((0, 2)));
+        assert_eq!(
+            parse("d1"),
+            Err(MoveInputError::OutsideBoard("d1".to_owned()))
+        );
+        assert_eq!(parse_position(&create_grid_of_size(4), "d4"), Ok((3, 3)));
+    }
+
+    #[test]
+    fn parse_position_should_read_row_and_column_pairs() {
+        assert_eq!(parse("2 3"), Ok((1, 2)));
+        assert_eq!(parse("3,1"), Ok((2, 0)));
+        assert_eq!(
+            parse("4 1"),
+            Err(MoveInputError::OutsideBoard("Row 4 (1-3)".to_owned()))
+        );
+        assert_eq!(
+            parse("1 0"),
+            Err(MoveInputError::OutsideBoard("Column 0 (1-3)".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_position_should_map_the_numpad_layout() {
+        assert_eq!(parse("numpad 7"), Ok((0, 0)));
+        assert_eq!(parse("n3"), Ok((2, 2)));
+        assert_eq!(parse("N5"), Ok((1, 1)));
+        assert!(matches!(
+            parse_position(&create_grid_of_size(4), "n5"),
+            Err(MoveInputError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn parse_position_should_read_words() {
+        assert_eq!(parse("center"), Ok((1, 1)));
+        assert_eq!(parse("Top Left"), Ok((0, 0)));
+        assert_eq!(parse("bottom-right"), Ok((2, 2)));
+        assert_eq!(parse("left top"), Ok((0, 0)));
+        assert_eq!(parse("top"), Ok((0, 1)));
+        assert_eq!(parse("right"), Ok((1, 2)));
+        assert_eq!(
+            parse_position(&create_grid_of_size(5), "bottom left"),
+            Ok((4, 0))
+        );
+        assert!(matches!(
+            parse_position(&create_grid_of_size(4), "center"),
+            Err(MoveInputError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn parse_position_should_explain_rejected_input() {
+        assert_eq!(parse("  "), Err(MoveInputError::Empty));
+        assert_eq!(
+            parse("somewhere"),
+            Err(MoveInputError::Unrecognized("somewhere".to_owned()))
+        );
+        assert_eq!(
+            parse_position(&from_array([[0, 0, 0], [0, 1, 0], [0, 0, 0]]), "middle"),
+            Err(MoveInputError::Taken(5))
+        );
+        assert_eq!(
+            MoveInputError::Taken(5).to_string(),
+            "Position 5 is already taken."
+        );
+    }
+}
@@ -0,0 +1,780 @@
+//! Line-based lobby protocol, one command per line:
+//!
+//! ```text
+//! LOGIN <name>        must come first, names are unique among connected users
+//! LIST                open and running tables, answered by TABLE lines and END
+//! CREATE [size]       open a table and sit as X
+//! JOIN <table>        sit as O, the game starts right away
+//! SPECTATE <table>    watch a table
+//! MOVE <position>     play a square on your table, numbered from 1
+//! SAY <text>          chat with everyone at your table
+//! LEAVE               leave your table, forfeiting a running game
+//! LEADERBOARD [name]  scores of a user, or of everybody
+//! QUIT                close the connection
+//! ```
+//!
+//! Replies are `OK ...` or `ERR ...`; table events are pushed as `START`,
+//! `BOARD`, `TURN`, `MOVED`, `CHAT`, `LEFT` and `RESULT` lines.
+
+use super::grid::{self, Grid, GridChecker, Marker, Player};
+use super::Leaderboard;
+
+use itertools::Itertools;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum TableStatus {
+    Waiting,
+    Playing,
+    Finished,
+}
+
+impl TableStatus {
+    fn name(&self) -> &'static str {
+        match self {
+            TableStatus::Waiting => "waiting",
+            TableStatus::Playing => "playing",
+            TableStatus::Finished => "finished",
+        }
+    }
+}
+
+struct Table {
+    grid: Grid,
+    players: [Option<String>; 2],
+    spectators: Vec<String>,
+    status: TableStatus,
+}
+
+impl Table {
+    fn members(&self) -> Vec<String> {
+        self.players
+            .iter()
+            .flatten()
+            .chain(self.spectators.iter())
+            .cloned()
+            .collect()
+    }
+
+    fn marker_of(&self, user: &str) -> Option<Marker> {
+        match self
+            .players
+            .iter()
+            .position(|player| player.as_deref() == Some(user))
+        {
+            Some(0) => Some(Marker::X),
+            Some(1) => Some(Marker::O),
+            _ => None,
+        }
+    }
+
+    fn player_with(&self, marker: Marker) -> Option<&str> {
+        match marker {
+            Marker::X => self.players[0].as_deref(),
+            Marker::O => self.players[1].as_deref(),
+            Marker::Null | Marker::Blocked => None,
+        }
+    }
+
+    fn marker_to_move(&self) -> Marker {
+        if self.grid.count_markers(Marker::X) <= self.grid.count_markers(Marker::O) {
+            Marker::X
+        } else {
+            Marker::O
+        }
+    }
+}
+
+struct Client {
+    sender: Sender<String>,
+    table: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct Lobby {
+    clients: HashMap<String, Client>,
+    tables: BTreeMap<u64, Table>,
+    leaderboards: HashMap<String, Leaderboard>,
+    next_table: u64,
+}
+
+fn is_valid_name(name: &str) -> bool {
+    (1..=16).contains(&name.len())
+        && name
+            .chars()
+            .all(|glyph| glyph.is_ascii_alphanumeric() || glyph == '_' || glyph == '-')
+}
+
+impl Lobby {
+    fn send(&self, user: &str, line: &str) {
+        if let Some(client) = self.clients.get(user) {
+            let _ = client.sender.send(line.to_owned());
+        }
+    }
+
+    fn broadcast(&self, table_id: u64, line: &str) {
+        if let Some(table) = self.tables.get(&table_id) {
+            for member in table.members() {
+                self.send(&member, line);
+            }
+        }
+    }
+
+    fn announce_turn(&self, table_id: u64) {
+        let table = &self.tables[&table_id];
+        self.broadcast(
+            table_id,
+            &format!("BOARD {} {}", table_id, table.grid.to_notation()),
+        );
+        if let Some(player) = table.player_with(table.marker_to_move()) {
+            self.broadcast(table_id, &format!("TURN {} {}", table_id, player));
+        }
+    }
+
+    pub fn login(&mut self, name: &str, sender: Sender<String>) -> Result<(), String> {
+        if !is_valid_name(name) {
+            return Err("names use up to 16 letters, digits, - or _".to_owned());
+        }
+        if self.clients.contains_key(name) {
+            return Err(format!("{} is already connected", name));
+        }
+        self.clients.insert(
+            name.to_owned(),
+            Client {
+                sender,
+                table: None,
+            },
+        );
+        self.leaderboards.entry(name.to_owned()).or_default();
+        self.send(name, &format!("OK welcome {}", name));
+        Ok(())
+    }
+
+    pub fn logout(&mut self, user: &str) {
+        self.leave(user);
+        self.clients.remove(user);
+    }
+
+    // Returns false once the client asked to quit.
+    pub fn handle(&mut self, user: &str, line: &str) -> bool {
+        let (command, argument) = line
+            .trim()
+            .split_once(' ')
+            .map_or((line.trim(), ""), |(command, argument)| {
+                (command, argument.trim())
+            });
+        let result = match command.to_uppercase().as_str() {
+            "LIST" => self.list(user),
+            "CREATE" => self.create(user, argument),
+            "JOIN" => self.join(user, argument),
+            "SPECTATE" => self.spectate(user, argument),
+            "MOVE" => self.play(user, argument),
+            "SAY" => self.say(user, argument),
+            "LEAVE" => match self.leave(user) {
+                true => Ok(()),
+                false => Err("you are not at a table".to_owned()),
+            },
+            "LEADERBOARD" => self.scores(user, argument),
+            "QUIT" => {
+                self.send(user, "BYE");
+                return false;
+            }
+            "LOGIN" => Err("already logged in".to_owned()),
+            _ => Err(format!("unknown command {}", command)),
+        };
+        if let Err(message) = result {
+            self.send(user, &format!("ERR {}", message));
+        }
+        true
+    }
+
+    fn current_table(&self, user: &str) -> Option<u64> {
+        self.clients.get(user).and_then(|client| client.table)
+    }
+
+    fn seat(&mut self, user: &str, table_id: Option<u64>) {
+        if let Some(client) = self.clients.get_mut(user) {
+            client.table = table_id;
+        }
+    }
+
+    fn ensure_free(&self, user: &str) -> Result<(), String> {
+        match self.current_table(user) {
+            Some(table_id) => Err(format!("leave table {} first", table_id)),
+            None => Ok(()),
+        }
+    }
+
+    fn parse_table(&self, argument: &str) -> Result<u64, String> {
+        argument
+            .parse::<u64>()
+            .ok()
+            .filter(|table_id| self.tables.contains_key(table_id))
+            .ok_or_else(|| format!("no table {}", argument))
+    }
+
+    fn list(&self, user: &str) -> Result<(), String> {
+        for (table_id, table) in &self.tables {
+            if table.status == TableStatus::Finished {
+                continue;
+            }
+            let names = table
+                .players
+                .iter()
+                .map(|player| player.as_deref().unwrap_or("-"))
+                .join(" ");
+            self.send(
+                user,
+                &format!(
+                    "TABLE {} {} {} {} {}",
+                    table_id,
+                    table.status.name(),
+                    table.grid.size,
+                    names,
+                    table.spectators.len()
+                ),
+            );
+        }
+        self.send(user, "END");
+        Ok(())
+    }
+
+    fn create(&mut self, user: &str, argument: &str) -> Result<(), String> {
+        self.ensure_free(user)?;
+        let size = match argument {
+            "" => 3,
+            size => size
+                .parse()
+                .ok()
+                .filter(|size| (3..=9).contains(size))
+                .ok_or("size must be between 3 and 9")?,
+        };
+        self.next_table += 1;
+        let table_id = self.next_table;
+        self.tables.insert(
+            table_id,
+            Table {
+                grid: grid::create_grid_of_size(size),
+                players: [Some(user.to_owned()), None],
+                spectators: Vec::new(),
+                status: TableStatus::Waiting,
+            },
+        );
+        self.seat(user, Some(table_id));
+        self.send(user, &format!("OK table {} X", table_id));
+        Ok(())
+    }
+
+    fn join(&mut self, user: &str, argument: &str) -> Result<(), String> {
+        self.ensure_free(user)?;
+        let table_id = self.parse_table(argument)?;
+        let table = self.tables.get_mut(&table_id).unwrap();
+        if table.status != TableStatus::Waiting {
+            return Err(format!("table {} is not open", table_id));
+        }
+        table.players[1] = Some(user.to_owned());
+        table.status = TableStatus::Playing;
+        let start = format!(
+            "START {} X {} O {}",
+            table_id,
+            table.players[0].as_deref().unwrap_or("-"),
+            user
+        );
+        self.seat(user, Some(table_id));
+        self.send(user, &format!("OK table {} O", table_id));
+        self.broadcast(table_id, &start);
+        self.announce_turn(table_id);
+        Ok(())
+    }
+
+    fn spectate(&mut self, user: &str, argument: &str) -> Result<(), String> {
+        self.ensure_free(user)?;
+        let table_id = self.parse_table(argument)?;
+        let table = self.tables.get_mut(&table_id).unwrap();
+        table.spectators.push(user.to_owned());
+        let board = format!("BOARD {} {}", table_id, table.grid.to_notation());
+        self.seat(user, Some(table_id));
+        self.send(user, &format!("OK spectating {}", table_id));
+        self.send(user, &board);
+        Ok(())
+    }
+
+    fn play(&mut self, user: &str, argument: &str) -> Result<(), String> {
+        let table_id = self.current_table(user).ok_or("you are not at a table")?;
+        let table = self.tables.get_mut(&table_id).unwrap();
+        let marker = table.marker_of(user).ok_or("spectators cannot play")?;
+        if table.status != TableStatus::Playing {
+            return Err("the game is not running".to_owned());
+        }
+        if table.marker_to_move() != marker {
+            return Err("it is not your turn".to_owned());
+        }
+        let position = argument
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| table.grid.extract_empty_positions().get(&number).cloned())
+            .ok_or_else(|| format!("position {} is not available", argument))?;
+        table.grid.insert(position, marker);
+
+        self.broadcast(
+            table_id,
+            &format!("MOVED {} {} {}", table_id, user, argument),
+        );
+        let table = &self.tables[&table_id];
+        match table.grid.winning_marker() {
+            Some(winner) => {
+                let winner = table.player_with(winner).map(str::to_owned);
+                self.finish(table_id, winner.as_deref(), "line");
+            }
+            None if table.grid.is_grid_full() => self.finish(table_id, None, "full"),
+            None => self.announce_turn(table_id),
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, table_id: u64, winner: Option<&str>, reason: &str) {
+        let table = self.tables.get_mut(&table_id).unwrap();
+        table.status = TableStatus::Finished;
+        let players = table.players.clone();
+        let board = format!("BOARD {} {}", table_id, table.grid.to_notation());
+
+        for player in players.iter().flatten() {
+            let outcome = match winner {
+                None => None,
+                Some(winner) if winner == player => Some(Player::Human),
+                Some(_) => Some(Player::Cpu),
+            };
+            self.leaderboards
+                .entry(player.clone())
+                .or_default()
+                .update_score(outcome);
+        }
+        self.broadcast(table_id, &board);
+        self.broadcast(
+            table_id,
+            &format!(
+                "RESULT {} {} {}",
+                table_id,
+                winner.map_or("tie".to_owned(), |winner| format!("winner {}", winner)),
+                reason
+            ),
+        );
+    }
+
+    fn say(&self, user: &str, text: &str) -> Result<(), String> {
+        let table_id = self.current_table(user).ok_or("you are not at a table")?;
+        if text.is_empty() {
+            return Err("nothing to say".to_owned());
+        }
+        self.broadcast(table_id, &format!("CHAT {} {} {}", table_id, user, text));
+        Ok(())
+    }
+
+    fn leave(&mut self, user: &str) -> bool {
+        let Some(table_id) = self.current_table(user) else {
+            return false;
+        };
+        self.seat(user, None);
+        let table = self.tables.get_mut(&table_id).unwrap();
+        table.spectators.retain(|spectator| spectator != user);
+        let marker = table.marker_of(user);
+        let status = table.status;
+        self.send(user, &format!("OK left {}", table_id));
+
+        if let Some(marker) = marker {
+            if status == TableStatus::Playing {
+                let winner = self.tables[&table_id]
+                    .player_with(marker.opponent())
+                    .map(str::to_owned);
+                self.finish(table_id, winner.as_deref(), "forfeit");
+            }
+            let table = self.tables.get_mut(&table_id).unwrap();
+            table.players = table
+                .players
+                .clone()
+                .map(|player| player.filter(|player| player != user));
+            if table.status == TableStatus::Waiting {
+                table.status = TableStatus::Finished;
+            }
+        }
+        self.broadcast(table_id, &format!("LEFT {} {}", table_id, user));
+
+        let table = &self.tables[&table_id];
+        if table.status == TableStatus::Finished && table.members().is_empty() {
+            self.tables.remove(&table_id);
+        }
+        true
+    }
+
+    fn scores(&self, user: &str, argument: &str) -> Result<(), String> {
+        let names = match argument {
+            "" => self.leaderboards.keys().sorted().cloned().collect(),
+            name if self.leaderboards.contains_key(name) => vec![name.to_owned()],
+            name => return Err(format!("unknown user {}", name)),
+        };
+        for name in names {
+            let board = &self.leaderboards[&name];
+            self.send(
+                user,
+                &format!(
+                    "SCORE {} wins={} losses={} ties={}",
+                    name, board.player, board.cpu, board.tie
+                ),
+            );
+        }
+        self.send(user, "END");
+        Ok(())
+    }
+}
+
+fn serve_client(stream: TcpStream, lobby: Arc<Mutex<Lobby>>) -> io::Result<()> {
+    let (sender, receiver) = mpsc::channel::<String>();
+    let mut writer = stream.try_clone()?;
+    thread::spawn(move || {
+        for line in receiver {
+            if writeln!(writer, "{}", line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut user: Option<String> = None;
+    for line in BufReader::new(stream).lines() {
+        // A broken connection still has to log the user out below.
+        let Ok(line) = line else {
+            break;
+        };
+        match &user {
+            Some(name) => {
+                if !lobby.lock().unwrap().handle(name, &line) {
+                    break;
+                }
+            }
+            None => {
+                let (command, name) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+                if !command.eq_ignore_ascii_case("LOGIN") {
+                    let _ = sender.send("ERR please LOGIN <name> first".to_owned());
+                    continue;
+                }
+                match lobby.lock().unwrap().login(name.trim(), sender.clone()) {
+                    Ok(()) => user = Some(name.trim().to_owned()),
+                    Err(message) => {
+                        let _ = sender.send(format!("ERR {}", message));
+                    }
+                }
+            }
+        }
+    }
+    if let Some(name) = user {
+        lobby.lock().unwrap().logout(&name);
+    }
+    Ok(())
+}
+
+pub struct LobbyServer {
+    local_addr: SocketAddr,
+    acceptor: thread::JoinHandle<()>,
+}
+
+impl LobbyServer {
+    pub fn start(address: &str) -> io::Result<LobbyServer> {
+        let listener = TcpListener::bind(address)?;
+        let local_addr = listener.local_addr()?;
+        let lobby = Arc::new(Mutex::new(Lobby::default()));
+        let acceptor = thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let lobby = Arc::clone(&lobby);
+                thread::spawn(move || serve_client(stream, lobby));
+            }
+        });
+        Ok(LobbyServer {
+            local_addr,
+            acceptor,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn wait(self) {
+        let _ = self.acceptor.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+    use std::time::Duration;
+
+    struct TestClient {
+        name: String,
+        receiver: Receiver<String>,
+    }
+
+    impl TestClient {
+        fn login(lobby: &mut Lobby, name: &str) -> TestClient {
+            let (sender, receiver) = mpsc::channel();
+            lobby.login(name, sender).unwrap();
+            let client = TestClient {
+                name: name.to_owned(),
+                receiver,
+            };
+            assert_eq!(client.drain(), vec![format!("OK welcome {}", name)]);
+            client
+        }
+
+        fn send(&self, lobby: &mut Lobby, line: &str) -> Vec<String> {
+            lobby.handle(&self.name, line);
+            self.drain()
+        }
+
+        fn drain(&self) -> Vec<String> {
+            self.receiver.try_iter().collect()
+        }
+    }
+
+    fn started_table(lobby: &mut Lobby) -> (TestClient, TestClient) {
+        let alice = TestClient::login(lobby, "alice");
+        let bob = TestClient::login(lobby, "bob");
+        alice.send(lobby, "CREATE");
+        bob.send(lobby, "JOIN 1");
+        alice.drain();
+        (alice, bob)
+    }
+
+    #[test]
+    fn login_should_reject_duplicate_and_invalid_names() {
+        let mut lobby = Lobby::default();
+        TestClient::login(&mut lobby, "alice");
+        assert!(lobby.login("alice", mpsc::channel().0).is_err());
+        assert!(lobby.login("not a name", mpsc::channel().0).is_err());
+        assert!(lobby.login("", mpsc::channel().0).is_err());
+    }
+
+    #[test]
+    fn list_should_show_open_and_running_tables() {
+        let mut lobby = Lobby::default();
+        let alice = TestClient::login(&mut lobby, "alice");
+        let carol = TestClient::login(&mut lobby, "carol");
+        assert_eq!(alice.send(&mut lobby, "CREATE 4"), vec!["OK table 1 X"]);
+        assert_eq!(
+            carol.send(&mut lobby, "list"),
+            vec!["TABLE 1 waiting 4 alice - 0", "END"]
+        );
+        assert_eq!(
+            alice.send(&mut lobby, "CREATE"),
+            vec!["ERR leave table 1 first"]
+        );
+    }
+
+    #[test]
+    fn join_should_start_the_game_for_both_players() {
+        let mut lobby = Lobby::default();
+        let alice = TestClient::login(&mut lobby, "alice");
+        let bob = TestClient::login(&mut lobby, "bob");
+        alice.send(&mut lobby, "CREATE");
+
+        assert_eq!(
+            bob.send(&mut lobby, "JOIN 1"),
+            vec![
+                "OK table 1 O",
+                "START 1 X alice O bob",
+                "BOARD 1 .../.../...",
+                "TURN 1 alice"
+            ]
+        );
+        assert_eq!(
+            alice.drain(),
+            vec![
+                "START 1 X alice O bob",
+                "BOARD 1 .../.../...",
+                "TURN 1 alice"
+            ]
+        );
+        assert_eq!(
+            bob.send(&mut lobby, "JOIN 1"),
+            vec!["ERR leave table 1 first"]
+        );
+    }
+
+    #[test]
+    fn moves_should_be_checked_and_broadcast_to_spectators() {
+        let mut lobby = Lobby::default();
+        let (alice, bob) = started_table(&mut lobby);
+        let carol = TestClient::login(&mut lobby, "carol");
+        assert_eq!(
+            carol.send(&mut lobby, "SPECTATE 1"),
+            vec!["OK spectating 1", "BOARD 1 .../.../..."]
+        );
+
+        assert_eq!(
+            bob.send(&mut lobby, "MOVE 5"),
+            vec!["ERR it is not your turn"]
+        );
+        assert_eq!(
+            carol.send(&mut lobby, "MOVE 5"),
+            vec!["ERR spectators cannot play"]
+        );
+        alice.send(&mut lobby, "MOVE 5");
+        bob.drain();
+        assert_eq!(
+            carol.drain(),
+            vec!["MOVED 1 alice 5", "BOARD 1 .../.X./...", "TURN 1 bob"]
+        );
+        assert_eq!(
+            bob.send(&mut lobby, "MOVE 5"),
+            vec!["ERR position 5 is not available"]
+        );
+    }
+
+    #[test]
+    fn finished_games_should_update_each_users_leaderboard() {
+        let mut lobby = Lobby::default();
+        let (alice, bob) = started_table(&mut lobby);
+        for (player, position) in [(&alice, 1), (&bob, 4), (&alice, 2), (&bob, 5), (&alice, 3)] {
+            player.send(&mut lobby, &format!("MOVE {}", position));
+        }
+        let messages = bob.drain();
+        assert_eq!(
+            messages[messages.len() - 2..],
+            ["BOARD 1 XXX/OO./...", "RESULT 1 winner alice line"]
+        );
+
+        assert_eq!(
+            bob.send(&mut lobby, "LEADERBOARD"),
+            vec![
+                "SCORE alice wins=1 losses=0 ties=0",
+                "SCORE bob wins=0 losses=1 ties=0",
+                "END"
+            ]
+        );
+        assert_eq!(
+            bob.send(&mut lobby, "LEADERBOARD dave"),
+            vec!["ERR unknown user dave"]
+        );
+    }
+
+    #[test]
+    fn chat_should_reach_everyone_at_the_table() {
+        let mut lobby = Lobby::default();
+        let (alice, bob) = started_table(&mut lobby);
+        bob.drain();
+        let carol = TestClient::login(&mut lobby, "carol");
+        carol.send(&mut lobby, "SPECTATE 1");
+
+        alice.send(&mut lobby, "SAY good luck!");
+        assert_eq!(bob.drain(), vec!["CHAT 1 alice good luck!"]);
+        assert_eq!(carol.drain(), vec!["CHAT 1 alice good luck!"]);
+    }
+
+    #[test]
+    fn leaving_a_running_game_should_forfeit_it() {
+        let mut lobby = Lobby::default();
+        let (alice, bob) = started_table(&mut lobby);
+        bob.drain();
+
+        lobby.logout("bob");
+        assert_eq!(
+            alice.drain(),
+            vec![
+                "BOARD 1 .../.../...",
+                "RESULT 1 winner alice forfeit",
+                "LEFT 1 bob"
+            ]
+        );
+        assert_eq!(lobby.leaderboards["bob"].cpu, 1);
+
+        assert_eq!(alice.send(&mut lobby, "LEAVE"), vec!["OK left 1"]);
+        assert!(lobby.tables.is_empty());
+        assert_eq!(
+            alice.send(&mut lobby, "LEAVE"),
+            vec!["ERR you are not at a table"]
+        );
+    }
+
+    fn connect(address: SocketAddr) -> (BufReader<TcpStream>, TcpStream) {
+        let stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        (BufReader::new(stream.try_clone().unwrap()), stream)
+    }
+
+    fn expect(reader: &mut BufReader<TcpStream>, expected: &str) {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line.trim_end(), expected);
+    }
+
+    #[test]
+    fn lobby_server_should_play_a_game_between_scripted_clients() {
+        let server = LobbyServer::start("127.0.0.1:0").unwrap();
+        let (mut alice_in, mut alice) = connect(server.local_addr());
+        let (mut bob_in, mut bob) = connect(server.local_addr());
+
+        writeln!(alice, "LIST").unwrap();
+        expect(&mut alice_in, "ERR please LOGIN <name> first");
+        writeln!(alice, "LOGIN alice\nCREATE").unwrap();
+        expect(&mut alice_in, "OK welcome alice");
+        expect(&mut alice_in, "OK table 1 X");
+
+        writeln!(bob, "LOGIN bob\nJOIN 1").unwrap();
+        expect(&mut bob_in, "OK welcome bob");
+        expect(&mut bob_in, "OK table 1 O");
+        expect(&mut alice_in, "START 1 X alice O bob");
+        expect(&mut alice_in, "BOARD 1 .../.../...");
+        expect(&mut alice_in, "TURN 1 alice");
+
+        writeln!(alice, "MOVE 9").unwrap();
+        expect(&mut bob_in, "START 1 X alice O bob");
+        expect(&mut bob_in, "BOARD 1 .../.../...");
+        expect(&mut bob_in, "TURN 1 alice");
+        expect(&mut bob_in, "MOVED 1 alice 9");
+
+        writeln!(bob, "QUIT").unwrap();
+        expect(&mut bob_in, "BOARD 1 .../.../..X");
+        expect(&mut bob_in, "TURN 1 bob");
+        expect(&mut bob_in, "BYE");
+        expect(&mut alice_in, "MOVED 1 alice 9");
+        expect(&mut alice_in, "BOARD 1 .../.../..X");
+        expect(&mut alice_in, "TURN 1 bob");
+        expect(&mut alice_in, "BOARD 1 .../.../..X");
+        expect(&mut alice_in, "RESULT 1 winner alice forfeit");
+        expect(&mut alice_in, "LEFT 1 bob");
+    }
+
+    #[test]
+    fn lobby_server_should_log_out_a_client_whose_connection_breaks() {
+        let server = LobbyServer::start("127.0.0.1:0").unwrap();
+        let (mut alice_in, mut alice) = connect(server.local_addr());
+        let (mut bob_in, mut bob) = connect(server.local_addr());
+
+        writeln!(alice, "LOGIN alice\nCREATE").unwrap();
+        expect(&mut alice_in, "OK welcome alice");
+        expect(&mut alice_in, "OK table 1 X");
+        writeln!(bob, "LOGIN bob\nJOIN 1").unwrap();
+        expect(&mut bob_in, "OK welcome bob");
+        expect(&mut alice_in, "START 1 X alice O bob");
+        expect(&mut alice_in, "BOARD 1 .../.../...");
+        expect(&mut alice_in, "TURN 1 alice");
+
+        // Invalid UTF-8 makes the server's read fail instead of ending cleanly.
+        bob.write_all(b"\xff\n").unwrap();
+        drop(bob);
+        drop(bob_in);
+        expect(&mut alice_in, "BOARD 1 .../.../...");
+        expect(&mut alice_in, "RESULT 1 winner alice forfeit");
+        expect(&mut alice_in, "LEFT 1 bob");
+
+        let (mut again_in, mut again) = connect(server.local_addr());
+        writeln!(again, "LOGIN bob").unwrap();
+        expect(&mut again_in, "OK welcome bob");
+    }
+}
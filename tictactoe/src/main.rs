@@ -1,17 +1,52 @@
+use error::{GameError, GameResult};
 use grid::*;
-use std::io::{self};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use ui::Ui;
 
-use crate::cpu::parse_difficulty;
+use crate::cpu::{parse_personality, parse_strength};
 
+mod adaptive;
+mod cli;
+mod clock;
 mod cpu;
+mod editor;
+mod engine;
+mod error;
+mod game;
+mod gomoku;
 mod grid;
+mod input;
+mod lobby;
 mod mocktest;
+mod notakto;
+mod numerical;
+mod order_chaos;
+mod phantom;
+mod puzzle;
+mod quantum;
+mod save;
+mod server;
+mod sliding;
+mod tui;
+mod ui;
+mod vanishing;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameRecord {
+    winner: Option<Player>,
+    cpu_strength: u8,
+    clock: Option<clock::ClockRecord>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Leaderboard {
     cpu: i32,
     player: i32,
     tie: i32,
+    games: Vec<GameRecord>,
+    puzzles: puzzle::PuzzleProgress,
 }
 
 impl Leaderboard {
@@ -22,118 +57,913 @@ impl Leaderboard {
             Some(Player::Human) => self.player += 1,
         }
     }
+
+    fn record_game(
+        &mut self,
+        winner: Option<Player>,
+        cpu_strength: u8,
+        clock: Option<clock::ClockRecord>,
+    ) {
+        self.update_score(winner);
+        self.games.push(GameRecord {
+            winner,
+            cpu_strength,
+            clock,
+        });
+    }
+
+    fn results(&self) -> Vec<Option<Player>> {
+        self.games.iter().map(|game| game.winner).collect()
+    }
+
+    fn render(&self) -> String {
+        let mut lines = vec![format!(
+            "Player: {} | CPU: {} | Tie: {}",
+            self.player, self.cpu, self.tie
+        )];
+        for (idx, game) in self.games.iter().enumerate() {
+            let outcome = match game.winner {
+                Some(Player::Human) => "Player won",
+                Some(Player::Cpu) => "CPU won",
+                None => "Tie",
+            };
+            let clock = game.clock.map_or(String::new(), |clock| {
+                let flagged = match clock.flagged {
+                    Some(Player::Human) => ", you ran out of time",
+                    Some(Player::Cpu) => ", the CPU ran out of time",
+                    None => "",
+                };
+                format!(
+                    ", clock you {} | CPU {}{}",
+                    clock::format_duration(clock.human),
+                    clock::format_duration(clock.cpu),
+                    flagged
+                )
+            });
+            lines.push(format!(
+                "Game {}: {} (CPU strength {}/100{})",
+                idx + 1,
+                outcome,
+                game.cpu_strength,
+                clock
+            ));
+        }
+        if self.puzzles.attempts() > 0 {
+            lines.push(self.puzzles.render());
+        }
+        lines.join("\n")
+    }
 }
 
 fn main() {
-    main_menu();
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let time_limit = options.time_limit;
+    let spawn_opponent = |command: &Option<String>, profile| match command {
+        Some(command) => engine::Opponent::Engine(spawn_engine(command, time_limit)),
+        None => engine::Opponent::Cpu(profile),
+    };
+
+    match options.command {
+        cli::Command::Help => print!("{}", cli::USAGE),
+        cli::Command::Script { config, moves } => {
+            let mut opponent = spawn_opponent(&options.engine, config.profile);
+            match cli::run_script(&config, &moves, &mut opponent, &mut cpu::seeded_rng(seed)) {
+                Ok(output) => println!("{}", output),
+                Err(output) => {
+                    println!("{}", output);
+                    std::process::exit(2);
+                }
+            }
+        }
+        cli::Command::Simulate {
+            config,
+            games,
+            bots,
+        } => {
+            let mut opponents = bots.map(|bot| match bot {
+                cli::BotSpec::Cpu(profile) => engine::Opponent::Cpu(profile),
+                cli::BotSpec::Engine(command) => spawn_opponent(&Some(command), config.profile),
+            });
+            println!(
+                "{}",
+                cli::run_simulation(&config, games, &mut opponents, &mut cpu::seeded_rng(seed))
+            )
+        }
+        cli::Command::Engine(config) => {
+            let result = engine::run_engine(
+                io::stdin().lock(),
+                &mut io::stdout(),
+                &config.profile,
+                &mut cpu::seeded_rng(seed),
+            );
+            if let Err(error) = result {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        cli::Command::Serve { address, cors } => {
+            match server::ApiServer::start(&address, 4, cors) {
+                Ok(api_server) => {
+                    let bound = api_server
+                        .local_addr()
+                        .map_or(address, |bound| bound.to_string());
+                    println!("Serving the game API on http://{}", bound);
+                    api_server.wait();
+                }
+                Err(error) => {
+                    eprintln!("error: unable to serve on {}: {}", address, error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        cli::Command::Lobby(address) => match lobby::LobbyServer::start(&address) {
+            Ok(lobby_server) => {
+                println!("Lobby open on {}", lobby_server.local_addr());
+                lobby_server.wait();
+            }
+            Err(error) => {
+                eprintln!("error: unable to open the lobby on {}: {}", address, error);
+                std::process::exit(1);
+            }
+        },
+        cli::Command::Play(config) => {
+            let engine = options
+                .engine
+                .as_ref()
+                .map(|command| spawn_engine(command, time_limit));
+            let position = options.position.clone();
+            run_interactive(|ui| play_single_game(ui, config, seed, engine, position))
+        }
+        cli::Command::Menu => run_interactive(|ui| main_menu(ui, Path::new(save::SAVE_FILE), seed)),
+    }
+}
+
+fn spawn_engine(command: &str, time_limit: Duration) -> engine::EngineProcess {
+    match engine::EngineProcess::spawn(command, time_limit) {
+        Ok(engine) => engine,
+        Err(error) => {
+            eprintln!("error: unable to start engine '{}': {}", command, error);
+            std::process::exit(1);
+        }
+    }
 }
 
-fn print_main_screen_menu(selected_difficulty: cpu::Difficulty) {
-    println!(
+fn run_interactive<F>(run: F)
+where
+    F: FnOnce(&mut dyn Ui) -> GameResult<()>,
+{
+    ui::install_interrupt_handler();
+    let result = if tui::is_interactive_terminal() {
+        run(&mut tui::TerminalUi::new())
+    } else {
+        run(&mut ui::ConsoleUi::new())
+    };
+
+    match result {
+        Ok(()) => (),
+        Err(GameError::EndOfInput) => println!("Input closed, ending the session."),
+        Err(GameError::Interrupted) => std::process::exit(130),
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn print_main_screen_menu(
+    profile: &cpu::CpuProfile,
+    adaptive_enabled: bool,
+    seed: u64,
+    puzzle_rating: i32,
+    tutor_enabled: bool,
+) -> String {
+    format!(
         "
 Please select something from main menu!
 1 (s) - Start Game
-2 (d) - Set Difficulty (currently selected: {difficulty:?})
-3 (l) - Leaderboard and Score Display
-4 (q) - Quit
+2 (d) - Set CPU Strength (current effective strength: {strength}/100)
+3 (p) - Set CPU Personality (currently selected: {personality:?})
+4 (l) - Leaderboard and Score Display
+5 (r) - Set Random Seed (current session seed: {seed})
+6 (a) - Toggle Adaptive Strength (currently: {adaptive})
+7 (e) - Position Editor, play from a set up position
+8 (z) - Puzzle Trainer (current rating: {puzzle_rating})
+9 (t) - Toggle Tutor Commentary on CPU moves (currently: {tutor})
+10 (q) - Quit
 ",
-        difficulty = selected_difficulty
-    );
+        strength = profile.strength(),
+        personality = profile.personality,
+        seed = seed,
+        adaptive = if adaptive_enabled { "on" } else { "off" },
+        puzzle_rating = puzzle_rating,
+        tutor = if tutor_enabled { "on" } else { "off" }
+    )
 }
 
-fn main_menu() {
-    let mut selected_difficulty = cpu::Difficulty::Medium;
-    let mut leaderboard = Leaderboard {
-        cpu: 0,
-        player: 0,
-        tie: 0,
-    };
-    loop {
-        print_main_screen_menu(selected_difficulty);
-        let user_input = ask_user_input(|| io::stdin().lock())
-            .trim()
-            .to_lowercase()
-            .to_owned();
+struct Session {
+    config: game::GameConfig,
+    seed: u64,
+    rng: cpu::GameRng,
+    adaptive_strength: adaptive::AdaptiveStrength,
+    adaptive_enabled: bool,
+    leaderboard: Leaderboard,
+    current_game: Option<Grid>,
+    engine: Option<engine::EngineProcess>,
+}
 
-        if (user_input == "s") | (user_input == "1") {
-            let winner = play_game(selected_difficulty);
-            leaderboard.update_score(winner);
+impl Session {
+    fn new(seed: u64) -> Session {
+        Session {
+            config: game::GameConfig::default(),
+            seed,
+            rng: cpu::seeded_rng(seed),
+            adaptive_strength: adaptive::AdaptiveStrength::default(),
+            adaptive_enabled: false,
+            leaderboard: Leaderboard::default(),
+            current_game: None,
+            engine: None,
         }
-        if (user_input == "d") | (user_input == "2") {
-            selected_difficulty = get_user_input_with(parse_difficulty, || io::stdin().lock());
+    }
+
+    fn start_game<U: Ui + ?Sized>(&mut self, ui: &mut U) -> GameResult<()> {
+        let special_game = match self.config.variant {
+            game::RuleVariant::Phantom => {
+                Some(phantom::play_phantom(&self.config, ui, &mut self.rng))
+            }
+            game::RuleVariant::OrderChaos => Some(order_chaos::play_order_chaos(
+                &self.config,
+                ui,
+                &mut self.rng,
+            )),
+            game::RuleVariant::Vanishing => {
+                Some(vanishing::play_vanishing(&self.config, ui, &mut self.rng))
+            }
+            game::RuleVariant::Gomoku => Some(gomoku::play_gomoku(&self.config, ui, &mut self.rng)),
+            game::RuleVariant::Numerical => {
+                Some(numerical::play_numerical(&self.config, ui, &mut self.rng))
+            }
+            game::RuleVariant::Notakto => {
+                Some(notakto::play_notakto(&self.config, ui, &mut self.rng))
+            }
+            game::RuleVariant::Sliding => {
+                Some(sliding::play_sliding(&self.config, ui, &mut self.rng))
+            }
+            game::RuleVariant::Quantum => {
+                Some(quantum::play_quantum(&self.config, ui, &mut self.rng))
+            }
+            _ => None,
+        };
+        if let Some(result) = special_game {
+            self.record_result(result?, None);
+            return Ok(());
         }
-        if (user_input == "q") | (user_input == "4") {
-            std::process::exit(0);
+        let mut game_grid = self
+            .current_game
+            .take()
+            .unwrap_or_else(|| self.config.new_grid());
+        let mut opponent = match self.engine.take() {
+            Some(engine) => engine::Opponent::Engine(engine),
+            None => engine::Opponent::Cpu(self.config.profile),
+        };
+        let mut game_clock = clock::Clock::new(self.config.time_control);
+        let result = play_game(
+            &self.config,
+            &mut opponent,
+            &mut self.rng,
+            ui,
+            &mut game_grid,
+            &mut game_clock,
+        );
+        if let engine::Opponent::Engine(engine) = opponent {
+            self.engine = Some(engine);
+        }
+        let winner = match result {
+            Ok(winner) => winner,
+            Err(error) => {
+                self.current_game = Some(game_grid);
+                return Err(error);
+            }
+        };
+
+        self.record_result(winner, game_clock.record());
+        Ok(())
+    }
+
+    fn record_result(&mut self, winner: Option<Player>, clock: Option<clock::ClockRecord>) {
+        self.leaderboard
+            .record_game(winner, self.config.profile.strength(), clock);
+        if self.adaptive_enabled {
+            let strength = self
+                .adaptive_strength
+                .next_strength(self.config.profile.strength(), &self.leaderboard.results());
+            self.config.profile =
+                cpu::CpuProfile::with_strength(self.config.profile.personality, strength);
         }
     }
-}
 
-fn play_game(difficulty: cpu::Difficulty) -> Option<grid::Player> {
-    println!("Starting a new game!");
-    let mut game_grid = grid::create_grid();
+    fn play_position<U: Ui + ?Sized>(
+        &mut self,
+        ui: &mut U,
+        config: game::GameConfig,
+        grid: Grid,
+    ) -> GameResult<()> {
+        let menu_config = self.config;
+        self.config = config;
+        self.current_game = Some(grid);
+        self.start_game(ui)?;
+        self.config = game::GameConfig {
+            profile: self.config.profile,
+            ..menu_config
+        };
+        Ok(())
+    }
 
-    while !game_grid.is_grid_full() {
-        game_grid = grid::make_user_turn(&game_grid, || io::stdin().lock());
+    fn run_menu<U: Ui + ?Sized>(&mut self, ui: &mut U) -> GameResult<()> {
+        loop {
+            let menu = print_main_screen_menu(
+                &self.config.profile,
+                self.adaptive_enabled,
+                self.seed,
+                self.leaderboard.puzzles.rating,
+                self.config.tutor,
+            );
+            let user_input = ui.prompt(&menu)?.trim().to_lowercase().to_owned();
 
-        if game_grid.is_grid_full() || game_grid.is_winning_grid().is_some() {
-            return game_grid.is_winning_grid();
+            if (user_input == "s") | (user_input == "1") {
+                self.start_game(ui)?;
+            }
+            if (user_input == "d") | (user_input == "2") {
+                let strength = get_user_input_with(parse_strength, ui)?;
+                self.config.profile =
+                    cpu::CpuProfile::with_strength(self.config.profile.personality, strength);
+            }
+            if (user_input == "p") | (user_input == "3") {
+                self.config.profile.personality = get_user_input_with(parse_personality, ui)?;
+            }
+            if (user_input == "l") | (user_input == "4") {
+                ui.show_message(&format!("\n{}", self.leaderboard.render()));
+            }
+            if (user_input == "r") | (user_input == "5") {
+                self.seed = get_user_input_with(parse_seed, ui)?;
+                self.rng = cpu::seeded_rng(self.seed);
+            }
+            if (user_input == "a") | (user_input == "6") {
+                self.adaptive_enabled = !self.adaptive_enabled;
+            }
+            if (user_input == "e") | (user_input == "7") {
+                if let Some((config, grid)) = editor::run_editor(ui, &self.config)? {
+                    self.play_position(ui, config, grid)?;
+                }
+            }
+            if (user_input == "z") | (user_input == "8") {
+                puzzle::run_puzzle(ui, &mut self.leaderboard.puzzles, &mut self.rng)?;
+            }
+            if (user_input == "t") | (user_input == "9") {
+                self.config.tutor = !self.config.tutor;
+            }
+            if (user_input == "q") | (user_input == "10") {
+                return Ok(());
+            }
         }
+    }
+}
+
+fn offer_to_save<U: Ui + ?Sized>(ui: &mut U, save_path: &Path, session: &Session) {
+    let answer =
+        ui.prompt("\nInterrupted! Save the game and the leaderboard before exiting? (y/n)");
+    if !matches!(answer, Ok(answer) if answer.trim().eq_ignore_ascii_case("y")) {
+        return;
+    }
+    save_to(ui, save_path, session);
+}
+
+fn save_to<U: Ui + ?Sized>(ui: &mut U, save_path: &Path, session: &Session) {
+    match save::save_session(
+        save_path,
+        &session.leaderboard,
+        session.current_game.as_ref(),
+    ) {
+        Ok(()) => ui.show_message(&format!("Saved to {}", save_path.display())),
+        Err(error) => ui.show_message(&format!("Unable to save: {}", error)),
+    }
+}
 
-        game_grid = cpu::make_cpu_move(&game_grid, difficulty);
+fn play_single_game<U: Ui + ?Sized>(
+    ui: &mut U,
+    config: game::GameConfig,
+    seed: u64,
+    engine: Option<engine::EngineProcess>,
+    position: Option<Grid>,
+) -> GameResult<()> {
+    let mut session = Session::new(seed);
+    session.config = config;
+    session.engine = engine;
+    session.current_game = position;
+    session.start_game(ui)?;
+    ui.show_message(&session.leaderboard.render());
+    Ok(())
+}
+
+fn main_menu<U: Ui + ?Sized>(ui: &mut U, save_path: &Path, seed: u64) -> GameResult<()> {
+    let mut session = Session::new(seed);
+    match save::load_session(save_path) {
+        Ok(Some((leaderboard, saved_game))) => {
+            session.leaderboard = leaderboard;
+            if saved_game.is_some() {
+                ui.show_message("A saved game was found, starting a game will resume it.");
+            }
+            session.current_game = saved_game;
+        }
+        Ok(None) => (),
+        Err(error) => ui.show_message(&format!("Ignoring unreadable save file: {}", error)),
+    }
 
-        if game_grid.is_grid_full() || game_grid.is_winning_grid().is_some() {
-            return game_grid.is_winning_grid();
+    let puzzle_attempts = session.leaderboard.puzzles.attempts();
+    let result = session.run_menu(ui);
+    match result {
+        Err(GameError::Interrupted) => offer_to_save(ui, save_path, &session),
+        // Solved puzzles are kept without asking, the rating is the point.
+        Ok(()) if session.leaderboard.puzzles.attempts() != puzzle_attempts => {
+            save_to(ui, save_path, &session)
         }
+        _ => (),
     }
-    None
+    result
 }
 
-fn get_user_input_with<F, G, R, T>(parser: F, mut reader: G) -> T
-where
-    G: FnMut() -> R,
-    R: io::BufRead,
-    F: Fn(R) -> Option<T>,
-{
-    let mut input = None;
-    while input.is_none() {
-        input = parser(reader());
+fn play_game<U: Ui + ?Sized>(
+    config: &game::GameConfig,
+    opponent: &mut engine::Opponent,
+    rng: &mut cpu::GameRng,
+    ui: &mut U,
+    game_grid: &mut Grid,
+    game_clock: &mut clock::Clock,
+) -> GameResult<Option<grid::Player>> {
+    ui.show_message("Starting a new game!");
+    let mut last_move = None;
+
+    while !config.is_over(game_grid) {
+        let player = config.player_to_move(game_grid);
+        let marker = config.marker_of(player);
+        let started = Instant::now();
+        let grid_after_move = match player {
+            Player::Human => {
+                if let Some(status) = game_clock.status() {
+                    ui.show_message(&status);
+                }
+                let budget = game_clock.budget(player);
+                match grid::make_user_turn(game_grid, ui, last_move, marker, budget) {
+                    Ok(grid_after_move) => Some(grid_after_move),
+                    Err(GameError::Timeout) => None,
+                    Err(error) => return Err(error),
+                }
+            }
+            Player::Cpu => match opponent.play(config, game_grid, player, rng) {
+                Ok((grid_after_move, cpu_move)) => {
+                    ui.show_message(&format!("CPU move rationale: {}", cpu_move.rule));
+                    if config.tutor {
+                        let comment = cpu::describe_move(game_grid, &marker, &cpu_move);
+                        ui.show_message(&format!("Tutor: the CPU {}.", comment));
+                    }
+                    Some(grid_after_move)
+                }
+                Err(error) => {
+                    ui.show_message(&format!("The engine forfeits the game: {}", error));
+                    ui.show_board(game_grid, &ui::Highlights::default());
+                    return Ok(Some(Player::Human));
+                }
+            },
+        };
+
+        let in_time = match grid_after_move {
+            Some(_) => game_clock.charge(player, started.elapsed()),
+            None => {
+                game_clock.flag(player);
+                false
+            }
+        };
+        let grid_after_move = match grid_after_move {
+            Some(grid_after_move) if in_time => grid_after_move,
+            _ => {
+                let who = match player {
+                    Player::Human => "you",
+                    Player::Cpu => "the CPU",
+                };
+                match config.time_control.on_timeout {
+                    clock::TimeoutRule::Loss => {
+                        ui.show_message(&format!(
+                            "Time is up for {}, the game is lost on time.",
+                            who
+                        ));
+                        ui.show_board(game_grid, &ui::Highlights::default());
+                        return Ok(Some(player.opponent()));
+                    }
+                    clock::TimeoutRule::RandomMove => {
+                        let position = cpu::choose_random_position(game_grid, rng);
+                        ui.show_message(&format!(
+                            "Time is up for {}, a random move is played at {}.",
+                            who,
+                            game_grid.position_number(position)
+                        ));
+                        let mut grid_after_move = game_grid.clone();
+                        grid_after_move.insert(position, marker);
+                        grid_after_move
+                    }
+                }
+            }
+        };
+        last_move = grid_after_move.newly_filled_position(game_grid);
+        *game_grid = grid_after_move;
     }
-    input.unwrap()
+
+    let highlights = ui::Highlights {
+        cursor: None,
+        last_move,
+        winning_line: game_grid.winning_line().unwrap_or_default(),
+        fading: None,
+    };
+    ui.show_board(game_grid, &highlights);
+    Ok(config.winner(game_grid))
+}
+
+fn parse_seed<U: Ui + ?Sized>(ui: &mut U) -> GameResult<Option<u64>> {
+    Ok(ui
+        .prompt(
+            "\nPlease enter a seed (a non-negative integer), replaying a seed replays the session:",
+        )?
+        .trim()
+        .parse()
+        .ok())
 }
 
-pub fn ask_user_input<G, R>(mut reader: G) -> String
+fn get_user_input_with<F, U, T>(parser: F, ui: &mut U) -> GameResult<T>
 where
-    G: FnMut() -> R,
-    R: io::BufRead,
+    U: Ui + ?Sized,
+    F: Fn(&mut U) -> GameResult<Option<T>>,
 {
-    let mut buffer = String::new();
-    reader().read_line(&mut buffer).expect("Unable to read");
-    buffer
+    loop {
+        if let Some(input) = parser(ui)? {
+            return Ok(input);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ui::{RecordingUi, UiEvent};
 
     #[test]
-    fn test_request_user_input() {
-        let mock_reader = move || "I'm George".as_bytes();
-        let answer = ask_user_input(mock_reader);
-        assert_eq!("I'm George", answer);
+    fn test_get_user_input_with_consecutive_invalid_then_valid_input() {
+        let mut ui = RecordingUi::new(&["invalid\n", "c\n"]);
+
+        let selected_personality = get_user_input_with(cpu::parse_personality, &mut ui).unwrap();
+
+        assert_eq!(selected_personality, crate::cpu::Personality::Chaotic);
+        assert_eq!(ui.prompts().len(), 2);
     }
 
     #[test]
-    fn test_get_user_input_with_consecutive_invalid_then_valid_input() {
-        let mut mock_inputs = vec!["invalid\n", "l\n"];
+    fn parse_seed_should_only_accept_non_negative_integers() {
+        let parse = |input| parse_seed(&mut RecordingUi::new(&[input])).unwrap();
+        assert_eq!(parse("1234\n"), Some(1234));
+        assert_eq!(parse("0\n"), Some(0));
+        assert_eq!(parse("-3\n"), None);
+        assert_eq!(parse("seed\n"), None);
+    }
 
-        let get_mock_reader = move || {
-            let input_str = mock_inputs.remove(0);
-            std::io::Cursor::new(input_str.as_bytes())
+    #[test]
+    fn play_game_should_route_all_output_through_ui() {
+        let mut ui = RecordingUi::new(&["1", "2", "3", "4", "5", "6", "7", "8", "9"]);
+        let config = game::GameConfig {
+            profile: cpu::CpuProfile::with_strength(cpu::Personality::Defensive, 100),
+            ..game::GameConfig::default()
         };
 
-        let selected_difficulty = get_user_input_with(cpu::parse_difficulty, get_mock_reader);
+        let mut game_grid = grid::create_grid();
+        let winner = play_game(
+            &config,
+            &mut engine::Opponent::Cpu(config.profile),
+            &mut cpu::seeded_rng(1),
+            &mut ui,
+            &mut game_grid,
+            &mut clock::Clock::new(Default::default()),
+        )
+        .unwrap();
 
-        assert_eq!(selected_difficulty, crate::cpu::Difficulty::Low);
+        assert_ne!(winner, Some(Player::Human));
+        assert_eq!(ui.messages()[0], "Starting a new game!");
+        assert!(ui
+            .messages()
+            .iter()
+            .any(|message| message.starts_with("CPU move rationale: ")));
+        assert!(matches!(ui.events.last(), Some(UiEvent::Board(_))));
+    }
+
+    #[test]
+    fn play_game_should_comment_cpu_moves_in_tutor_mode() {
+        let config = game::GameConfig {
+            tutor: true,
+            ..game::GameConfig::default()
+        };
+        let mut ui = RecordingUi::new(&["1", "2"]);
+        let mut game_grid = config.new_grid();
+
+        let result = play_game(
+            &config,
+            &mut engine::Opponent::Cpu(config.profile),
+            &mut cpu::seeded_rng(3),
+            &mut ui,
+            &mut game_grid,
+            &mut clock::Clock::new(Default::default()),
+        );
+
+        assert!(matches!(result, Err(GameError::EndOfInput)));
+        let comments = ui
+            .messages()
+            .into_iter()
+            .filter(|message| message.starts_with("Tutor: the CPU "))
+            .collect::<Vec<_>>();
+        assert_eq!(comments[0], "Tutor: the CPU took the center.");
+        assert_eq!(comments[1], "Tutor: the CPU blocked your row 1.");
+    }
+
+    #[test]
+    fn play_game_should_replay_identically_with_same_seed_and_inputs() {
+        let config = game::GameConfig {
+            profile: cpu::CpuProfile::with_strength(cpu::Personality::Chaotic, 30),
+            ..game::GameConfig::default()
+        };
+        let replay = |seed| {
+            let mut ui = RecordingUi::new(&["1", "2", "3", "4", "5", "6", "7", "8", "9"]);
+            let mut game_grid = grid::create_grid();
+            let winner = play_game(
+                &config,
+                &mut engine::Opponent::Cpu(config.profile),
+                &mut cpu::seeded_rng(seed),
+                &mut ui,
+                &mut game_grid,
+                &mut clock::Clock::new(Default::default()),
+            )
+            .unwrap();
+            (winner, game_grid, ui.events)
+        };
+
+        assert_eq!(replay(2024), replay(2024));
+    }
+
+    #[test]
+    fn get_user_input_with_should_stop_when_input_runs_out() {
+        let mut ui = RecordingUi::new(&["invalid\n"]);
+        let result = get_user_input_with(cpu::parse_personality, &mut ui);
+        assert!(matches!(result, Err(GameError::EndOfInput)));
+    }
+
+    #[test]
+    fn play_game_should_resume_with_cpu_to_move() {
+        let mut ui = RecordingUi::new(&[]);
+        let mut game_grid = from_array([[1, 0, 0], [0, 0, 0], [0, 0, 0]]);
+
+        let result = play_game(
+            &Default::default(),
+            &mut engine::Opponent::Cpu(Default::default()),
+            &mut cpu::seeded_rng(3),
+            &mut ui,
+            &mut game_grid,
+            &mut clock::Clock::new(Default::default()),
+        );
+
+        assert!(matches!(result, Err(GameError::EndOfInput)));
+        assert_eq!(game_grid.count_markers(Marker::X), 1);
+        assert_eq!(game_grid.count_markers(Marker::O), 1);
+    }
+
+    #[test]
+    fn play_game_should_let_cpu_open_with_x_when_human_plays_o() {
+        let config = game::GameConfig {
+            human_marker: Marker::O,
+            first_player: Player::Cpu,
+            ..game::GameConfig::default()
+        };
+        let mut ui = RecordingUi::new(&[]);
+        let mut game_grid = config.new_grid();
+
+        let result = play_game(
+            &config,
+            &mut engine::Opponent::Cpu(config.profile),
+            &mut cpu::seeded_rng(3),
+            &mut ui,
+            &mut game_grid,
+            &mut clock::Clock::new(Default::default()),
+        );
+
+        assert!(matches!(result, Err(GameError::EndOfInput)));
+        assert_eq!(game_grid.count_markers(Marker::X), 1);
+        assert_eq!(game_grid.count_markers(Marker::O), 0);
+    }
+
+    #[test]
+    fn play_game_should_lose_on_time_when_configured() {
+        let config = game::GameConfig {
+            time_control: clock::TimeControl {
+                per_move: Some(Duration::ZERO),
+                ..clock::TimeControl::default()
+            },
+            ..game::GameConfig::default()
+        };
+        let mut ui = RecordingUi::new(&["5"]);
+        let mut game_grid = config.new_grid();
+        let mut game_clock = clock::Clock::new(config.time_control);
+
+        let winner = play_game(
+            &config,
+            &mut engine::Opponent::Cpu(config.profile),
+            &mut cpu::seeded_rng(3),
+            &mut ui,
+            &mut game_grid,
+            &mut game_clock,
+        )
+        .unwrap();
+
+        assert_eq!(winner, Some(Player::Cpu));
+        assert_eq!(game_grid, config.new_grid());
+        assert!(ui.messages().contains(&"Time left: you 0:00 | CPU 0:00"));
+        assert!(ui
+            .messages()
+            .contains(&"Time is up for you, the game is lost on time."));
+        assert_eq!(game_clock.record().unwrap().flagged, Some(Player::Human));
+    }
+
+    #[test]
+    fn play_game_should_play_random_moves_on_timeout_when_configured() {
+        let config = game::GameConfig {
+            time_control: clock::TimeControl {
+                per_move: Some(Duration::ZERO),
+                on_timeout: clock::TimeoutRule::RandomMove,
+                ..clock::TimeControl::default()
+            },
+            ..game::GameConfig::default()
+        };
+        let mut ui = RecordingUi::new(&[]);
+        let mut game_grid = config.new_grid();
+
+        play_game(
+            &config,
+            &mut engine::Opponent::Cpu(config.profile),
+            &mut cpu::seeded_rng(3),
+            &mut ui,
+            &mut game_grid,
+            &mut clock::Clock::new(config.time_control),
+        )
+        .unwrap();
+
+        assert!(config.is_over(&game_grid));
+        assert!(ui
+            .messages()
+            .iter()
+            .any(|message| message.starts_with("Time is up for you, a random move is played at ")));
+    }
+
+    #[test]
+    fn start_game_should_record_the_clock_with_the_result() {
+        let mut session = Session::new(5);
+        session.config.time_control.per_game = Some(Duration::from_secs(600));
+        let mut ui = RecordingUi::new(&["1", "2", "3", "4", "5", "6", "7", "8", "9"]);
+
+        session.start_game(&mut ui).unwrap();
+
+        let record = session.leaderboard.games[0].clock.unwrap();
+        assert_eq!(record.flagged, None);
+        assert!(record.human < Duration::from_secs(600));
+        assert!(session
+            .leaderboard
+            .render()
+            .contains(", clock you 0:00 | CPU 0:00)"));
+    }
+
+    #[test]
+    fn start_game_should_play_phantom_games_through_the_referee() {
+        let mut session = Session::new(5);
+        session.config.variant = game::RuleVariant::Phantom;
+        let inputs = ["1", "2", "3", "4", "5", "6", "7", "8", "9"].repeat(2);
+        let mut ui = RecordingUi::new(&inputs);
+
+        session.start_game(&mut ui).unwrap();
+
+        assert_eq!(session.leaderboard.games.len(), 1);
+        assert!(session.current_game.is_none());
+        assert_eq!(
+            ui.messages()[0],
+            "Starting a phantom game! You only see your own markers."
+        );
+    }
+
+    #[test]
+    fn start_game_should_play_quantum_games_on_their_own_board() {
+        let mut session = Session::new(5);
+        session.config.variant = game::RuleVariant::Quantum;
+        let inputs = ["1", "2", "3", "4", "5", "6", "7", "8", "9"].repeat(10);
+        let mut ui = RecordingUi::new(&inputs);
+
+        session.start_game(&mut ui).unwrap();
+
+        assert_eq!(session.leaderboard.games.len(), 1);
+        assert_eq!(
+            ui.messages()[0],
+            "Starting a quantum game! Each turn places two entangled marks."
+        );
+    }
+
+    #[test]
+    fn play_single_game_should_play_on_a_larger_board() {
+        let config = game::GameConfig {
+            size: 4,
+            ..game::GameConfig::default()
+        };
+        let inputs = (1..=16)
+            .map(|number| number.to_string())
+            .collect::<Vec<_>>();
+        let mut ui = RecordingUi::new(&inputs.iter().map(String::as_str).collect::<Vec<_>>());
+
+        play_single_game(&mut ui, config, 11, None, None).unwrap();
+
+        assert!(ui.messages().last().unwrap().starts_with("Player: "));
+        assert!(ui.prompts()[0].ends_with("13, 14, 15, 16"));
+    }
+
+    #[test]
+    fn session_should_keep_unfinished_game_when_input_runs_out() {
+        let mut ui = RecordingUi::new(&["s", "5"]);
+        let mut session = Session::new(7);
+
+        let result = session.run_menu(&mut ui);
+
+        assert!(matches!(result, Err(GameError::EndOfInput)));
+        let saved_game = session.current_game.unwrap();
+        assert_eq!(saved_game.count_markers(Marker::X), 1);
+        assert_eq!(saved_game.count_markers(Marker::O), 1);
+    }
+
+    #[test]
+    fn session_should_play_out_an_edited_position() {
+        let mut ui = RecordingUi::new(&["e", "load XX./OO./...", "play", "3", "q"]);
+        let mut session = Session::new(7);
+
+        session.run_menu(&mut ui).unwrap();
+
+        assert_eq!(session.leaderboard.results(), vec![Some(Player::Human)]);
+        assert_eq!(session.config, game::GameConfig::default());
+        assert!(session.current_game.is_none());
+    }
+
+    #[test]
+    fn session_should_quit_without_error() {
+        let mut ui = RecordingUi::new(&["l", "q"]);
+        assert!(Session::new(7).run_menu(&mut ui).is_ok());
+        assert_eq!(ui.prompts().len(), 2);
+    }
+
+    #[test]
+    fn offer_to_save_should_write_session_when_accepted() {
+        let path = std::env::temp_dir().join(format!(
+            "tictactoe_offer_to_save_{}.txt",
+            std::process::id()
+        ));
+        let mut session = Session::new(7);
+        session.leaderboard.record_game(Some(Player::Cpu), 80, None);
+        session.current_game = Some(from_array([[1, 0, 0], [0, -1, 0], [0, 0, 0]]));
+
+        offer_to_save(&mut RecordingUi::new(&["n"]), &path, &session);
+        assert!(!path.exists());
+
+        offer_to_save(&mut RecordingUi::new(&["y"]), &path, &session);
+        let loaded = save::load_session(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, Some((session.leaderboard, session.current_game)));
+    }
+
+    #[test]
+    fn main_menu_should_save_puzzle_progress_on_quit() {
+        let path = std::env::temp_dir().join(format!(
+            "tictactoe_puzzle_progress_{}.txt",
+            std::process::id()
+        ));
+        // The seeded puzzle offers 1, 3 and 8, any answer is an attempt.
+        let inputs = ["z", "1", "1", "q"];
+
+        main_menu(&mut RecordingUi::new(&inputs), &path, 7).unwrap();
+        let loaded = save::load_session(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let (leaderboard, _) = loaded.expect("Puzzle progress should be saved");
+        assert_eq!(leaderboard.puzzles.attempts(), 1);
     }
 
     #[test]
@@ -142,6 +972,7 @@ mod tests {
             cpu: 1,
             player: 3,
             tie: 0,
+            ..Leaderboard::default()
         };
 
         board.update_score(None);
@@ -154,6 +985,7 @@ mod tests {
             cpu: 1,
             player: 3,
             tie: 0,
+            ..Leaderboard::default()
         };
 
         let expected_score_player = &board.player + 1;
@@ -165,4 +997,45 @@ mod tests {
         board.update_score(Some(Player::Cpu));
         assert_eq!(board.cpu, expected_score_cpu);
     }
+
+    #[test]
+    fn record_game_should_keep_cpu_strength_with_each_result() {
+        let mut board = Leaderboard::default();
+
+        board.record_game(Some(Player::Human), 80, None);
+        board.record_game(None, 85, None);
+
+        assert_eq!(board.player, 1);
+        assert_eq!(board.tie, 1);
+        assert_eq!(board.results(), vec![Some(Player::Human), None]);
+        assert_eq!(
+            board.games[1],
+            GameRecord {
+                winner: None,
+                cpu_strength: 85,
+                clock: None,
+            }
+        );
+    }
+
+    #[test]
+    fn render_should_show_puzzle_progress_once_attempted() {
+        let mut board = Leaderboard::default();
+        assert!(!board.render().contains("Puzzles"));
+
+        board.puzzles.record(1000, true);
+        assert!(board
+            .render()
+            .ends_with("Puzzles: rating 1016 | solved 1 | failed 0"));
+    }
+
+    #[test]
+    fn render_should_list_scores_and_games() {
+        let mut board = Leaderboard::default();
+        board.record_game(Some(Player::Cpu), 40, None);
+
+        let rendered = board.render();
+        assert!(rendered.contains("Player: 0 | CPU: 1 | Tie: 0"));
+        assert!(rendered.contains("Game 1: CPU won (CPU strength 40/100)"));
+    }
 }
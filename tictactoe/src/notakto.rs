@@ -0,0 +1,529 @@
+//! Notakto: both players place X on any of several 3x3 boards. A board with
+//! three in a row is dead, and whoever kills the last live board loses.
+//!
+//! The CPU plays by Plambeck's misère quotient: every live board maps to an
+//! element of the monoid
+//!
+//! `<a, b, c, d | a² = 1, b³ = b, b²c = c, c³ = ac², b²d = d, cd = ad, d² = c²>`
+//!
+//! and the player to move loses exactly when the product over all boards is
+//! one of `a`, `b²`, `bc` or `c²`.
+
+use super::cpu::CpuProfile;
+use super::error::GameResult;
+use super::game::GameConfig;
+use super::grid::{self, Grid, GridChecker, Marker, Player};
+use super::ui::Ui;
+
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// Live boards up to rotation and reflection, with their quotient values.
+const BOARD_VALUES: [(&str, &str); 46] = [
+    (".../.../...", "c"),
+    (".../.X./...", "c2"),
+    ("X../.../...", "1"),
+    (".X./.../...", "1"),
+    ("XX./.../...", "ad"),
+    ("X.X/.../...", "b"),
+    (".X./X../...", "a"),
+    ("..X/X../...", "b"),
+    ("X../.X./...", "b"),
+    (".X./.X./...", "b"),
+    (".../X.X/...", "a"),
+    ("..X/.../X..", "a"),
+    ("XX./X../...", "b"),
+    ("X.X/X../...", "a"),
+    (".XX/X../...", "d"),
+    ("XX./.X./...", "ab"),
+    ("X.X/.X./...", "a"),
+    (".X./XX./...", "ab"),
+    ("..X/XX./...", "a"),
+    ("X../X.X/...", "d"),
+    (".X./X.X/...", "b"),
+    ("X.X/.../X..", "ab"),
+    (".XX/.../X..", "d"),
+    ("X../..X/X..", "a"),
+    (".X./..X/X..", "1"),
+    ("XX./XX./...", "a"),
+    ("X.X/XX./...", "b"),
+    (".XX/XX./...", "b"),
+    ("XX./X.X/...", "a"),
+    ("X.X/X.X/...", "b"),
+    (".XX/X../X..", "ab"),
+    ("XX./..X/X..", "b"),
+    ("X.X/..X/X..", "b"),
+    (".XX/..X/X..", "a"),
+    (".X./X.X/X..", "ab"),
+    ("..X/X.X/X..", "a"),
+    ("X../.XX/X..", "b"),
+    (".X./.XX/X..", "b"),
+    (".X./X.X/.X.", "a"),
+    ("X.X/.../X.X", "a"),
+    ("XX./.XX/X..", "a"),
+    ("XX./X.X/.X.", "b"),
+    ("X.X/X.X/.X.", "a"),
+    ("X.X/..X/XX.", "a"),
+    (".XX/X.X/X..", "b"),
+    (".XX/X.X/XX.", "a"),
+];
+
+// Exponents of a, b, c and d, kept in the normal form of the presentation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct QuotientValue {
+    a: u8,
+    b: u8,
+    c: u8,
+    d: u8,
+}
+
+impl QuotientValue {
+    pub const IDENTITY: QuotientValue = QuotientValue {
+        a: 0,
+        b: 0,
+        c: 0,
+        d: 0,
+    };
+
+    fn reduced(mut a: u8, mut b: u8, mut c: u8, mut d: u8) -> QuotientValue {
+        loop {
+            if d >= 2 {
+                (d, c) = (d - 2, c + 2);
+            } else if (c >= 1 && d >= 1) || c >= 3 {
+                // cd = ad and c³ = ac² both trade one c for an a.
+                (c, a) = (c - 1, a + 1);
+            } else if b >= 3 {
+                b -= 2;
+            } else if b == 2 && (c >= 1 || d >= 1) {
+                b = 0;
+            } else {
+                return QuotientValue { a: a % 2, b, c, d };
+            }
+        }
+    }
+
+    pub fn multiply(self, other: QuotientValue) -> QuotientValue {
+        QuotientValue::reduced(
+            self.a + other.a,
+            self.b + other.b,
+            self.c + other.c,
+            self.d + other.d,
+        )
+    }
+
+    // Words such as "ab", "c2" or "1" for the identity.
+    pub fn from_word(word: &str) -> Option<QuotientValue> {
+        let mut value = QuotientValue::IDENTITY;
+        let mut glyphs = word.chars().peekable();
+        while let Some(glyph) = glyphs.next() {
+            let power = if glyphs.next_if_eq(&'2').is_some() {
+                2
+            } else {
+                1
+            };
+            let generator = match glyph {
+                'a' => QuotientValue::reduced(power, 0, 0, 0),
+                'b' => QuotientValue::reduced(0, power, 0, 0),
+                'c' => QuotientValue::reduced(0, 0, power, 0),
+                'd' => QuotientValue::reduced(0, 0, 0, power),
+                '1' if word.len() == 1 => QuotientValue::IDENTITY,
+                _ => return None,
+            };
+            value = value.multiply(generator);
+        }
+        Some(value)
+    }
+
+    // The player to move loses from these positions.
+    pub fn is_p_position(self) -> bool {
+        [(1, 0, 0), (0, 2, 0), (0, 1, 1), (0, 0, 2)].contains(&(self.a, self.b, self.c))
+            && self.d == 0
+    }
+}
+
+fn cell_bits(grid: &Grid, transform: u8) -> u16 {
+    grid.grid
+        .iter()
+        .filter(|(_, marker)| **marker != Marker::Null)
+        .map(|((row, col), _)| {
+            let (mut row, mut col) = (*row, *col);
+            if transform & 4 != 0 {
+                (row, col) = (col, row);
+            }
+            if transform & 1 != 0 {
+                row = 2 - row;
+            }
+            if transform & 2 != 0 {
+                col = 2 - col;
+            }
+            1 << (row * 3 + col)
+        })
+        .sum()
+}
+
+fn canonical_bits(grid: &Grid) -> u16 {
+    (0..8)
+        .map(|transform| cell_bits(grid, transform))
+        .min()
+        .unwrap_or_default()
+}
+
+fn value_table() -> &'static HashMap<u16, QuotientValue> {
+    static TABLE: OnceLock<HashMap<u16, QuotientValue>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        BOARD_VALUES
+            .iter()
+            .map(|(notation, word)| {
+                let grid = Grid::from_notation(notation).expect("Valid board notation");
+                let value = QuotientValue::from_word(word).expect("Valid quotient word");
+                (canonical_bits(&grid), value)
+            })
+            .collect()
+    })
+}
+
+// Dead boards are out of the game and count as the identity.
+pub fn board_value(board: &Grid) -> QuotientValue {
+    if board.is_dead() {
+        return QuotientValue::IDENTITY;
+    }
+    value_table()
+        .get(&canonical_bits(board))
+        .copied()
+        .expect("Every live 3x3 board has a quotient value")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notakto {
+    pub boards: Vec<Grid>,
+}
+
+impl Notakto {
+    pub fn new(count: usize) -> Notakto {
+        Notakto {
+            boards: vec![grid::create_grid_of_size(3); count],
+        }
+    }
+
+    pub fn live_boards(&self) -> Vec<usize> {
+        (0..self.boards.len())
+            .filter(|index| !self.boards[*index].is_dead())
+            .collect()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.live_boards().is_empty()
+    }
+
+    pub fn moves(&self) -> Vec<(usize, (i32, i32))> {
+        self.live_boards()
+            .into_iter()
+            .flat_map(|index| {
+                self.boards[index]
+                    .extract_empty_positions()
+                    .into_values()
+                    .sorted()
+                    .map(move |position| (index, position))
+            })
+            .collect()
+    }
+
+    pub fn play(&mut self, index: usize, position: (i32, i32)) -> Result<(), String> {
+        match self.boards.get(index) {
+            None => Err(format!("there is no board {}", index + 1)),
+            Some(board) if board.is_dead() => Err(format!("board {} is dead", index + 1)),
+            Some(board) if board.grid.get(&position) != Some(&Marker::Null) => Err(format!(
+                "position {} on board {} is taken",
+                board.position_number(position),
+                index + 1
+            )),
+            Some(_) => {
+                self.boards[index].insert(position, Marker::X);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn value(&self) -> QuotientValue {
+        self.boards
+            .iter()
+            .map(board_value)
+            .fold(QuotientValue::IDENTITY, QuotientValue::multiply)
+    }
+
+    pub fn render(&self) -> String {
+        let mut headers = (0..self.boards.len()).map(|index| {
+            let title = format!("Board {}", index + 1);
+            if self.boards[index].is_dead() {
+                format!("{:<11}", title + " (dead)")
+            } else {
+                format!("{:<11}", title)
+            }
+        });
+        let rendered = self.boards.iter().map(Grid::render).collect::<Vec<_>>();
+        let rows = (0..5).map(|line| {
+            rendered
+                .iter()
+                .map(|board| format!("{:<11}", board.lines().nth(line).unwrap_or_default()))
+                .join("    ")
+        });
+        std::iter::once(headers.join("    "))
+            .chain(rows)
+            .map(|line| line.trim_end().to_owned())
+            .join("\n")
+    }
+}
+
+// Moves into a P-position when one exists, otherwise stalls without killing
+// a board so that the opponent has room to go wrong.
+pub fn choose_notakto_move<R: Rng>(
+    game: &Notakto,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> (usize, (i32, i32)) {
+    let moves = game.moves();
+    let after = |(index, position): (usize, (i32, i32))| {
+        let mut next = game.clone();
+        next.boards[index].insert(position, Marker::X);
+        next
+    };
+    let pick = |candidates: Vec<(usize, (i32, i32))>, rng: &mut R| candidates.choose(rng).copied();
+    if rng.gen_bool(profile.blunder_rate.clamp(0.0, 1.0)) {
+        return pick(moves, rng).expect("Notakto move requested on a finished game");
+    }
+
+    let winning = moves
+        .iter()
+        .filter(|candidate| {
+            let next = after(**candidate);
+            !next.is_over() && next.value().is_p_position()
+        })
+        .copied()
+        .collect::<Vec<_>>();
+    let safe = moves
+        .iter()
+        .filter(|(index, position)| {
+            let next = after((*index, *position));
+            !next.boards[*index].is_dead()
+        })
+        .copied()
+        .collect::<Vec<_>>();
+    pick(winning, rng)
+        .or_else(|| pick(safe, rng))
+        .or_else(|| pick(moves, rng))
+        .expect("Notakto move requested on a finished game")
+}
+
+fn choose_board<U: Ui + ?Sized>(game: &Notakto, ui: &mut U) -> GameResult<usize> {
+    let live = game.live_boards();
+    if let [index] = live[..] {
+        return Ok(index);
+    }
+    let choices = live.iter().map(|index| (index + 1).to_string()).join(", ");
+    loop {
+        let answer = ui.prompt(&format!(
+            "Please select one of the live boards:\n{}",
+            choices
+        ))?;
+        match answer.trim().parse::<usize>() {
+            Ok(number) if live.contains(&(number.wrapping_sub(1))) => return Ok(number - 1),
+            _ => ui.show_message(&format!("'{}' is not a live board.", answer.trim())),
+        }
+    }
+}
+
+pub fn play_notakto<U: Ui + ?Sized, R: Rng>(
+    config: &GameConfig,
+    ui: &mut U,
+    rng: &mut R,
+) -> GameResult<Option<Player>> {
+    let mut game = Notakto::new(config.boards);
+    ui.show_message(&format!(
+        "Starting Notakto on {} boards! Both sides play X, whoever kills the last board loses.",
+        config.boards
+    ));
+    let mut player = config.first_player;
+    loop {
+        ui.show_message(&game.render());
+        let (index, position) = match player {
+            Player::Human => {
+                let index = choose_board(&game, ui)?;
+                (index, ui.choose_position(&game.boards[index], None)?)
+            }
+            Player::Cpu => {
+                let (index, position) = choose_notakto_move(&game, &config.profile, rng);
+                ui.show_message(&format!(
+                    "The CPU plays position {} on board {}.",
+                    game.boards[index].position_number(position),
+                    index + 1
+                ));
+                (index, position)
+            }
+        };
+        if let Err(error) = game.play(index, position) {
+            ui.show_message(&format!("Invalid: {}", error));
+            continue;
+        }
+        if game.is_over() {
+            ui.show_message(&game.render());
+            return Ok(Some(player.opponent()));
+        }
+        player = player.opponent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::{seeded_rng, Personality};
+    use super::*;
+    use crate::ui::RecordingUi;
+
+    fn value(word: &str) -> QuotientValue {
+        QuotientValue::from_word(word).unwrap()
+    }
+
+    fn game(boards: &[&str]) -> Notakto {
+        Notakto {
+            boards: boards
+                .iter()
+                .map(|notation| Grid::from_notation(notation).unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn quotient_values_should_follow_the_relations() {
+        assert_eq!(value("a").multiply(value("a")), QuotientValue::IDENTITY);
+        assert_eq!(value("b2").multiply(value("b")), value("b"));
+        assert_eq!(value("b2").multiply(value("c")), value("c"));
+        assert_eq!(value("c2").multiply(value("c")), value("ac2"));
+        assert_eq!(value("c").multiply(value("d")), value("ad"));
+        assert_eq!(value("d").multiply(value("d")), value("c2"));
+        assert_eq!(QuotientValue::from_word("e"), None);
+        let p_positions = ["a", "b2", "bc", "c2"];
+        for word in [
+            "1", "a", "b", "ab", "b2", "ab2", "c", "ac", "bc", "abc", "c2", "ac2", "bc2", "abc2",
+            "d", "ad", "bd", "abd",
+        ] {
+            assert_eq!(
+                value(word).is_p_position(),
+                p_positions.contains(&word),
+                "{}",
+                word
+            );
+        }
+    }
+
+    #[test]
+    fn board_value_should_ignore_symmetry_and_dead_boards() {
+        assert_eq!(board_value(&grid::create_grid()), value("c"));
+        assert_eq!(
+            board_value(&Grid::from_notation(".../.X./...").unwrap()),
+            value("c2")
+        );
+        assert_eq!(
+            board_value(&Grid::from_notation("..X/.../...").unwrap()),
+            board_value(&Grid::from_notation("X../.../...").unwrap())
+        );
+        assert_eq!(
+            board_value(&Grid::from_notation("XXX/.../...").unwrap()),
+            QuotientValue::IDENTITY
+        );
+        assert_eq!(value_table().len(), BOARD_VALUES.len());
+    }
+
+    #[test]
+    fn notakto_should_track_dead_boards_and_reject_bad_moves() {
+        let mut notakto = game(&["XX./.../...", ".../.../..."]);
+        assert_eq!(notakto.moves().len(), 16);
+        notakto.play(0, (0, 2)).unwrap();
+        assert_eq!(notakto.live_boards(), vec![1]);
+        assert_eq!(notakto.play(0, (1, 1)), Err("board 1 is dead".to_owned()));
+        assert_eq!(
+            notakto.play(2, (1, 1)),
+            Err("there is no board 3".to_owned())
+        );
+        notakto.play(1, (1, 1)).unwrap();
+        assert_eq!(
+            notakto.play(1, (1, 1)),
+            Err("position 5 on board 2 is taken".to_owned())
+        );
+        assert!(!notakto.is_over());
+    }
+
+    #[test]
+    fn choose_notakto_move_should_take_the_center_of_a_single_board() {
+        let profile = CpuProfile::with_strength(Personality::Defensive, 100);
+        let single = Notakto::new(1);
+        assert_eq!(
+            choose_notakto_move(&single, &profile, &mut seeded_rng(1)),
+            (0, (1, 1))
+        );
+
+        // With two empty boards the product c² is already lost for the mover,
+        // who then avoids killing a board.
+        let double = Notakto::new(2);
+        assert!(double.value().is_p_position());
+        let (index, position) = choose_notakto_move(&double, &profile, &mut seeded_rng(1));
+        let mut next = double.clone();
+        next.play(index, position).unwrap();
+        assert_eq!(next.live_boards().len(), 2);
+    }
+
+    #[test]
+    fn choose_notakto_move_should_leave_a_p_position_whenever_possible() {
+        let profile = CpuProfile::with_strength(Personality::Defensive, 100);
+        for boards in [
+            ["X../.../...", ".X./.../..."],
+            ["XX./.../...", ".../.X./..."],
+        ] {
+            let notakto = game(&boards);
+            assert!(!notakto.value().is_p_position());
+            let (index, position) = choose_notakto_move(&notakto, &profile, &mut seeded_rng(2));
+            let mut next = notakto.clone();
+            next.play(index, position).unwrap();
+            assert!(next.value().is_p_position());
+        }
+    }
+
+    #[test]
+    fn play_notakto_should_make_the_human_lose_when_killing_the_last_board() {
+        let config = GameConfig {
+            boards: 1,
+            profile: CpuProfile::with_strength(Personality::Defensive, 100),
+            ..GameConfig::default()
+        };
+        let inputs = ["1", "2", "3", "4", "5", "6", "7", "8", "9"].repeat(2);
+        let mut ui = RecordingUi::new(&inputs);
+
+        let winner = play_notakto(&config, &mut ui, &mut seeded_rng(3)).unwrap();
+
+        assert_eq!(winner, Some(Player::Cpu));
+        assert!(ui.messages().last().unwrap().starts_with("Board 1 (dead)"));
+    }
+
+    #[test]
+    fn play_notakto_should_ask_for_a_live_board() {
+        let config = GameConfig {
+            boards: 2,
+            ..GameConfig::default()
+        };
+        let mut ui = RecordingUi::new(&["3", "2", "5"]);
+
+        let result = play_notakto(&config, &mut ui, &mut seeded_rng(3));
+
+        assert!(result.is_err());
+        assert_eq!(
+            ui.prompts()[0],
+            "Please select one of the live boards:\n1, 2"
+        );
+        assert!(ui.messages().contains(&"'3' is not a live board."));
+        assert!(ui
+            .messages()
+            .iter()
+            .any(|message| message.starts_with("The CPU plays position ")));
+    }
+}
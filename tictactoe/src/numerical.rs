@@ -0,0 +1,379 @@
+//! Graham's numerical tic-tac-toe: the opening player places the odd numbers
+//! 1-9, the other player the even ones, each number once. Whoever completes
+//! a full line adding up to 15 wins.
+
+use super::cpu::CpuProfile;
+use super::error::GameResult;
+use super::game::{GameConfig, RuleVariant};
+use super::grid::Player;
+use super::input;
+use super::ui::Ui;
+
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+pub const TARGET: u8 = 15;
+const SEARCH_DEPTH: usize = 3;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Parity {
+    Odd,
+    Even,
+}
+
+impl Parity {
+    pub fn other(self) -> Parity {
+        match self {
+            Parity::Odd => Parity::Even,
+            Parity::Even => Parity::Odd,
+        }
+    }
+
+    fn numbers(self) -> impl Iterator<Item = u8> {
+        let first = match self {
+            Parity::Odd => 1,
+            Parity::Even => 2,
+        };
+        (first..=9).step_by(2)
+    }
+}
+
+// Row-major cells of a 3x3 board, 0 for an empty cell. Not a Grid: its
+// cells hold a Marker owned by one side, while a number here belongs to
+// nobody once placed, and a Copy array keeps the lookahead free of clones.
+// The lines still come from RuleVariant::lines like every other board.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct NumberBoard {
+    cells: [u8; 9],
+}
+
+impl NumberBoard {
+    fn index((row, col): (i32, i32)) -> usize {
+        (row * 3 + col) as usize
+    }
+
+    fn position(index: usize) -> (i32, i32) {
+        ((index / 3) as i32, (index % 3) as i32)
+    }
+
+    pub fn get(&self, position: (i32, i32)) -> Option<u8> {
+        Some(self.cells[NumberBoard::index(position)]).filter(|value| *value != 0)
+    }
+
+    pub fn empty_cells(&self) -> Vec<(i32, i32)> {
+        (0..9)
+            .filter(|index| self.cells[*index] == 0)
+            .map(NumberBoard::position)
+            .collect()
+    }
+
+    // Numbers of the given parity that are not on the board yet.
+    pub fn pool(&self, parity: Parity) -> Vec<u8> {
+        parity
+            .numbers()
+            .filter(|number| !self.cells.contains(number))
+            .collect()
+    }
+
+    pub fn place(&mut self, position: (i32, i32), number: u8) -> Result<(), String> {
+        let index = NumberBoard::index(position);
+        if self.cells[index] != 0 {
+            return Err(format!("{} is already taken", label(position)));
+        }
+        if !(1..=9).contains(&number) || self.cells.contains(&number) {
+            return Err(format!("{} is not available", number));
+        }
+        self.cells[index] = number;
+        Ok(())
+    }
+
+    pub fn winning_line(&self) -> Option<Vec<(i32, i32)>> {
+        RuleVariant::Numerical
+            .lines(&GameConfig::default())
+            .into_iter()
+            .find(|line| {
+                let values = line.iter().map(|position| self.get(*position));
+                values.clone().all(|value| value.is_some())
+                    && values.map(|value| value.unwrap_or(0)).sum::<u8>() == TARGET
+            })
+    }
+
+    pub fn is_full(&self) -> bool {
+        !self.cells.contains(&0)
+    }
+
+    pub fn render(&self) -> String {
+        (0..3)
+            .map(|row| {
+                (0..3)
+                    .map(|col| match self.get((row, col)) {
+                        Some(number) => format!(" {:<2} ", number),
+                        None => format!(" {} ", label((row, col))),
+                    })
+                    .join("|")
+            })
+            .join("\n----+----+----\n")
+    }
+}
+
+// Empty cells show their algebraic coordinate so they never look like numbers.
+fn label((row, col): (i32, i32)) -> String {
+    format!("{}{}", (b'a' + col as u8) as char, row + 1)
+}
+
+fn moves(board: &NumberBoard, parity: Parity) -> Vec<((i32, i32), u8)> {
+    board
+        .empty_cells()
+        .into_iter()
+        .cartesian_product(board.pool(parity))
+        .collect()
+}
+
+fn negamax(board: &NumberBoard, parity: Parity, depth: usize) -> i32 {
+    if board.winning_line().is_some() {
+        return -(10 + depth as i32);
+    }
+    if depth == 0 || board.is_full() {
+        return 0;
+    }
+    moves(board, parity)
+        .into_iter()
+        .map(|(position, number)| {
+            let mut after = *board;
+            after.cells[NumberBoard::index(position)] = number;
+            -negamax(&after, parity.other(), depth - 1)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+// Looks a few moves ahead, so it takes any line to 15 and avoids leaving one.
+pub fn choose_numerical_move<R: Rng>(
+    board: &NumberBoard,
+    parity: Parity,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> ((i32, i32), u8) {
+    let candidates = moves(board, parity);
+    if rng.gen_bool(profile.blunder_rate.clamp(0.0, 1.0)) {
+        return *candidates
+            .choose(rng)
+            .expect("Move requested on a full board");
+    }
+    let scored = candidates
+        .into_iter()
+        .map(|(position, number)| {
+            let mut after = *board;
+            after.cells[NumberBoard::index(position)] = number;
+            (
+                (position, number),
+                -negamax(&after, parity.other(), SEARCH_DEPTH - 1),
+            )
+        })
+        .collect::<Vec<_>>();
+    let best_score = scored.iter().map(|(_, score)| *score).max().unwrap_or(0);
+    let best = scored
+        .into_iter()
+        .filter(|(_, score)| *score == best_score)
+        .map(|(choice, _)| choice)
+        .collect::<Vec<_>>();
+    *best.choose(rng).expect("Move requested on a full board")
+}
+
+fn prompt_for_number_move<U: Ui + ?Sized>(
+    board: &NumberBoard,
+    parity: Parity,
+    ui: &mut U,
+) -> GameResult<((i32, i32), u8)> {
+    let cells = board.empty_cells();
+    let position = loop {
+        let answer = ui.prompt(&format!(
+            "Please select one of the empty cells:\n{}",
+            cells.iter().map(|position| label(*position)).join(", ")
+        ))?;
+        match input::parse_coordinates(&answer, 3) {
+            Ok(position) if cells.contains(&position) => break position,
+            Ok(position) => ui.show_message(&format!("{} is already taken.", label(position))),
+            Err(error) => ui.show_message(&error.to_string()),
+        }
+    };
+    let pool = board.pool(parity);
+    loop {
+        let answer = ui.prompt(&format!(
+            "Which number do you place on {}?\n{}",
+            label(position),
+            pool.iter().join(", ")
+        ))?;
+        match answer.trim().parse::<u8>() {
+            Ok(number) if pool.contains(&number) => return Ok((position, number)),
+            _ => ui.show_message(&format!("'{}' is not one of your numbers.", answer.trim())),
+        }
+    }
+}
+
+pub fn play_numerical<U: Ui + ?Sized, R: Rng>(
+    config: &GameConfig,
+    ui: &mut U,
+    rng: &mut R,
+) -> GameResult<Option<Player>> {
+    let parity_of = |player: Player| {
+        if player == config.first_player {
+            Parity::Odd
+        } else {
+            Parity::Even
+        }
+    };
+    ui.show_message(&format!(
+        "Starting numerical tic-tac-toe! You place the {} numbers, complete a line adding up to {}.",
+        match parity_of(Player::Human) {
+            Parity::Odd => "odd",
+            Parity::Even => "even",
+        },
+        TARGET
+    ));
+    let mut board = NumberBoard::default();
+    let mut player = config.first_player;
+
+    loop {
+        ui.show_message(&board.render());
+        let parity = parity_of(player);
+        let (position, number) = match player {
+            Player::Human => prompt_for_number_move(&board, parity, ui)?,
+            Player::Cpu => {
+                let (position, number) =
+                    choose_numerical_move(&board, parity, &config.profile, rng);
+                ui.show_message(&format!(
+                    "The CPU places {} on {}.",
+                    number,
+                    label(position)
+                ));
+                (position, number)
+            }
+        };
+        if let Err(error) = board.place(position, number) {
+            ui.show_message(&format!("Invalid: {}", error));
+            continue;
+        }
+        if board.winning_line().is_some()
+            || board.is_full()
+            || board.pool(parity.other()).is_empty()
+        {
+            ui.show_message(&board.render());
+            return Ok(board.winning_line().map(|_| player));
+        }
+        player = player.opponent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::{seeded_rng, Personality};
+    use super::*;
+    use crate::ui::RecordingUi;
+
+    fn board_from(cells: [u8; 9]) -> NumberBoard {
+        NumberBoard { cells }
+    }
+
+    #[test]
+    fn pool_should_hold_the_unused_numbers_of_a_parity() {
+        let board = board_from([1, 0, 0, 0, 4, 0, 0, 0, 9]);
+        assert_eq!(board.pool(Parity::Odd), vec![3, 5, 7]);
+        assert_eq!(board.pool(Parity::Even), vec![2, 6, 8]);
+    }
+
+    #[test]
+    fn place_should_reject_taken_cells_and_used_numbers() {
+        let mut board = board_from([1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            board.place((0, 0), 3),
+            Err("a1 is already taken".to_owned())
+        );
+        assert_eq!(board.place((1, 1), 1), Err("1 is not available".to_owned()));
+        assert!(board.place((1, 1), 6).is_ok());
+        assert_eq!(board.get((1, 1)), Some(6));
+    }
+
+    #[test]
+    fn winning_line_should_need_a_full_line_adding_up_to_fifteen() {
+        assert_eq!(
+            board_from([8, 1, 6, 0, 0, 0, 0, 0, 0]).winning_line(),
+            Some(vec![(0, 0), (0, 1), (0, 2)])
+        );
+        assert_eq!(board_from([9, 6, 0, 0, 0, 0, 0, 0, 0]).winning_line(), None);
+        assert_eq!(
+            board_from([2, 0, 0, 0, 5, 0, 0, 0, 8]).winning_line(),
+            Some(vec![(0, 0), (1, 1), (2, 2)])
+        );
+        assert_eq!(board_from([2, 4, 6, 0, 0, 0, 0, 0, 0]).winning_line(), None);
+    }
+
+    #[test]
+    fn render_should_label_empty_cells_with_coordinates() {
+        let board = board_from([7, 0, 0, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(
+            board.render(),
+            " 7  | b1 | c1 \n----+----+----\n a2 | b2 | c2 \n----+----+----\n a3 | b3 | 2  "
+        );
+    }
+
+    #[test]
+    fn choose_numerical_move_should_win_and_block() {
+        let profile = CpuProfile::with_strength(Personality::Defensive, 100);
+        let board = board_from([8, 0, 0, 0, 4, 0, 0, 0, 0]);
+        let winning = choose_numerical_move(&board, Parity::Odd, &profile, &mut seeded_rng(1));
+        assert_eq!(winning, ((2, 2), 3));
+
+        // Most odd moves here leave a line the evens can finish at once.
+        let board = board_from([1, 0, 0, 0, 0, 0, 0, 0, 6]);
+        let (position, number) =
+            choose_numerical_move(&board, Parity::Odd, &profile, &mut seeded_rng(1));
+        let mut after = board;
+        after.place(position, number).unwrap();
+        assert!(moves(&after, Parity::Even)
+            .into_iter()
+            .all(|(position, number)| {
+                let mut reply = after;
+                reply.place(position, number).unwrap();
+                reply.winning_line().is_none()
+            }));
+    }
+
+    #[test]
+    fn play_numerical_should_ask_for_a_cell_and_a_number() {
+        let config = GameConfig::default();
+        let mut ui = RecordingUi::new(&["b2", "4", "5"]);
+
+        let result = play_numerical(&config, &mut ui, &mut seeded_rng(1));
+
+        assert!(result.is_err());
+        assert_eq!(
+            ui.prompts()[1],
+            "Which number do you place on b2?\n1, 3, 5, 7, 9"
+        );
+        assert!(ui.messages().contains(&"'4' is not one of your numbers."));
+        assert!(ui
+            .messages()
+            .iter()
+            .any(|message| message.starts_with("The CPU places ")));
+    }
+
+    #[test]
+    fn play_numerical_should_let_the_cpu_win_with_the_odd_numbers() {
+        let config = GameConfig {
+            first_player: Player::Cpu,
+            profile: CpuProfile::with_strength(Personality::Defensive, 100),
+            ..GameConfig::default()
+        };
+        let inputs = [
+            "a1", "2", "b1", "4", "c1", "6", "a2", "8", "b2", "c2", "a3", "b3", "c3",
+        ]
+        .repeat(3);
+        let mut ui = RecordingUi::new(&inputs);
+
+        let winner = play_numerical(&config, &mut ui, &mut seeded_rng(5)).unwrap();
+
+        assert_eq!(winner, Some(Player::Cpu));
+    }
+}
@@ -0,0 +1,258 @@
+//! Order and Chaos on a 6x6 board: both sides may place X or O. Order wins
+//! with five of one marker in a row, Chaos wins by filling the board first.
+//! Order always opens, so whoever moves first plays Order.
+
+use super::cpu::CpuProfile;
+use super::error::GameResult;
+use super::game::GameConfig;
+use super::grid::{self, Grid, GridChecker, Marker, Player};
+use super::ui::{Highlights, Ui};
+
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+pub const BOARD_SIZE: usize = 6;
+pub const RUN_LENGTH: usize = 5;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Role {
+    Order,
+    Chaos,
+}
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Order => "Order",
+            Role::Chaos => "Chaos",
+        }
+    }
+}
+
+// How close Order is to a five: every run still free of one of the two
+// markers counts, weighted by how many of the other it already holds.
+pub fn order_potential(grid: &Grid) -> u64 {
+    if grid.winning_line().is_some() {
+        return u64::MAX;
+    }
+    grid.lines()
+        .iter()
+        .map(|run| {
+            let count = |marker| {
+                run.iter()
+                    .filter(|position| grid.grid.get(position) == Some(&marker))
+                    .count() as u32
+            };
+            let (x_count, o_count) = (count(Marker::X), count(Marker::O));
+            match (x_count, o_count) {
+                (0, 0) => 1,
+                (held, 0) | (0, held) => 8u64.pow(held),
+                _ => 0,
+            }
+        })
+        .sum()
+}
+
+fn candidate_moves(grid: &Grid) -> Vec<((i32, i32), Marker)> {
+    grid.extract_empty_positions()
+        .into_values()
+        .sorted()
+        .cartesian_product([Marker::X, Marker::O])
+        .collect()
+}
+
+// Order raises the potential as far as it can and Chaos keeps it as low as
+// it can, which also makes Chaos avoid completing a five by accident.
+pub fn choose_role_move<R: Rng>(
+    grid: &Grid,
+    role: Role,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> ((i32, i32), Marker) {
+    let moves = candidate_moves(grid);
+    if rng.gen_bool(profile.blunder_rate.clamp(0.0, 1.0)) {
+        return *moves.choose(rng).expect("Move requested on a full board");
+    }
+    let scored = moves
+        .into_iter()
+        .map(|(position, marker)| {
+            let mut after = grid.clone();
+            after.insert(position, marker);
+            let potential = order_potential(&after);
+            let score = match role {
+                Role::Order => potential,
+                Role::Chaos => u64::MAX - potential,
+            };
+            ((position, marker), score)
+        })
+        .collect::<Vec<_>>();
+    let best_score = scored.iter().map(|(_, score)| *score).max().unwrap_or(0);
+    let best = scored
+        .into_iter()
+        .filter(|(_, score)| *score == best_score)
+        .map(|(choice, _)| choice)
+        .collect::<Vec<_>>();
+    *best.choose(rng).expect("Move requested on a full board")
+}
+
+pub fn play_order_chaos<U: Ui + ?Sized, R: Rng>(
+    config: &GameConfig,
+    ui: &mut U,
+    rng: &mut R,
+) -> GameResult<Option<Player>> {
+    let order = config.first_player;
+    let role_of = |player: Player| {
+        if player == order {
+            Role::Order
+        } else {
+            Role::Chaos
+        }
+    };
+    ui.show_message(&format!(
+        "Starting Order and Chaos! You play {}: Order needs {} of a kind in a row, Chaos fills the board.",
+        role_of(Player::Human).name(),
+        RUN_LENGTH
+    ));
+    let mut grid = config.new_grid();
+    let mut player = order;
+    let mut last_move = None;
+
+    while grid.winning_line().is_none() && !grid.is_grid_full() {
+        let grid_after_move = match player {
+            Player::Human => grid::make_user_choice_turn(&grid, ui, last_move, None)?.0,
+            Player::Cpu => {
+                let (position, marker) =
+                    choose_role_move(&grid, role_of(player), &config.profile, rng);
+                ui.show_message(&format!(
+                    "The CPU ({}) places {} at {}.",
+                    role_of(player).name(),
+                    marker.glyph(),
+                    grid.position_number(position)
+                ));
+                let mut grid_after_move = grid.clone();
+                grid_after_move.insert(position, marker);
+                grid_after_move
+            }
+        };
+        last_move = grid_after_move.newly_filled_position(&grid);
+        grid = grid_after_move;
+        player = player.opponent();
+    }
+
+    let winning_run = grid.winning_line();
+    let winner_role = if winning_run.is_some() {
+        Role::Order
+    } else {
+        Role::Chaos
+    };
+    ui.show_message(&format!("{} wins!", winner_role.name()));
+    let highlights = Highlights {
+        cursor: None,
+        last_move,
+        winning_line: winning_run.unwrap_or_default(),
+        fading: None,
+    };
+    ui.show_board(&grid, &highlights);
+    Ok([Player::Human, Player::Cpu]
+        .into_iter()
+        .find(|player| role_of(*player) == winner_role))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::{seeded_rng, Personality};
+    use super::super::game::RuleVariant;
+    use super::*;
+    use crate::ui::RecordingUi;
+
+    fn order_chaos() -> GameConfig {
+        GameConfig {
+            variant: RuleVariant::OrderChaos,
+            ..GameConfig::default()
+        }
+    }
+
+    fn board(cells: &[((i32, i32), Marker)]) -> Grid {
+        let mut grid = order_chaos().new_grid();
+        for (position, marker) in cells {
+            grid.insert(*position, *marker);
+        }
+        grid
+    }
+
+    #[test]
+    fn order_potential_should_ignore_mixed_runs_and_max_out_on_a_five() {
+        assert_eq!(order_potential(&board(&[])), 32);
+
+        let mixed = board(&[((0, 0), Marker::X), ((0, 1), Marker::O)]);
+        // The shared row run dies, five other runs now hold a single marker.
+        assert_eq!(order_potential(&mixed), 32 - 1 + 5 * 7);
+
+        let five = board(&(0..5).map(|col| ((2, col), Marker::O)).collect::<Vec<_>>());
+        assert_eq!(order_potential(&five), u64::MAX);
+    }
+
+    #[test]
+    fn choose_role_move_should_complete_or_break_a_four() {
+        let profile = CpuProfile::with_strength(Personality::Defensive, 100);
+        let four = board(&(1..5).map(|col| ((3, col), Marker::X)).collect::<Vec<_>>());
+
+        let (position, marker) = choose_role_move(&four, Role::Order, &profile, &mut seeded_rng(1));
+        assert!([(3, 0), (3, 5)].contains(&position));
+        assert_eq!(marker, Marker::X);
+
+        let (position, marker) = choose_role_move(&four, Role::Chaos, &profile, &mut seeded_rng(1));
+        assert!([(3, 0), (3, 5)].contains(&position));
+        assert_eq!(marker, Marker::O);
+    }
+
+    #[test]
+    fn choose_role_move_should_keep_chaos_from_completing_a_five() {
+        let profile = CpuProfile::with_strength(Personality::Defensive, 100);
+        let grid = board(&(0..4).map(|row| ((row, 0), Marker::O)).collect::<Vec<_>>());
+        let (position, marker) = choose_role_move(&grid, Role::Chaos, &profile, &mut seeded_rng(4));
+        let mut after = grid.clone();
+        after.insert(position, marker);
+        assert!(after.winning_line().is_none());
+    }
+
+    #[test]
+    fn play_order_chaos_should_let_the_human_pick_markers() {
+        let config = GameConfig {
+            first_player: Player::Cpu,
+            ..order_chaos()
+        };
+        let mut ui = RecordingUi::new(&["o", "1"]);
+
+        let result = play_order_chaos(&config, &mut ui, &mut seeded_rng(2));
+
+        assert!(result.is_err());
+        let messages = ui.messages();
+        assert!(messages[0].starts_with("Starting Order and Chaos! You play Chaos"));
+        assert!(messages[1].starts_with("The CPU (Order) places "));
+        assert_eq!(ui.prompts()[0], "Which marker do you place, X or O?");
+        assert!(ui.prompts()[1].ends_with("36"));
+    }
+
+    #[test]
+    fn play_order_chaos_should_end_with_a_winning_role() {
+        let config = GameConfig {
+            profile: CpuProfile::with_strength(Personality::Defensive, 100),
+            ..order_chaos()
+        };
+        let inputs = (1..=36)
+            .flat_map(|number| ["x".to_owned(), number.to_string()])
+            .collect::<Vec<_>>();
+        let mut ui = RecordingUi::new(&inputs.iter().map(String::as_str).collect::<Vec<_>>());
+
+        let winner = play_order_chaos(&config, &mut ui, &mut seeded_rng(6)).unwrap();
+
+        let verdict = if winner == Some(Player::Human) {
+            "Order wins!"
+        } else {
+            "Chaos wins!"
+        };
+        assert!(ui.messages().contains(&verdict));
+    }
+}
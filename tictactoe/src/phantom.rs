@@ -0,0 +1,339 @@
+//! Phantom tic-tac-toe: each player only sees their own markers and the
+//! opponent markers they bumped into. Playing on a hidden occupied square
+//! reveals it and the player tries again.
+
+use super::cpu::{self, CpuProfile, MoveRule};
+use super::error::GameResult;
+use super::game::GameConfig;
+use super::grid::{self, Grid, GridChecker, Marker, Player};
+use super::ui::{Highlights, Ui};
+
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+
+const PRIORITIES: [MoveRule; 8] = [
+    MoveRule::Win,
+    MoveRule::Block,
+    MoveRule::Fork,
+    MoveRule::BlockFork,
+    MoveRule::Center,
+    MoveRule::OppositeCorner,
+    MoveRule::EmptyCorner,
+    MoveRule::Edge,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Attempt {
+    Placed,
+    Revealed,
+    AlreadyKnown,
+}
+
+fn index(player: Player) -> usize {
+    match player {
+        Player::Human => 0,
+        Player::Cpu => 1,
+    }
+}
+
+// Holds the true board and what each player has seen of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Referee {
+    board: Grid,
+    views: [Grid; 2],
+    placed: [usize; 2],
+}
+
+impl Referee {
+    pub fn new(size: usize) -> Referee {
+        let empty = grid::create_grid_of_size(size);
+        Referee {
+            board: empty.clone(),
+            views: [empty.clone(), empty],
+            placed: [0; 2],
+        }
+    }
+
+    pub fn board(&self) -> &Grid {
+        &self.board
+    }
+
+    pub fn view(&self, player: Player) -> &Grid {
+        &self.views[index(player)]
+    }
+
+    // Opponent markers on the board that the player has not found yet.
+    pub fn hidden_count(&self, player: Player, opponent_marker: Marker) -> usize {
+        let revealed = self.view(player).count_markers(opponent_marker);
+        self.placed[index(player.opponent())] - revealed
+    }
+
+    pub fn attempt(&mut self, player: Player, marker: Marker, position: (i32, i32)) -> Attempt {
+        let view = &mut self.views[index(player)];
+        if view.grid.get(&position) != Some(&Marker::Null) {
+            return Attempt::AlreadyKnown;
+        }
+        match self.board.grid.get(&position) {
+            Some(Marker::Null) => {
+                self.board.insert(position, marker);
+                view.insert(position, marker);
+                self.placed[index(player)] += 1;
+                Attempt::Placed
+            }
+            Some(occupant) => {
+                view.insert(position, *occupant);
+                Attempt::Revealed
+            }
+            None => Attempt::AlreadyKnown,
+        }
+    }
+}
+
+// Every true board consistent with the view, given how many markers are hidden.
+pub fn possible_boards(view: &Grid, opponent: Marker, hidden: usize) -> Vec<Grid> {
+    let mut unknown = view
+        .extract_empty_positions()
+        .into_values()
+        .collect::<Vec<_>>();
+    unknown.sort();
+    let boards = unknown
+        .into_iter()
+        .combinations(hidden)
+        .map(|cells| {
+            let mut board = view.clone();
+            for cell in cells {
+                board.insert(cell, opponent);
+            }
+            board
+        })
+        .filter(|board| board.winning_marker().is_none())
+        .collect::<Vec<_>>();
+    if boards.is_empty() {
+        vec![view.clone()]
+    } else {
+        boards
+    }
+}
+
+// Scores each unknown square by the strategy rule it would serve on every
+// possible hidden board, squares that are occupied there score nothing.
+pub fn choose_phantom_move<R: Rng>(
+    view: &Grid,
+    marker: Marker,
+    hidden: usize,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> (i32, i32) {
+    let mut candidates = view
+        .extract_empty_positions()
+        .into_values()
+        .collect::<Vec<_>>();
+    candidates.sort();
+    if rng.gen_bool(profile.blunder_rate.clamp(0.0, 1.0)) {
+        return *candidates
+            .choose(rng)
+            .expect("Phantom move requested on a full view");
+    }
+
+    let mut scores = HashMap::<(i32, i32), usize>::new();
+    for board in possible_boards(view, marker.opponent(), hidden) {
+        let best_rule = PRIORITIES.iter().enumerate().find_map(|(rank, rule)| {
+            let positions = cpu::extract_rule_candidates(&board, &marker, *rule);
+            (!positions.is_empty()).then_some((rank, positions))
+        });
+        if let Some((rank, positions)) = best_rule {
+            for position in positions {
+                *scores.entry(position).or_default() += PRIORITIES.len() - rank;
+            }
+        }
+    }
+    let best_score = candidates
+        .iter()
+        .map(|position| scores.get(position).copied().unwrap_or_default())
+        .max()
+        .unwrap_or_default();
+    let best = candidates
+        .into_iter()
+        .filter(|position| scores.get(position).copied().unwrap_or_default() == best_score)
+        .collect::<Vec<_>>();
+    *best
+        .choose(rng)
+        .expect("Phantom move requested on a full view")
+}
+
+pub fn play_phantom<U: Ui + ?Sized, R: Rng>(
+    config: &GameConfig,
+    ui: &mut U,
+    rng: &mut R,
+) -> GameResult<Option<Player>> {
+    ui.show_message("Starting a phantom game! You only see your own markers.");
+    let mut referee = Referee::new(config.variant.fixed_size().unwrap_or(config.size));
+    let mut player = config.first_player;
+
+    while !config.is_over(referee.board()) {
+        let marker = config.marker_of(player);
+        let attempt = match player {
+            Player::Human => {
+                let view = referee.view(player).clone();
+                let position = ui.choose_position(&view, None)?;
+                let attempt = referee.attempt(player, marker, position);
+                if attempt == Attempt::Revealed {
+                    ui.show_message(&format!(
+                        "Position {} is already taken by {}, try again.",
+                        view.position_number(position),
+                        marker.opponent().glyph()
+                    ));
+                }
+                attempt
+            }
+            Player::Cpu => {
+                let hidden = referee.hidden_count(player, marker.opponent());
+                let view = referee.view(player);
+                let position = choose_phantom_move(view, marker, hidden, &config.profile, rng);
+                let attempt = referee.attempt(player, marker, position);
+                match attempt {
+                    Attempt::Revealed => {
+                        ui.show_message("The CPU found one of your markers and tries again.")
+                    }
+                    _ => ui.show_message("The CPU has played."),
+                }
+                attempt
+            }
+        };
+        if attempt == Attempt::Placed {
+            player = player.opponent();
+        }
+    }
+
+    ui.show_message("The fog lifts, here is the full board:");
+    let highlights = Highlights {
+        cursor: None,
+        last_move: None,
+        winning_line: referee.board().winning_line().unwrap_or_default(),
+        fading: None,
+    };
+    ui.show_board(referee.board(), &highlights);
+    Ok(config.winner(referee.board()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::seeded_rng;
+    use super::super::game::RuleVariant;
+    use super::super::grid::from_array;
+    use super::*;
+    use crate::ui::RecordingUi;
+
+    #[test]
+    fn referee_should_keep_views_separate_and_reveal_collisions() {
+        let mut referee = Referee::new(3);
+        assert_eq!(
+            referee.attempt(Player::Human, Marker::X, (1, 1)),
+            Attempt::Placed
+        );
+        assert_eq!(referee.view(Player::Cpu).count_markers(Marker::X), 0);
+        assert_eq!(referee.hidden_count(Player::Cpu, Marker::X), 1);
+
+        assert_eq!(
+            referee.attempt(Player::Cpu, Marker::O, (1, 1)),
+            Attempt::Revealed
+        );
+        assert_eq!(
+            referee.view(Player::Cpu).grid.get(&(1, 1)),
+            Some(&Marker::X)
+        );
+        assert_eq!(referee.hidden_count(Player::Cpu, Marker::X), 0);
+        assert_eq!(
+            referee.attempt(Player::Cpu, Marker::O, (1, 1)),
+            Attempt::AlreadyKnown
+        );
+        assert_eq!(referee.board().count_markers(Marker::O), 0);
+    }
+
+    #[test]
+    fn possible_boards_should_place_hidden_markers_on_unknown_squares() {
+        let view = from_array([[-1, 0, 0], [0, 1, 0], [0, 0, 0]]);
+        assert_eq!(possible_boards(&view, Marker::X, 0).len(), 1);
+        assert_eq!(possible_boards(&view, Marker::X, 1).len(), 7);
+        // Boards where X would already have won are impossible.
+        let view = from_array([[1, 1, 0], [-1, -1, 0], [0, 0, 0]]);
+        assert!(possible_boards(&view, Marker::X, 1)
+            .iter()
+            .all(|board| board.grid.get(&(0, 2)) == Some(&Marker::Null)));
+    }
+
+    #[test]
+    fn choose_phantom_move_should_complete_a_known_line() {
+        let view = from_array([[-1, -1, 0], [0, 0, 0], [0, 0, 0]]);
+        let profile = CpuProfile::with_strength(cpu::Personality::Defensive, 100);
+        let position = choose_phantom_move(&view, Marker::O, 2, &profile, &mut seeded_rng(1));
+        assert_eq!(position, (0, 2));
+    }
+
+    #[test]
+    fn choose_phantom_move_should_prefer_the_center_without_information() {
+        let view = grid::create_grid_of_size(3);
+        let profile = CpuProfile::with_strength(cpu::Personality::Defensive, 100);
+        let position = choose_phantom_move(&view, Marker::X, 0, &profile, &mut seeded_rng(1));
+        assert_eq!(position, (1, 1));
+    }
+
+    #[test]
+    fn play_phantom_should_let_the_human_retry_after_a_reveal() {
+        let config = GameConfig {
+            first_player: Player::Cpu,
+            profile: CpuProfile::with_strength(cpu::Personality::Defensive, 100),
+            ..GameConfig::default()
+        };
+        let mut ui = RecordingUi::new(&["5", "1"]);
+
+        let result = play_phantom(&config, &mut ui, &mut seeded_rng(2));
+
+        assert!(result.is_err());
+        assert_eq!(
+            ui.messages(),
+            vec![
+                "Starting a phantom game! You only see your own markers.",
+                "The CPU has played.",
+                "Candidate: 5",
+                "Position 5 is already taken by O, try again.",
+                "Candidate: 1",
+                "The CPU has played."
+            ]
+        );
+    }
+
+    #[test]
+    fn play_phantom_should_finish_and_show_the_true_board() {
+        let config = GameConfig {
+            profile: CpuProfile::with_strength(cpu::Personality::Chaotic, 0),
+            ..GameConfig::default()
+        };
+        let inputs = ["1", "2", "3", "4", "5", "6", "7", "8", "9"].repeat(2);
+        let mut ui = RecordingUi::new(&inputs);
+
+        play_phantom(&config, &mut ui, &mut seeded_rng(5)).unwrap();
+
+        assert!(ui
+            .messages()
+            .contains(&"The fog lifts, here is the full board:"));
+    }
+
+    #[test]
+    fn play_phantom_should_stay_on_three_by_three_whatever_the_configured_size() {
+        let config = GameConfig {
+            variant: RuleVariant::Phantom,
+            size: 5,
+            first_player: Player::Cpu,
+            ..GameConfig::default()
+        };
+        let mut ui = RecordingUi::new(&[]);
+
+        let result = play_phantom(&config, &mut ui, &mut seeded_rng(2));
+
+        assert!(result.is_err());
+        assert_eq!(ui.boards()[0].lines().count(), 5);
+    }
+}
@@ -0,0 +1,442 @@
+use super::error::GameResult;
+use super::grid::{self, Grid, GridChecker, Marker};
+use super::ui::{Highlights, Ui};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap};
+
+pub const MAX_PUZZLE_MOVES: u32 = 3;
+
+// Result of perfect play for the side to move, counted in plies.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Outcome {
+    Win(u32),
+    Draw,
+    Loss(u32),
+}
+
+impl Outcome {
+    fn score(&self) -> i32 {
+        match self {
+            Outcome::Win(plies) => 100 - *plies as i32,
+            Outcome::Draw => 0,
+            Outcome::Loss(plies) => *plies as i32 - 100,
+        }
+    }
+
+    fn for_parent(&self) -> Outcome {
+        match self {
+            Outcome::Win(plies) => Outcome::Loss(plies + 1),
+            Outcome::Draw => Outcome::Draw,
+            Outcome::Loss(plies) => Outcome::Win(plies + 1),
+        }
+    }
+}
+
+fn win_in(moves: u32) -> Outcome {
+    Outcome::Win(2 * moves - 1)
+}
+
+fn marker_to_move(grid: &Grid) -> Marker {
+    if grid.count_markers(Marker::X) <= grid.count_markers(Marker::O) {
+        Marker::X
+    } else {
+        Marker::O
+    }
+}
+
+fn sorted_empty_positions(grid: &Grid) -> Vec<(i32, i32)> {
+    let mut positions = grid
+        .extract_empty_positions()
+        .into_values()
+        .collect::<Vec<_>>();
+    positions.sort();
+    positions
+}
+
+#[derive(Debug, Default)]
+pub struct Solver {
+    memo: HashMap<String, Outcome>,
+}
+
+impl Solver {
+    // Exhaustive negamax, only practical on 3x3 boards.
+    pub fn solve(&mut self, grid: &Grid) -> Outcome {
+        if grid.winning_marker().is_some() {
+            return Outcome::Loss(0);
+        }
+        if grid.is_grid_full() {
+            return Outcome::Draw;
+        }
+        let key = grid.to_notation();
+        if let Some(outcome) = self.memo.get(&key) {
+            return *outcome;
+        }
+        let outcome = self
+            .move_outcomes(grid)
+            .into_iter()
+            .map(|(_, outcome)| outcome)
+            .max_by_key(Outcome::score)
+            .unwrap_or(Outcome::Draw);
+        self.memo.insert(key, outcome);
+        outcome
+    }
+
+    pub fn move_outcomes(&mut self, grid: &Grid) -> Vec<((i32, i32), Outcome)> {
+        let marker = marker_to_move(grid);
+        sorted_empty_positions(grid)
+            .into_iter()
+            .map(|position| {
+                let mut child = grid.clone();
+                child.insert(position, marker);
+                (position, self.solve(&child).for_parent())
+            })
+            .collect()
+    }
+
+    // Moves that keep a forced win in the given number of own moves.
+    pub fn winning_moves(&mut self, grid: &Grid, moves: u32) -> Vec<(i32, i32)> {
+        self.move_outcomes(grid)
+            .into_iter()
+            .filter(|(_, outcome)| *outcome == win_in(moves))
+            .map(|(position, _)| position)
+            .collect()
+    }
+
+    // The defence that holds out the longest, first in row-major order on ties.
+    pub fn best_defence(&mut self, grid: &Grid) -> Option<(i32, i32)> {
+        self.move_outcomes(grid)
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, outcome)| outcome.score())
+            .map(|(position, _)| position)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Puzzle {
+    pub grid: Grid,
+    pub moves: u32,
+    pub solutions: Vec<(i32, i32)>,
+    pub rating: i32,
+}
+
+impl Puzzle {
+    pub fn marker(&self) -> Marker {
+        marker_to_move(&self.grid)
+    }
+}
+
+fn puzzle_rating(grid: &Grid, moves: u32, solutions: usize) -> i32 {
+    let decoys = grid.extract_empty_positions().len() - solutions;
+    800 + 300 * (moves as i32 - 1) + 40 * decoys as i32
+}
+
+fn collect_positions(grid: &Grid, positions: &mut BTreeMap<String, Grid>) {
+    if grid.winning_marker().is_some() || grid.is_grid_full() {
+        return;
+    }
+    if positions.insert(grid.to_notation(), grid.clone()).is_some() {
+        return;
+    }
+    let marker = marker_to_move(grid);
+    for position in sorted_empty_positions(grid) {
+        let mut child = grid.clone();
+        child.insert(position, marker);
+        collect_positions(&child, positions);
+    }
+}
+
+pub fn find_puzzles(solver: &mut Solver, moves: u32) -> Vec<Puzzle> {
+    let mut positions = BTreeMap::new();
+    collect_positions(&grid::create_grid_of_size(3), &mut positions);
+    let mut puzzles = Vec::new();
+    for grid in positions.into_values() {
+        if solver.solve(&grid) != win_in(moves) {
+            continue;
+        }
+        let solutions = solver.winning_moves(&grid, moves);
+        let rating = puzzle_rating(&grid, moves, solutions.len());
+        puzzles.push(Puzzle {
+            grid,
+            moves,
+            solutions,
+            rating,
+        });
+    }
+    puzzles
+}
+
+pub fn generate_puzzle<R: Rng>(solver: &mut Solver, moves: u32, rng: &mut R) -> Option<Puzzle> {
+    find_puzzles(solver, moves).choose(rng).cloned()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PuzzleProgress {
+    pub rating: i32,
+    pub solved: u32,
+    pub failed: u32,
+}
+
+impl Default for PuzzleProgress {
+    fn default() -> PuzzleProgress {
+        PuzzleProgress {
+            rating: 1000,
+            solved: 0,
+            failed: 0,
+        }
+    }
+}
+
+impl PuzzleProgress {
+    pub fn attempts(&self) -> u32 {
+        self.solved + self.failed
+    }
+
+    // Elo update against the puzzle rating, returns the rating change.
+    pub fn record(&mut self, puzzle_rating: i32, solved: bool) -> i32 {
+        let expected = 1.0 / (1.0 + 10f64.powf((puzzle_rating - self.rating) as f64 / 400.0));
+        let score = if solved { 1.0 } else { 0.0 };
+        let change = (32.0 * (score - expected)).round() as i32;
+        self.rating += change;
+        if solved {
+            self.solved += 1;
+        } else {
+            self.failed += 1;
+        }
+        change
+    }
+
+    pub fn render(&self) -> String {
+        format!(
+            "Puzzles: rating {} | solved {} | failed {}",
+            self.rating, self.solved, self.failed
+        )
+    }
+}
+
+fn list_positions(grid: &Grid, positions: &[(i32, i32)]) -> String {
+    positions
+        .iter()
+        .map(|position| grid.position_number(*position).to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Plays the puzzle out against the longest defence, returns whether it was solved.
+pub fn play_puzzle<U: Ui + ?Sized>(
+    ui: &mut U,
+    solver: &mut Solver,
+    puzzle: &Puzzle,
+) -> GameResult<bool> {
+    let marker = puzzle.marker();
+    let mut grid = puzzle.grid.clone();
+    let mut last_move = None;
+    ui.show_message(&format!(
+        "Puzzle (rating {}): {} to move and win in {}.",
+        puzzle.rating,
+        marker.glyph(),
+        puzzle.moves
+    ));
+
+    for remaining in (1..=puzzle.moves).rev() {
+        let solutions = solver.winning_moves(&grid, remaining);
+        let grid_after_move = grid::make_user_turn(&grid, ui, last_move, marker, None)?;
+        let played = grid_after_move.newly_filled_position(&grid);
+        if !played.is_some_and(|position| solutions.contains(&position)) {
+            ui.show_message(&format!(
+                "Not quite, that does not force a win in {}. Winning moves: {}.",
+                remaining,
+                list_positions(&grid, &solutions)
+            ));
+            return Ok(false);
+        }
+        grid = grid_after_move;
+        if remaining == 1 {
+            break;
+        }
+        let Some(defence) = solver.best_defence(&grid) else {
+            break;
+        };
+        grid.insert(defence, marker.opponent());
+        last_move = Some(defence);
+        ui.show_message(&format!(
+            "Correct! The opponent defends at {}.",
+            grid.position_number(defence)
+        ));
+    }
+
+    let highlights = Highlights {
+        cursor: None,
+        last_move: None,
+        winning_line: grid.winning_line().unwrap_or_default(),
+        fading: None,
+    };
+    ui.show_board(&grid, &highlights);
+    ui.show_message("Solved!");
+    Ok(true)
+}
+
+fn parse_puzzle_moves<U: Ui + ?Sized>(ui: &mut U) -> GameResult<Option<u32>> {
+    Ok(ui
+        .prompt(&format!(
+            "\nWin in how many moves? (1-{}, 2 trains forks and double threats)",
+            MAX_PUZZLE_MOVES
+        ))?
+        .trim()
+        .parse()
+        .ok()
+        .filter(|moves| (1..=MAX_PUZZLE_MOVES).contains(moves)))
+}
+
+pub fn run_puzzle<U: Ui + ?Sized, R: Rng>(
+    ui: &mut U,
+    progress: &mut PuzzleProgress,
+    rng: &mut R,
+) -> GameResult<()> {
+    let moves = loop {
+        if let Some(moves) = parse_puzzle_moves(ui)? {
+            break moves;
+        }
+    };
+    let mut solver = Solver::default();
+    let Some(puzzle) = generate_puzzle(&mut solver, moves, rng) else {
+        ui.show_message(&format!("No win-in-{} puzzles exist.", moves));
+        return Ok(());
+    };
+    let solved = play_puzzle(ui, &mut solver, &puzzle)?;
+    let change = progress.record(puzzle.rating, solved);
+    ui.show_message(&format!(
+        "Rating change: {:+}\n{}",
+        change,
+        progress.render()
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::seeded_rng;
+    use super::super::grid::from_array;
+    use super::*;
+    use crate::ui::RecordingUi;
+
+    #[test]
+    fn solver_should_find_the_shortest_forced_result() {
+        let mut solver = Solver::default();
+        assert_eq!(solver.solve(&grid::create_grid_of_size(3)), Outcome::Draw);
+        assert_eq!(
+            solver.solve(&from_array([[1, 1, 0], [-1, -1, 0], [0, 0, 0]])),
+            Outcome::Win(1)
+        );
+        let opposite_corners = from_array([[1, 0, 0], [0, -1, 0], [0, 0, 1]]);
+        assert_eq!(solver.solve(&opposite_corners), Outcome::Draw);
+        let adjacent_edge = from_array([[1, -1, 0], [0, 0, 0], [0, 0, 0]]);
+        assert_eq!(solver.solve(&adjacent_edge), Outcome::Win(5));
+    }
+
+    #[test]
+    fn winning_moves_should_only_keep_wins_of_the_requested_length() {
+        let mut solver = Solver::default();
+        let grid = from_array([[1, 0, 0], [-1, 1, 0], [-1, 0, 0]]);
+        assert_eq!(solver.winning_moves(&grid, 1), vec![(2, 2)]);
+        assert_eq!(solver.winning_moves(&grid, 2), vec![(0, 1), (0, 2), (2, 1)]);
+
+        let double_threat = from_array([[1, 0, 0], [0, -1, 0], [-1, 0, 1]]);
+        assert_eq!(solver.winning_moves(&double_threat, 2), vec![(0, 2)]);
+    }
+
+    #[test]
+    fn find_puzzles_should_return_positions_with_exact_wins() {
+        let mut solver = Solver::default();
+        let mut ratings = Vec::new();
+        for moves in 1..=MAX_PUZZLE_MOVES {
+            let puzzles = find_puzzles(&mut solver, moves);
+            assert!(!puzzles.is_empty());
+            for puzzle in puzzles.iter().take(20) {
+                assert_eq!(solver.solve(&puzzle.grid), win_in(moves));
+                assert!(!puzzle.solutions.is_empty());
+            }
+            ratings.push(puzzles[0].rating);
+        }
+        assert!(ratings[0] < ratings[2]);
+    }
+
+    #[test]
+    fn generate_puzzle_should_depend_on_seed() {
+        let mut solver = Solver::default();
+        let first = generate_puzzle(&mut solver, 2, &mut seeded_rng(1));
+        assert_eq!(first, generate_puzzle(&mut solver, 2, &mut seeded_rng(1)));
+        assert_eq!(first.unwrap().moves, 2);
+    }
+
+    #[test]
+    fn play_puzzle_should_accept_winning_lines_against_best_defence() {
+        let mut solver = Solver::default();
+        let grid = from_array([[1, 0, 0], [0, -1, 0], [-1, 0, 1]]);
+        let puzzle = Puzzle {
+            solutions: solver.winning_moves(&grid, 2),
+            grid,
+            moves: 2,
+            rating: 1100,
+        };
+        let mut ui = RecordingUi::new(&["3", "6"]);
+
+        assert!(play_puzzle(&mut ui, &mut solver, &puzzle).unwrap());
+        assert_eq!(
+            ui.messages()[0],
+            "Puzzle (rating 1100): X to move and win in 2."
+        );
+        assert!(ui
+            .messages()
+            .contains(&"Correct! The opponent defends at 2."));
+        assert_eq!(ui.messages().last(), Some(&"Solved!"));
+    }
+
+    #[test]
+    fn play_puzzle_should_reject_moves_that_do_not_force_the_win() {
+        let mut solver = Solver::default();
+        let grid = from_array([[1, 0, 0], [0, -1, 0], [-1, 0, 1]]);
+        let puzzle = Puzzle {
+            solutions: solver.winning_moves(&grid, 2),
+            grid,
+            moves: 2,
+            rating: 1100,
+        };
+        let mut ui = RecordingUi::new(&["6"]);
+
+        assert!(!play_puzzle(&mut ui, &mut solver, &puzzle).unwrap());
+        assert_eq!(
+            ui.messages().last(),
+            Some(&"Not quite, that does not force a win in 2. Winning moves: 3.")
+        );
+    }
+
+    #[test]
+    fn puzzle_progress_should_follow_elo() {
+        let mut progress = PuzzleProgress::default();
+        assert_eq!(progress.record(1000, true), 16);
+        assert_eq!(progress.rating, 1016);
+        assert!(progress.record(600, false) < -16);
+        assert_eq!(progress.attempts(), 2);
+        assert_eq!(
+            PuzzleProgress::default().render(),
+            "Puzzles: rating 1000 | solved 0 | failed 0"
+        );
+    }
+
+    #[test]
+    fn run_puzzle_should_update_progress() {
+        let puzzle = generate_puzzle(&mut Solver::default(), 1, &mut seeded_rng(4)).unwrap();
+        let answer = puzzle.grid.position_number(puzzle.solutions[0]).to_string();
+        let mut progress = PuzzleProgress::default();
+        let mut ui = RecordingUi::new(&["7", "1", &answer]);
+
+        run_puzzle(&mut ui, &mut progress, &mut seeded_rng(4)).unwrap();
+
+        assert_eq!(progress.solved, 1);
+        assert!(progress.rating > 1000);
+        assert!(ui.messages().last().unwrap().ends_with(&progress.render()));
+    }
+}
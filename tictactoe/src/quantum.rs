@@ -0,0 +1,611 @@
+//! Goff's quantum tic-tac-toe: every turn places a spooky mark in two cells.
+//! When the marks entangle into a cycle, the other player decides where the
+//! newest mark collapses and the whole entangled group turns classical.
+
+use super::cpu::CpuProfile;
+use super::error::GameResult;
+use super::game::GameConfig;
+use super::grid::{self, Grid, Marker, Player};
+use super::input;
+use super::ui::Ui;
+
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpookyMark {
+    pub marker: Marker,
+    pub turn: usize,
+    pub cells: [(i32, i32); 2],
+}
+
+impl SpookyMark {
+    fn label(&self) -> String {
+        format!("{}{}", self.marker.glyph().to_ascii_lowercase(), self.turn)
+    }
+
+    fn other_cell(&self, cell: (i32, i32)) -> (i32, i32) {
+        if self.cells[0] == cell {
+            self.cells[1]
+        } else {
+            self.cells[0]
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantumBoard {
+    layout: Grid,
+    classical: HashMap<(i32, i32), (Marker, usize)>,
+    spooky: Vec<SpookyMark>,
+    turn: usize,
+}
+
+impl Default for QuantumBoard {
+    fn default() -> QuantumBoard {
+        QuantumBoard {
+            layout: grid::create_grid_of_size(3),
+            classical: HashMap::new(),
+            spooky: Vec::new(),
+            turn: 0,
+        }
+    }
+}
+
+impl QuantumBoard {
+    pub fn open_cells(&self) -> Vec<(i32, i32)> {
+        self.layout
+            .grid
+            .keys()
+            .filter(|cell| !self.classical.contains_key(cell))
+            .copied()
+            .sorted()
+            .collect()
+    }
+
+    pub fn position_number(&self, cell: (i32, i32)) -> usize {
+        self.layout.position_number(cell)
+    }
+
+    pub fn last_mark(&self) -> Option<&SpookyMark> {
+        self.spooky.last()
+    }
+
+    fn entangled(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        let mut seen = HashSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(cell) = queue.pop_front() {
+            if cell == to {
+                return true;
+            }
+            for mark in self.spooky.iter().filter(|mark| mark.cells.contains(&cell)) {
+                let next = mark.other_cell(cell);
+                if seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        false
+    }
+
+    // Places a spooky mark and reports whether it closed an entanglement cycle.
+    pub fn place(&mut self, marker: Marker, cells: [(i32, i32); 2]) -> Result<bool, String> {
+        let open = self.open_cells();
+        if cells[0] == cells[1] {
+            return Err("a spooky mark needs two different cells".to_owned());
+        }
+        if let Some(cell) = cells.iter().find(|cell| !open.contains(cell)) {
+            return Err(format!(
+                "position {} is not open",
+                self.position_number(*cell)
+            ));
+        }
+        let cycle = self.entangled(cells[0], cells[1]);
+        self.turn += 1;
+        self.spooky.push(SpookyMark {
+            marker,
+            turn: self.turn,
+            cells,
+        });
+        Ok(cycle)
+    }
+
+    pub fn place_classical(&mut self, marker: Marker, cell: (i32, i32)) {
+        self.turn += 1;
+        self.classical.insert(cell, (marker, self.turn));
+    }
+
+    // Sends the newest mark to `cell`, every mark sharing a cell with a
+    // collapsed one is then forced into its other cell.
+    pub fn collapse_last(&mut self, cell: (i32, i32)) {
+        let Some(last) = self.spooky.last().copied() else {
+            return;
+        };
+        let mut queue = VecDeque::from([(last.turn, cell)]);
+        while let Some((turn, cell)) = queue.pop_front() {
+            let Some(index) = self.spooky.iter().position(|mark| mark.turn == turn) else {
+                continue;
+            };
+            let mark = self.spooky.remove(index);
+            self.classical.insert(cell, (mark.marker, mark.turn));
+            for other in self
+                .spooky
+                .iter()
+                .filter(|other| other.cells.contains(&cell))
+            {
+                queue.push_back((other.turn, other.other_cell(cell)));
+            }
+        }
+    }
+
+    // When both players complete a line in the same collapse, the line whose
+    // newest mark is older wins.
+    pub fn winner(&self) -> Option<Marker> {
+        self.layout
+            .lines()
+            .iter()
+            .filter_map(|line| {
+                let marks = line
+                    .iter()
+                    .map(|cell| self.classical.get(cell).copied())
+                    .collect::<Option<Vec<_>>>()?;
+                let marker = marks[0].0;
+                marks
+                    .iter()
+                    .all(|(other, _)| *other == marker)
+                    .then(|| (marks.iter().map(|(_, turn)| *turn).max(), marker))
+            })
+            .min_by_key(|(newest, _)| *newest)
+            .map(|(_, marker)| marker)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winner().is_some() || self.open_cells().is_empty()
+    }
+
+    fn cell_text(&self, cell: (i32, i32)) -> String {
+        if let Some((marker, turn)) = self.classical.get(&cell) {
+            return format!("{}{}", marker.glyph(), turn);
+        }
+        let marks = self
+            .spooky
+            .iter()
+            .filter(|mark| mark.cells.contains(&cell))
+            .map(SpookyMark::label)
+            .collect::<Vec<_>>();
+        if marks.is_empty() {
+            self.position_number(cell).to_string()
+        } else {
+            marks.join(" ")
+        }
+    }
+
+    // Classical marks show in capitals, spooky ones in lower case, both with
+    // the turn they were played on.
+    pub fn render(&self) -> String {
+        let size = self.layout.size as i32;
+        let texts = (0..size)
+            .map(|row| {
+                (0..size)
+                    .map(|col| self.cell_text((row, col)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let width = texts.iter().flatten().map(String::len).max().unwrap_or(1);
+        texts
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|text| format!(" {:<width$} ", text, width = width))
+                    .join("|")
+            })
+            .join(&format!(
+                "\n{}\n",
+                vec!["-".repeat(width + 2); self.layout.size].join("+")
+            ))
+    }
+}
+
+fn outcome_score(board: &QuantumBoard, marker: Marker) -> i32 {
+    match board.winner() {
+        Some(winner) if winner == marker => 100,
+        Some(_) => -100,
+        None => 0,
+    }
+}
+
+fn choose_best<T: Copy, R: Rng>(scored: Vec<(T, i32)>, rng: &mut R) -> T {
+    let best_score = scored.iter().map(|(_, score)| *score).max().unwrap_or(0);
+    let best = scored
+        .into_iter()
+        .filter(|(_, score)| *score == best_score)
+        .map(|(choice, _)| choice)
+        .collect::<Vec<_>>();
+    *best
+        .choose(rng)
+        .expect("Quantum choice requested without options")
+}
+
+// Picks the collapse of the newest mark that is best for `marker`.
+pub fn choose_collapse<R: Rng>(board: &QuantumBoard, marker: Marker, rng: &mut R) -> (i32, i32) {
+    let last = board
+        .last_mark()
+        .expect("Collapse requested without spooky marks");
+    let scored = last
+        .cells
+        .iter()
+        .map(|cell| {
+            let mut collapsed = board.clone();
+            collapsed.collapse_last(*cell);
+            (*cell, outcome_score(&collapsed, marker))
+        })
+        .collect();
+    choose_best(scored, rng)
+}
+
+fn cell_weight(board: &QuantumBoard, cell: (i32, i32)) -> i32 {
+    let last = board.layout.size as i32 - 1;
+    match cell {
+        (1, 1) => 2,
+        (row, col) if (row == 0 || row == last) && (col == 0 || col == last) => 1,
+        _ => 0,
+    }
+}
+
+// Avoids cycles the opponent can collapse into a loss and otherwise favours
+// the center and the corners.
+pub fn choose_quantum_move<R: Rng>(
+    board: &QuantumBoard,
+    marker: Marker,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> [(i32, i32); 2] {
+    let pairs = board
+        .open_cells()
+        .into_iter()
+        .tuple_combinations()
+        .map(|(first, second)| [first, second])
+        .collect::<Vec<_>>();
+    if rng.gen_bool(profile.blunder_rate.clamp(0.0, 1.0)) {
+        return *pairs
+            .choose(rng)
+            .expect("Quantum move requested on a full board");
+    }
+    let scored = pairs
+        .into_iter()
+        .map(|cells| {
+            let mut placed = board.clone();
+            let score = match placed.place(marker, cells) {
+                Ok(true) => cells
+                    .iter()
+                    .map(|cell| {
+                        let mut collapsed = placed.clone();
+                        collapsed.collapse_last(*cell);
+                        outcome_score(&collapsed, marker)
+                    })
+                    .min()
+                    .unwrap_or(0),
+                _ => cells.iter().map(|cell| cell_weight(board, *cell)).sum(),
+            };
+            (cells, score)
+        })
+        .collect();
+    choose_best(scored, rng)
+}
+
+// Cells are picked with a line prompt under the rendered board, a cursor
+// grid could only show the classical marks.
+fn choose_cells<U: Ui + ?Sized>(board: &QuantumBoard, ui: &mut U) -> GameResult<[(i32, i32); 2]> {
+    let first = prompt_for_cell(
+        board,
+        ui,
+        "Choose the first cell of your spooky mark:",
+        None,
+    )?;
+    let second = prompt_for_cell(
+        board,
+        ui,
+        "Choose the second cell of your spooky mark:",
+        Some(first),
+    )?;
+    Ok([first, second])
+}
+
+fn prompt_for_cell<U: Ui + ?Sized>(
+    board: &QuantumBoard,
+    ui: &mut U,
+    message: &str,
+    taken: Option<(i32, i32)>,
+) -> GameResult<(i32, i32)> {
+    let cells = board
+        .open_cells()
+        .into_iter()
+        .filter(|cell| Some(*cell) != taken)
+        .collect::<Vec<_>>();
+    let list_of_choices = cells
+        .iter()
+        .map(|cell| board.position_number(*cell))
+        .sorted()
+        .join(", ");
+    loop {
+        let answer = ui.prompt(&format!("{}\n{}", message, list_of_choices))?;
+        match input::parse_coordinates(&answer, 3) {
+            Ok(cell) if cells.contains(&cell) => return Ok(cell),
+            Ok(cell) => ui.show_message(&format!(
+                "Position {} is not available.",
+                board.position_number(cell)
+            )),
+            Err(error) => ui.show_message(&error.to_string()),
+        }
+    }
+}
+
+fn prompt_for_collapse<U: Ui + ?Sized>(board: &QuantumBoard, ui: &mut U) -> GameResult<(i32, i32)> {
+    let last = *board
+        .last_mark()
+        .expect("Collapse requested without spooky marks");
+    let [first, second] = last.cells.map(|cell| board.position_number(cell));
+    loop {
+        let answer = ui.prompt(&format!(
+            "A cycle formed! Choose where {} collapses, position {} or {}:",
+            last.label(),
+            first,
+            second
+        ))?;
+        match input::parse_coordinates(&answer, 3) {
+            Ok(cell) if last.cells.contains(&cell) => return Ok(cell),
+            Ok(_) => ui.show_message(&format!("Please choose position {} or {}.", first, second)),
+            Err(error) => ui.show_message(&error.to_string()),
+        }
+    }
+}
+
+pub fn play_quantum<U: Ui + ?Sized, R: Rng>(
+    config: &GameConfig,
+    ui: &mut U,
+    rng: &mut R,
+) -> GameResult<Option<Player>> {
+    ui.show_message("Starting a quantum game! Each turn places two entangled marks.");
+    let mut board = QuantumBoard::default();
+    let mut player = config.first_player;
+
+    while !board.is_over() {
+        ui.show_message(&board.render());
+        let marker = config.marker_of(player);
+        let open = board.open_cells();
+        if let [cell] = open[..] {
+            board.place_classical(marker, cell);
+            ui.show_message(&format!(
+                "Only position {} is left, {} takes it.",
+                board.position_number(cell),
+                marker.glyph()
+            ));
+            player = player.opponent();
+            continue;
+        }
+
+        let cells = match player {
+            Player::Human => choose_cells(&board, ui)?,
+            Player::Cpu => {
+                let cells = choose_quantum_move(&board, marker, &config.profile, rng);
+                ui.show_message(&format!(
+                    "The CPU entangles positions {} and {}.",
+                    board.position_number(cells[0]),
+                    board.position_number(cells[1])
+                ));
+                cells
+            }
+        };
+        let cycle = match board.place(marker, cells) {
+            Ok(cycle) => cycle,
+            Err(error) => {
+                ui.show_message(&format!("Invalid: {}", error));
+                continue;
+            }
+        };
+        if cycle {
+            ui.show_message(&board.render());
+            let collapser = player.opponent();
+            let cell = match collapser {
+                Player::Human => prompt_for_collapse(&board, ui)?,
+                Player::Cpu => choose_collapse(&board, config.marker_of(collapser), rng),
+            };
+            let label = board.last_mark().map(SpookyMark::label).unwrap_or_default();
+            ui.show_message(&format!(
+                "The cycle collapses, {} lands on position {}.",
+                label,
+                board.position_number(cell)
+            ));
+            board.collapse_last(cell);
+        }
+        player = player.opponent();
+    }
+
+    ui.show_message(&board.render());
+    Ok(board.winner().and_then(|marker| config.player_of(marker)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::{seeded_rng, Personality};
+    use super::*;
+    use crate::ui::RecordingUi;
+
+    fn cell(number: usize) -> (i32, i32) {
+        input::parse_coordinates(&number.to_string(), 3).unwrap()
+    }
+
+    fn board_from(moves: &[(Marker, usize, usize)]) -> QuantumBoard {
+        let mut board = QuantumBoard::default();
+        for (marker, first, second) in moves {
+            board.place(*marker, [cell(*first), cell(*second)]).unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn place_should_detect_entanglement_cycles() {
+        let mut board = board_from(&[(Marker::X, 1, 2), (Marker::O, 2, 3)]);
+        assert_eq!(board.place(Marker::X, [cell(5), cell(9)]), Ok(false));
+        assert_eq!(board.place(Marker::O, [cell(3), cell(1)]), Ok(true));
+        assert!(board.place(Marker::X, [cell(4), cell(4)]).is_err());
+    }
+
+    #[test]
+    fn collapse_last_should_resolve_the_whole_entangled_group() {
+        let mut board = board_from(&[
+            (Marker::X, 1, 2),
+            (Marker::O, 2, 3),
+            (Marker::X, 3, 7),
+            (Marker::O, 1, 3),
+        ]);
+        board.collapse_last(cell(1));
+
+        assert_eq!(board.classical.get(&cell(1)), Some(&(Marker::O, 4)));
+        assert_eq!(board.classical.get(&cell(2)), Some(&(Marker::X, 1)));
+        assert_eq!(board.classical.get(&cell(3)), Some(&(Marker::O, 2)));
+        assert_eq!(board.classical.get(&cell(7)), Some(&(Marker::X, 3)));
+        assert!(board.spooky.is_empty());
+        assert_eq!(board.open_cells().len(), 5);
+    }
+
+    #[test]
+    fn winner_should_prefer_the_line_completed_with_older_marks() {
+        let mut board = QuantumBoard::default();
+        for (number, marker, turn) in [
+            (1, Marker::X, 1),
+            (2, Marker::X, 3),
+            (3, Marker::X, 5),
+            (7, Marker::O, 2),
+            (8, Marker::O, 4),
+            (9, Marker::O, 6),
+        ] {
+            board.classical.insert(cell(number), (marker, turn));
+        }
+        assert_eq!(board.winner(), Some(Marker::X));
+        board.classical.insert(cell(3), (Marker::X, 7));
+        assert_eq!(board.winner(), Some(Marker::O));
+        assert!(board.is_over());
+    }
+
+    #[test]
+    fn render_should_show_spooky_and_classical_marks() {
+        let mut board = board_from(&[(Marker::X, 1, 5), (Marker::O, 5, 9)]);
+        board.place_classical(Marker::X, cell(3));
+        assert_eq!(
+            board.render(),
+            [
+                " x1    | 2     | X3    ",
+                "-------+-------+-------",
+                " 4     | x1 o2 | 6     ",
+                "-------+-------+-------",
+                " 7     | 8     | o2    ",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn choose_collapse_should_pick_the_winning_cell() {
+        let mut board = QuantumBoard::default();
+        board.classical.insert(cell(1), (Marker::O, 1));
+        board.classical.insert(cell(2), (Marker::O, 3));
+        board.turn = 3;
+        board.place(Marker::X, [cell(3), cell(4)]).unwrap();
+        board.place(Marker::O, [cell(3), cell(4)]).unwrap();
+
+        let choice = choose_collapse(&board, Marker::O, &mut seeded_rng(1));
+        assert_eq!(choice, cell(3));
+        let choice = choose_collapse(&board, Marker::X, &mut seeded_rng(1));
+        assert_eq!(choice, cell(4));
+    }
+
+    #[test]
+    fn choose_quantum_move_should_play_open_cells_and_avoid_losing_cycles() {
+        let profile = CpuProfile::with_strength(Personality::Defensive, 100);
+        let board = board_from(&[(Marker::X, 1, 2)]);
+        let cells = choose_quantum_move(&board, Marker::O, &profile, &mut seeded_rng(3));
+        assert!(cells.iter().all(|cell| board.open_cells().contains(cell)));
+        assert_ne!(cells[0], cells[1]);
+
+        let random = CpuProfile::with_strength(Personality::Chaotic, 0);
+        for seed in 0..20 {
+            let cells = choose_quantum_move(&board, Marker::O, &random, &mut seeded_rng(seed));
+            assert!(board.clone().place(Marker::O, cells).is_ok());
+        }
+
+        // Closing the 3-4 cycle would let X collapse its own mark onto 3.
+        let mut board = QuantumBoard::default();
+        board.classical.insert(cell(1), (Marker::X, 1));
+        board.classical.insert(cell(2), (Marker::X, 3));
+        board.turn = 3;
+        board.place(Marker::X, [cell(3), cell(4)]).unwrap();
+        board.turn = 5;
+        let cells = choose_quantum_move(&board, Marker::O, &profile, &mut seeded_rng(3));
+        assert_ne!(cells, [cell(3), cell(4)]);
+    }
+
+    #[test]
+    fn play_quantum_should_let_the_human_collapse_the_cpu_cycle() {
+        let config = GameConfig {
+            profile: CpuProfile::with_strength(Personality::Chaotic, 0),
+            ..GameConfig::default()
+        };
+        let mut ui = RecordingUi::new(&["1", "2", "2", "3", "5", "2"]);
+
+        let result = play_quantum(&config, &mut ui, &mut seeded_rng(12));
+
+        assert!(result.is_err());
+        let prompts = ui.prompts();
+        assert_eq!(
+            prompts[0],
+            "Choose the first cell of your spooky mark:\n1, 2, 3, 4, 5, 6, 7, 8, 9"
+        );
+        assert_eq!(
+            prompts[1],
+            "Choose the second cell of your spooky mark:\n2, 3, 4, 5, 6, 7, 8, 9"
+        );
+        assert_eq!(
+            prompts[4],
+            "A cycle formed! Choose where o4 collapses, position 1 or 2:"
+        );
+        let messages = ui.messages();
+        assert!(messages.contains(&"The CPU entangles positions 1 and 2."));
+        assert!(messages.contains(&"Please choose position 1 or 2."));
+        assert!(messages.contains(&"The cycle collapses, o4 lands on position 2."));
+        assert_eq!(
+            messages.last().unwrap(),
+            &[
+                " X1 | O4 | X3 ",
+                "----+----+----",
+                " O2 | 5  | 6  ",
+                "----+----+----",
+                " 7  | 8  | 9  ",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn play_quantum_should_finish_a_full_game() {
+        let config = GameConfig {
+            profile: CpuProfile::with_strength(Personality::Defensive, 100),
+            ..GameConfig::default()
+        };
+        let mut inputs = Vec::new();
+        for _ in 0..10 {
+            inputs.extend(["1", "2", "3", "4", "5", "6", "7", "8", "9"]);
+        }
+        let mut ui = RecordingUi::new(&inputs);
+
+        play_quantum(&config, &mut ui, &mut seeded_rng(8)).unwrap();
+
+        assert!(ui
+            .messages()
+            .iter()
+            .any(|message| message.starts_with("The cycle collapses, ")));
+        assert!(ui.messages().last().unwrap().contains('|'));
+    }
+}
@@ -0,0 +1,190 @@
+use super::clock::ClockRecord;
+use super::grid::{Grid, Player};
+use super::{GameRecord, Leaderboard};
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+pub const SAVE_FILE: &str = "tictactoe_save.txt";
+
+fn player_to_str(winner: Option<Player>) -> &'static str {
+    match winner {
+        Some(Player::Human) => "human",
+        Some(Player::Cpu) => "cpu",
+        None => "tie",
+    }
+}
+
+fn player_from_str(value: &str) -> Option<Option<Player>> {
+    match value {
+        "human" => Some(Some(Player::Human)),
+        "cpu" => Some(Some(Player::Cpu)),
+        "tie" => Some(None),
+        _ => None,
+    }
+}
+
+pub fn serialize_session(leaderboard: &Leaderboard, game: Option<&Grid>) -> String {
+    let mut lines = vec![
+        format!("player={}", leaderboard.player),
+        format!("cpu={}", leaderboard.cpu),
+        format!("tie={}", leaderboard.tie),
+    ];
+    for record in &leaderboard.games {
+        let clock = record.clock.map_or(String::new(), |clock| {
+            format!(
+                ":{}:{}:{}",
+                clock.human.as_millis(),
+                clock.cpu.as_millis(),
+                clock
+                    .flagged
+                    .map_or("none", |player| player_to_str(Some(player)))
+            )
+        });
+        lines.push(format!(
+            "record={}:{}{}",
+            player_to_str(record.winner),
+            record.cpu_strength,
+            clock
+        ));
+    }
+    if leaderboard.puzzles.attempts() > 0 {
+        lines.push(format!("puzzle_rating={}", leaderboard.puzzles.rating));
+        lines.push(format!("puzzle_solved={}", leaderboard.puzzles.solved));
+        lines.push(format!("puzzle_failed={}", leaderboard.puzzles.failed));
+    }
+    if let Some(grid) = game {
+        lines.push(format!("game={}", grid.to_notation()));
+    }
+    lines.join("\n") + "\n"
+}
+
+pub fn deserialize_session(content: &str) -> io::Result<(Leaderboard, Option<Grid>)> {
+    let invalid = |line: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid save line: {}", line),
+        )
+    };
+    let mut leaderboard = Leaderboard::default();
+    let mut game = None;
+
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let (key, value) = line.split_once('=').ok_or_else(|| invalid(line))?;
+        match key {
+            "player" => leaderboard.player = value.parse().map_err(|_| invalid(line))?,
+            "cpu" => leaderboard.cpu = value.parse().map_err(|_| invalid(line))?,
+            "tie" => leaderboard.tie = value.parse().map_err(|_| invalid(line))?,
+            "record" => {
+                let fields = value.split(':').collect::<Vec<_>>();
+                let clock = match fields[..] {
+                    [_, _] => None,
+                    [_, _, human, cpu, flagged] => {
+                        let millis = |value: &str| value.parse().map(Duration::from_millis);
+                        Some(ClockRecord {
+                            human: millis(human).map_err(|_| invalid(line))?,
+                            cpu: millis(cpu).map_err(|_| invalid(line))?,
+                            flagged: match flagged {
+                                "none" => None,
+                                player => player_from_str(player).flatten(),
+                            },
+                        })
+                    }
+                    _ => return Err(invalid(line)),
+                };
+                leaderboard.games.push(GameRecord {
+                    winner: player_from_str(fields[0]).ok_or_else(|| invalid(line))?,
+                    cpu_strength: fields[1].parse().map_err(|_| invalid(line))?,
+                    clock,
+                });
+            }
+            "puzzle_rating" => {
+                leaderboard.puzzles.rating = value.parse().map_err(|_| invalid(line))?
+            }
+            "puzzle_solved" => {
+                leaderboard.puzzles.solved = value.parse().map_err(|_| invalid(line))?
+            }
+            "puzzle_failed" => {
+                leaderboard.puzzles.failed = value.parse().map_err(|_| invalid(line))?
+            }
+            "game" => game = Some(Grid::from_notation(value).ok_or_else(|| invalid(line))?),
+            _ => (),
+        }
+    }
+    Ok((leaderboard, game))
+}
+
+pub fn save_session(path: &Path, leaderboard: &Leaderboard, game: Option<&Grid>) -> io::Result<()> {
+    fs::write(path, serialize_session(leaderboard, game))
+}
+
+pub fn load_session(path: &Path) -> io::Result<Option<(Leaderboard, Option<Grid>)>> {
+    match fs::read_to_string(path) {
+        Ok(content) => deserialize_session(&content).map(Some),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::grid::from_array;
+    use super::*;
+
+    #[test]
+    fn session_should_round_trip_through_text() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.record_game(Some(Player::Human), 80, None);
+        leaderboard.record_game(
+            None,
+            85,
+            Some(ClockRecord {
+                human: Duration::from_millis(12_500),
+                cpu: Duration::from_millis(40),
+                flagged: Some(Player::Cpu),
+            }),
+        );
+        leaderboard.puzzles.record(1200, true);
+        let grid = from_array([[1, 0, 0], [0, -1, 0], [0, 0, 0]]);
+
+        let content = serialize_session(&leaderboard, Some(&grid));
+        assert!(content.contains("puzzle_solved=1\n"));
+        assert!(content.contains("record=human:80\n"));
+        assert!(content.contains("record=tie:85:12500:40:cpu\n"));
+        assert!(content.contains("game=X../.O./...\n"));
+
+        let (loaded_leaderboard, loaded_game) = deserialize_session(&content).unwrap();
+        assert_eq!(loaded_leaderboard, leaderboard);
+        assert_eq!(loaded_game, Some(grid));
+    }
+
+    #[test]
+    fn deserialize_session_should_reject_corrupted_lines() {
+        assert!(deserialize_session("player=three\n").is_err());
+        assert!(deserialize_session("record=alien:10\n").is_err());
+        assert!(deserialize_session("record=human:10:5\n").is_err());
+        assert!(deserialize_session("nonsense\n").is_err());
+    }
+
+    #[test]
+    fn load_session_should_return_none_without_save_file() {
+        let path = std::env::temp_dir().join("tictactoe_missing_save_file.txt");
+        assert!(load_session(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn save_session_should_write_file_that_load_session_reads() {
+        let path =
+            std::env::temp_dir().join(format!("tictactoe_save_test_{}.txt", std::process::id()));
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.record_game(Some(Player::Cpu), 60, None);
+
+        save_session(&path, &leaderboard, None).unwrap();
+        let loaded = load_session(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, Some((leaderboard, None)));
+    }
+}
@@ -0,0 +1,557 @@
+use super::cpu::{self, CpuMove, CpuProfile, GameRng, Personality};
+use super::game::{GameConfig, RuleVariant};
+use super::grid::{Grid, GridChecker, Marker, Player};
+
+use itertools::Itertools;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+const MAX_BODY_LENGTH: u64 = 64 * 1024;
+
+pub struct ServerGame {
+    config: GameConfig,
+    grid: Grid,
+    rng: GameRng,
+    moves: Vec<usize>,
+    last_cpu_move: Option<CpuMove>,
+}
+
+impl ServerGame {
+    fn new(config: GameConfig, seed: u64) -> ServerGame {
+        let mut game = ServerGame {
+            config,
+            grid: config.new_grid(),
+            rng: cpu::seeded_rng(seed),
+            moves: Vec::new(),
+            last_cpu_move: None,
+        };
+        game.play_cpu_if_due();
+        game
+    }
+
+    fn play_cpu_if_due(&mut self) {
+        if self.config.is_over(&self.grid) || self.config.player_to_move(&self.grid) != Player::Cpu
+        {
+            return;
+        }
+        let (grid, cpu_move) =
+            self.config
+                .make_cpu_move(&self.grid, Player::Cpu, &self.config.profile, &mut self.rng);
+        self.grid = grid;
+        self.moves
+            .push(self.grid.position_number(cpu_move.position));
+        self.last_cpu_move = Some(cpu_move);
+    }
+
+    fn status(&self) -> &'static str {
+        if !self.config.is_over(&self.grid) {
+            return "in_progress";
+        }
+        match self.config.winner(&self.grid) {
+            Some(Player::Human) => "human_won",
+            Some(Player::Cpu) => "cpu_won",
+            None => "tie",
+        }
+    }
+
+    fn cpu_move_view(&self) -> Value {
+        match &self.last_cpu_move {
+            Some(cpu_move) => json!({
+                "row": cpu_move.position.0,
+                "col": cpu_move.position.1,
+                "position": self.grid.position_number(cpu_move.position),
+                "rule": cpu_move.rule.to_string(),
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn view(&self, id: u64) -> Value {
+        let to_move = match self.config.is_over(&self.grid) {
+            true => Value::Null,
+            false => json!(player_name(self.config.player_to_move(&self.grid))),
+        };
+        json!({
+            "id": id,
+            "board": self.grid.to_notation(),
+            "size": self.grid.size,
+            "variant": self.config.variant.name(),
+            "human_marker": self.config.marker_of(Player::Human).glyph().to_string(),
+            "personality": format!("{:?}", self.config.profile.personality).to_lowercase(),
+            "strength": self.config.profile.strength(),
+            "status": self.status(),
+            "winner": self.config.winner(&self.grid).map(player_name),
+            "to_move": to_move,
+            "available": self.grid.extract_empty_positions().keys().sorted().collect::<Vec<_>>(),
+            "winning_line": self.grid.winning_line().unwrap_or_default(),
+            "moves": self.moves,
+            "last_cpu_move": self.cpu_move_view(),
+        })
+    }
+}
+
+fn player_name(player: Player) -> &'static str {
+    match player {
+        Player::Human => "human",
+        Player::Cpu => "cpu",
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CreateGameRequest {
+    size: Option<usize>,
+    marker: Option<String>,
+    first: Option<String>,
+    variant: Option<String>,
+    personality: Option<String>,
+    strength: Option<u8>,
+    seed: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MoveRequest {
+    position: Option<usize>,
+    row: Option<i32>,
+    col: Option<i32>,
+}
+
+type SharedGame = Arc<Mutex<ServerGame>>;
+type ApiError = (u16, String);
+type ApiResult = Result<(u16, Value), ApiError>;
+
+fn bad_request(message: &str) -> ApiError {
+    (400, message.to_owned())
+}
+
+fn parse_body<'a, T: Deserialize<'a> + Default>(body: &'a str) -> Result<T, ApiError> {
+    if body.trim().is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_str(body).map_err(|error| bad_request(&format!("invalid body: {}", error)))
+}
+
+fn build_config(request: &CreateGameRequest) -> Result<GameConfig, ApiError> {
+    let mut config = GameConfig::default();
+    if let Some(size) = request.size {
+        if !(3..=9).contains(&size) {
+            return Err(bad_request("size must be between 3 and 9"));
+        }
+        config.size = size;
+    }
+    if let Some(marker) = &request.marker {
+        config.human_marker = match marker.to_uppercase().as_str() {
+            "X" => Marker::X,
+            "O" => Marker::O,
+            _ => return Err(bad_request("marker must be X or O")),
+        };
+    }
+    if let Some(first) = &request.first {
+        config.first_player = match first.to_lowercase().as_str() {
+            "human" => Player::Human,
+            "cpu" => Player::Cpu,
+            _ => return Err(bad_request("first must be human or cpu")),
+        };
+    }
+    if let Some(variant) = &request.variant {
+        config.variant =
+            RuleVariant::from_name(variant).ok_or_else(|| bad_request("unknown variant"))?;
+        if !config.variant.is_grid_game() {
+            return Err(bad_request("this variant cannot be played over HTTP"));
+        }
+    }
+    let personality = match &request.personality {
+        Some(name) => {
+            Personality::from_name(name).ok_or_else(|| bad_request("unknown personality"))?
+        }
+        None => config.profile.personality,
+    };
+    let strength = request.strength.unwrap_or(config.profile.strength());
+    if strength > 100 {
+        return Err(bad_request("strength must be between 0 and 100"));
+    }
+    config.profile = CpuProfile::with_strength(personality, strength);
+    Ok(config)
+}
+
+#[derive(Default)]
+pub struct GameStore {
+    games: Mutex<HashMap<u64, SharedGame>>,
+    next_id: AtomicU64,
+}
+
+impl GameStore {
+    fn get(&self, id: &str) -> Result<(u64, SharedGame), ApiError> {
+        let not_found = || (404, format!("no game with id {}", id));
+        let id = id.parse::<u64>().map_err(|_| not_found())?;
+        let games = self.games.lock().unwrap();
+        games
+            .get(&id)
+            .cloned()
+            .map(|game| (id, game))
+            .ok_or_else(not_found)
+    }
+
+    fn create(&self, body: &str) -> ApiResult {
+        let request: CreateGameRequest = parse_body(body)?;
+        let config = build_config(&request)?;
+        let game = ServerGame::new(config, request.seed.unwrap_or_else(rand::random));
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let view = game.view(id);
+        self.games
+            .lock()
+            .unwrap()
+            .insert(id, Arc::new(Mutex::new(game)));
+        Ok((201, view))
+    }
+
+    fn list(&self) -> ApiResult {
+        let games = self.games.lock().unwrap();
+        let summaries = games
+            .iter()
+            .sorted_by_key(|(id, _)| **id)
+            .map(|(id, game)| json!({"id": id, "status": game.lock().unwrap().status()}))
+            .collect::<Vec<_>>();
+        Ok((200, json!({ "games": summaries })))
+    }
+
+    fn state(&self, id: &str) -> ApiResult {
+        let (id, game) = self.get(id)?;
+        let view = game.lock().unwrap().view(id);
+        Ok((200, view))
+    }
+
+    fn cpu_move(&self, id: &str) -> ApiResult {
+        let (_, game) = self.get(id)?;
+        let view = game.lock().unwrap().cpu_move_view();
+        Ok((200, view))
+    }
+
+    fn delete(&self, id: &str) -> ApiResult {
+        let (id, _) = self.get(id)?;
+        self.games.lock().unwrap().remove(&id);
+        Ok((200, json!({ "deleted": id })))
+    }
+
+    fn play(&self, id: &str, body: &str) -> ApiResult {
+        let request: MoveRequest = parse_body(body)?;
+        let (id, game) = self.get(id)?;
+        let mut game = game.lock().unwrap();
+        if game.config.is_over(&game.grid) {
+            return Err((409, "the game is over".to_owned()));
+        }
+
+        let position = match (request.position, request.row, request.col) {
+            (Some(number), None, None) => game.grid.extract_empty_positions().get(&number).cloned(),
+            (None, Some(row), Some(col)) => Some((row, col))
+                .filter(|position| game.grid.grid.get(position) == Some(&Marker::Null)),
+            _ => return Err(bad_request("expected either position or row and col")),
+        };
+        let position = position.ok_or_else(|| (409, "position is not available".to_owned()))?;
+
+        let marker = game.config.marker_of(Player::Human);
+        game.grid.insert(position, marker);
+        let number = game.grid.position_number(position);
+        game.moves.push(number);
+        game.last_cpu_move = None;
+        game.play_cpu_if_due();
+        Ok((200, game.view(id)))
+    }
+}
+
+pub fn handle_request(store: &GameStore, method: &str, url: &str, body: &str) -> (u16, String) {
+    let path = url.split('?').next().unwrap_or_default();
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    let result = match (method, segments.as_slice()) {
+        ("POST", ["games"]) => store.create(body),
+        ("GET", ["games"]) => store.list(),
+        ("GET", ["games", id]) => store.state(id),
+        ("DELETE", ["games", id]) => store.delete(id),
+        ("POST", ["games", id, "moves"]) => store.play(id, body),
+        ("GET", ["games", id, "cpu-move"]) => store.cpu_move(id),
+        (_, ["games"]) | (_, ["games", _]) | (_, ["games", _, _]) => {
+            Err((405, "method not allowed".to_owned()))
+        }
+        _ => Err((404, format!("no route for {}", path))),
+    };
+    match result {
+        Ok((status, value)) => (status, value.to_string()),
+        Err((status, message)) => (status, json!({ "error": message }).to_string()),
+    }
+}
+
+// Cross-origin headers are only sent for an origin given with --cors.
+fn serve_requests(server: &tiny_http::Server, store: &GameStore, cors: Option<&str>) {
+    while let Ok(mut request) = server.recv() {
+        let mut body = String::new();
+        let read = request
+            .as_reader()
+            .take(MAX_BODY_LENGTH)
+            .read_to_string(&mut body);
+        let (status, content) = match (request.method().as_str(), read) {
+            ("OPTIONS", _) => (204, String::new()),
+            (_, Err(_)) => (400, json!({"error": "body is not valid UTF-8"}).to_string()),
+            (method, Ok(_)) => handle_request(store, method, request.url(), &body),
+        };
+        let mut response = tiny_http::Response::from_string(content)
+            .with_status_code(status)
+            .with_header(header("Content-Type", "application/json"));
+        if let Some(origin) = cors {
+            response = response
+                .with_header(header("Access-Control-Allow-Origin", origin))
+                .with_header(header(
+                    "Access-Control-Allow-Methods",
+                    "GET, POST, DELETE, OPTIONS",
+                ))
+                .with_header(header("Access-Control-Allow-Headers", "Content-Type"));
+        }
+        let _ = request.respond(response);
+    }
+}
+
+fn header(name: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header::from_bytes(name, value).expect("header is valid")
+}
+
+pub struct ApiServer {
+    server: Arc<tiny_http::Server>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ApiServer {
+    pub fn start(
+        address: &str,
+        worker_count: usize,
+        cors: Option<String>,
+    ) -> io::Result<ApiServer> {
+        let server = tiny_http::Server::http(address)
+            .map(Arc::new)
+            .map_err(|error| io::Error::new(io::ErrorKind::AddrNotAvailable, error))?;
+        let store = Arc::new(GameStore::default());
+        let workers = (0..worker_count.max(1))
+            .map(|_| {
+                let server = Arc::clone(&server);
+                let store = Arc::clone(&store);
+                let cors = cors.clone();
+                thread::spawn(move || serve_requests(&server, &store, cors.as_deref()))
+            })
+            .collect();
+        Ok(ApiServer { server, workers })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    pub fn wait(self) {
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+    }
+
+    #[cfg(test)]
+    pub fn shutdown(self) {
+        for _ in &self.workers {
+            self.server.unblock();
+        }
+        self.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    fn call(store: &GameStore, method: &str, url: &str, body: &str) -> (u16, Value) {
+        let (status, content) = handle_request(store, method, url, body);
+        (status, serde_json::from_str(&content).unwrap())
+    }
+
+    #[test]
+    fn create_game_should_apply_requested_rules() {
+        let store = GameStore::default();
+        let (status, game) = call(
+            &store,
+            "POST",
+            "/games",
+            r#"{"size":4,"marker":"o","variant":"misere","personality":"chaotic","strength":30}"#,
+        );
+        assert_eq!(status, 201);
+        assert_eq!(game["id"], 1);
+        assert_eq!(game["size"], 4);
+        assert_eq!(game["human_marker"], "O");
+        assert_eq!(game["variant"], "misere");
+        assert_eq!(game["personality"], "chaotic");
+        assert_eq!(game["strength"], 30);
+        assert_eq!(game["status"], "in_progress");
+        assert_eq!(game["to_move"], "human");
+        assert_eq!(game["available"].as_array().unwrap().len(), 16);
+    }
+
+    #[test]
+    fn create_game_should_let_cpu_open_when_asked() {
+        let store = GameStore::default();
+        let (_, game) = call(&store, "POST", "/games", r#"{"first":"cpu","seed":3}"#);
+        assert_eq!(game["moves"].as_array().unwrap().len(), 1);
+        assert_eq!(game["to_move"], "human");
+        assert!(game["last_cpu_move"]["rule"].is_string());
+    }
+
+    #[test]
+    fn create_game_should_reject_invalid_rules() {
+        let store = GameStore::default();
+        for body in [
+            r#"{"size":12}"#,
+            r#"{"marker":"Z"}"#,
+            r#"{"strength":150}"#,
+            r#"{"variant":"gomoku"}"#,
+            r#"{"variant":"phantom"}"#,
+            r#"{"colour":"red"}"#,
+            "not json",
+        ] {
+            let (status, error) = call(&store, "POST", "/games", body);
+            assert_eq!(status, 400, "{}", body);
+            assert!(error["error"].is_string());
+        }
+    }
+
+    #[test]
+    fn submitting_a_move_should_return_the_cpu_reply() {
+        let store = GameStore::default();
+        call(&store, "POST", "/games", r#"{"strength":100,"seed":1}"#);
+
+        let (status, game) = call(&store, "POST", "/games/1/moves", r#"{"position":1}"#);
+        assert_eq!(status, 200);
+        assert_eq!(game["board"], "X../.O./...");
+        assert_eq!(game["moves"], json!([1, 5]));
+        assert_eq!(game["last_cpu_move"]["position"], 5);
+
+        let (_, cpu_move) = call(&store, "GET", "/games/1/cpu-move", "");
+        assert_eq!(cpu_move, game["last_cpu_move"]);
+
+        let (status, game) = call(&store, "POST", "/games/1/moves", r#"{"row":2,"col":2}"#);
+        assert_eq!(status, 200);
+        assert_eq!(game["moves"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn submitting_a_move_should_reject_taken_squares_and_finished_games() {
+        let store = GameStore::default();
+        call(&store, "POST", "/games", r#"{"strength":100,"seed":1}"#);
+
+        let (status, _) = call(&store, "POST", "/games/1/moves", r#"{"position":1}"#);
+        assert_eq!(status, 200);
+        let (status, _) = call(&store, "POST", "/games/1/moves", r#"{"position":5}"#);
+        assert_eq!(status, 409);
+        let (status, _) = call(&store, "POST", "/games/1/moves", r#"{"row":1}"#);
+        assert_eq!(status, 400);
+
+        let mut status_text = Value::Null;
+        for number in 1..=9 {
+            let (_, game) = call(
+                &store,
+                "POST",
+                "/games/1/moves",
+                &format!(r#"{{"position":{}}}"#, number),
+            );
+            if game["status"].is_string() {
+                status_text = game["status"].clone();
+            }
+        }
+        assert_ne!(status_text, "in_progress");
+        assert_ne!(status_text, "human_won");
+        let (status, error) = call(&store, "POST", "/games/1/moves", r#"{"position":1}"#);
+        assert_eq!(status, 409);
+        assert_eq!(error["error"], "the game is over");
+    }
+
+    #[test]
+    fn games_should_be_kept_apart_by_id() {
+        let store = GameStore::default();
+        call(&store, "POST", "/games", "");
+        call(&store, "POST", "/games", "");
+        call(&store, "POST", "/games/2/moves", r#"{"position":9}"#);
+
+        let (_, first) = call(&store, "GET", "/games/1", "");
+        let (_, second) = call(&store, "GET", "/games/2", "");
+        assert_eq!(first["moves"], json!([]));
+        assert_eq!(second["moves"].as_array().unwrap()[0], 9);
+
+        let (_, list) = call(&store, "GET", "/games", "");
+        assert_eq!(list["games"].as_array().unwrap().len(), 2);
+
+        assert_eq!(call(&store, "DELETE", "/games/1", "").0, 200);
+        assert_eq!(call(&store, "GET", "/games/1", "").0, 404);
+        assert_eq!(call(&store, "GET", "/games/abc", "").0, 404);
+        assert_eq!(call(&store, "PUT", "/games/2", "").0, 405);
+        assert_eq!(call(&store, "GET", "/leaderboard", "").0, 404);
+    }
+
+    #[test]
+    fn store_should_handle_concurrent_games() {
+        let store = Arc::new(GameStore::default());
+        let handles = (0..8)
+            .map(|_| {
+                let store = Arc::clone(&store);
+                thread::spawn(move || {
+                    let (_, game) = call(&store, "POST", "/games", "");
+                    let url = format!("/games/{}/moves", game["id"]);
+                    call(&store, "POST", &url, r#"{"position":5}"#).0
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 200);
+        }
+        let (_, list) = call(&store, "GET", "/games", "");
+        assert_eq!(list["games"].as_array().unwrap().len(), 8);
+    }
+
+    fn post_game(server: &ApiServer) -> String {
+        let body = r#"{"seed":5}"#;
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        write!(
+            stream,
+            "POST /games HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn api_server_should_answer_over_localhost() {
+        let server = ApiServer::start("127.0.0.1:0", 2, None).unwrap();
+        let response = post_game(&server);
+
+        assert!(response.starts_with("HTTP/1.1 201"));
+        assert!(!response.contains("Access-Control-Allow-Origin"));
+        let content = response.split("\r\n\r\n").nth(1).unwrap();
+        let game: Value = serde_json::from_str(content).unwrap();
+        assert_eq!(game["board"], ".../.../...");
+
+        server.shutdown();
+    }
+
+    #[test]
+    fn api_server_should_allow_the_configured_origin() {
+        let server =
+            ApiServer::start("127.0.0.1:0", 1, Some("http://localhost:3000".to_owned())).unwrap();
+        let response = post_game(&server);
+
+        assert!(response.contains("Access-Control-Allow-Origin: http://localhost:3000"));
+        server.shutdown();
+    }
+}
@@ -0,0 +1,470 @@
+//! Three men's morris: each side places as many pieces as the board is wide,
+//! then moves them one step along the board lines instead of adding more.
+
+use super::cpu::CpuProfile;
+use super::error::GameResult;
+use super::game::GameConfig;
+use super::grid::{Grid, GridChecker, Marker, Player};
+use super::input;
+use super::ui::{Highlights, Ui};
+
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+
+const SEARCH_DEPTH: usize = 6;
+pub const MAX_SLIDES: usize = 40;
+const REPETITIONS_FOR_DRAW: usize = 3;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SlideMove {
+    Place((i32, i32)),
+    Slide { from: (i32, i32), to: (i32, i32) },
+}
+
+impl SlideMove {
+    pub fn destination(&self) -> (i32, i32) {
+        match self {
+            SlideMove::Place(position) => *position,
+            SlideMove::Slide { to, .. } => *to,
+        }
+    }
+}
+
+pub fn pieces_per_side(grid: &Grid) -> usize {
+    grid.size
+}
+
+// Neighbours along a row, a column or one of the two main diagonals.
+pub fn is_adjacent(grid: &Grid, from: (i32, i32), to: (i32, i32)) -> bool {
+    let (d_row, d_col) = (to.0 - from.0, to.1 - from.1);
+    if from == to || d_row.abs() > 1 || d_col.abs() > 1 {
+        return false;
+    }
+    let last = grid.size as i32 - 1;
+    let on_diagonal = |(row, col): (i32, i32)| row == col;
+    let on_antidiagonal = |(row, col): (i32, i32)| row + col == last;
+    d_row == 0
+        || d_col == 0
+        || (d_row == d_col && on_diagonal(from) && on_diagonal(to))
+        || (d_row == -d_col && on_antidiagonal(from) && on_antidiagonal(to))
+}
+
+fn sorted_cells(grid: &Grid, marker: Marker) -> Vec<(i32, i32)> {
+    grid.grid
+        .iter()
+        .filter(|(_, cell)| **cell == marker)
+        .map(|(position, _)| *position)
+        .sorted()
+        .collect()
+}
+
+pub fn legal_moves(grid: &Grid, marker: Marker) -> Vec<SlideMove> {
+    let empty = sorted_cells(grid, Marker::Null);
+    if grid.count_markers(marker) < pieces_per_side(grid) {
+        return empty.into_iter().map(SlideMove::Place).collect();
+    }
+    sorted_cells(grid, marker)
+        .into_iter()
+        .flat_map(|from| {
+            empty
+                .iter()
+                .filter(move |to| is_adjacent(grid, from, **to))
+                .map(move |to| SlideMove::Slide { from, to: *to })
+        })
+        .collect()
+}
+
+pub fn apply_move(grid: &Grid, marker: Marker, slide_move: SlideMove) -> Grid {
+    let mut after = grid.clone();
+    if let SlideMove::Slide { from, .. } = slide_move {
+        after.insert(from, Marker::Null);
+    }
+    after.insert(slide_move.destination(), marker);
+    after
+}
+
+fn negamax(grid: &Grid, marker: Marker, depth: usize) -> i32 {
+    if grid.winning_marker().is_some() {
+        return -(10 + depth as i32);
+    }
+    if depth == 0 {
+        return 0;
+    }
+    legal_moves(grid, marker)
+        .into_iter()
+        .map(|slide_move| {
+            -negamax(
+                &apply_move(grid, marker, slide_move),
+                marker.opponent(),
+                depth - 1,
+            )
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+pub fn choose_sliding_move<R: Rng>(
+    grid: &Grid,
+    marker: Marker,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> Option<SlideMove> {
+    let moves = legal_moves(grid, marker);
+    if rng.gen_bool(profile.blunder_rate.clamp(0.0, 1.0)) {
+        return moves.choose(rng).copied();
+    }
+    let scored = moves
+        .into_iter()
+        .map(|slide_move| {
+            let after = apply_move(grid, marker, slide_move);
+            (
+                slide_move,
+                -negamax(&after, marker.opponent(), SEARCH_DEPTH - 1),
+            )
+        })
+        .collect::<Vec<_>>();
+    let best_score = scored.iter().map(|(_, score)| *score).max()?;
+    scored
+        .into_iter()
+        .filter(|(_, score)| *score == best_score)
+        .map(|(slide_move, _)| slide_move)
+        .collect::<Vec<_>>()
+        .choose(rng)
+        .copied()
+}
+
+fn prompt_for_cell<U, F>(
+    grid: &Grid,
+    ui: &mut U,
+    message: &str,
+    choices: &[(i32, i32)],
+    reject: F,
+) -> GameResult<(i32, i32)>
+where
+    U: Ui + ?Sized,
+    F: Fn(usize) -> String,
+{
+    let list_of_choices = choices
+        .iter()
+        .map(|position| grid.position_number(*position).to_string())
+        .join(", ");
+    loop {
+        let answer = ui.prompt(&format!("{}\n{}", message, list_of_choices))?;
+        ui.show_message(&format!("Candidate: {}", answer.trim()));
+        match input::parse_coordinates(&answer, grid.size) {
+            Ok(position) if choices.contains(&position) => return Ok(position),
+            Ok(position) => ui.show_message(&reject(grid.position_number(position))),
+            Err(error) => ui.show_message(&error.to_string()),
+        }
+    }
+}
+
+pub fn make_sliding_turn<U: Ui + ?Sized>(
+    grid: &Grid,
+    ui: &mut U,
+    last_move: Option<(i32, i32)>,
+    marker: Marker,
+) -> GameResult<SlideMove> {
+    let moves = legal_moves(grid, marker);
+    if let Some(SlideMove::Place(_)) = moves.first() {
+        return ui.choose_position(grid, last_move).map(SlideMove::Place);
+    }
+
+    let highlights = Highlights {
+        last_move,
+        ..Highlights::default()
+    };
+    ui.show_board(grid, &highlights);
+    let movable = moves
+        .iter()
+        .filter_map(|slide_move| match slide_move {
+            SlideMove::Slide { from, .. } => Some(*from),
+            SlideMove::Place(_) => None,
+        })
+        .dedup()
+        .collect::<Vec<_>>();
+    let from = prompt_for_cell(
+        grid,
+        ui,
+        "Please select one of your pieces to move:",
+        &movable,
+        |number| format!("Position {} is not one of your movable pieces.", number),
+    )?;
+    let targets = moves
+        .iter()
+        .filter_map(|slide_move| match slide_move {
+            SlideMove::Slide { from: start, to } if *start == from => Some(*to),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let to = prompt_for_cell(
+        grid,
+        ui,
+        &format!("Move the piece at {} to:", grid.position_number(from)),
+        &targets,
+        |number| format!("Position {} is not an empty neighbour.", number),
+    )?;
+    Ok(SlideMove::Slide { from, to })
+}
+
+fn describe(grid: &Grid, slide_move: SlideMove) -> String {
+    match slide_move {
+        SlideMove::Place(position) => {
+            format!("places a piece at {}", grid.position_number(position))
+        }
+        SlideMove::Slide { from, to } => format!(
+            "slides from {} to {}",
+            grid.position_number(from),
+            grid.position_number(to)
+        ),
+    }
+}
+
+// Draws by threefold repetition of a position with the same side to move,
+// or once the sliding phase runs past MAX_SLIDES moves.
+#[derive(Debug, Default)]
+pub struct DrawWatch {
+    seen: HashMap<String, usize>,
+    slides: usize,
+}
+
+impl DrawWatch {
+    pub fn check(&mut self, grid: &Grid, to_move: Marker) -> Option<String> {
+        let key = format!("{}:{}", grid.to_notation(), to_move.glyph());
+        let repetitions = self.seen.entry(key).or_default();
+        *repetitions += 1;
+        if *repetitions >= REPETITIONS_FOR_DRAW {
+            Some("The same position came up three times, the game is drawn.".to_owned())
+        } else if self.slides >= MAX_SLIDES {
+            Some(format!(
+                "No line after {} moves, the game is drawn.",
+                MAX_SLIDES
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn record(&mut self, slide_move: SlideMove) {
+        if let SlideMove::Slide { .. } = slide_move {
+            self.slides += 1;
+        }
+    }
+}
+
+pub fn play_sliding<U: Ui + ?Sized, R: Rng>(
+    config: &GameConfig,
+    ui: &mut U,
+    rng: &mut R,
+) -> GameResult<Option<Player>> {
+    let mut grid = config.new_grid();
+    ui.show_message(&format!(
+        "Starting a sliding game! After placing {} pieces each, move them to a neighbouring cell.",
+        pieces_per_side(&grid)
+    ));
+    let mut player = config.first_player;
+    let mut last_move = None;
+    let mut draws = DrawWatch::default();
+    let mut winner = None;
+
+    while grid.winning_marker().is_none() {
+        let marker = config.marker_of(player);
+        if let Some(reason) = draws.check(&grid, marker) {
+            ui.show_message(&reason);
+            break;
+        }
+        if legal_moves(&grid, marker).is_empty() {
+            ui.show_message(&format!(
+                "{} cannot move any piece and loses.",
+                match player {
+                    Player::Human => "You",
+                    Player::Cpu => "The CPU",
+                }
+            ));
+            winner = Some(player.opponent());
+            break;
+        }
+
+        let slide_move = match player {
+            Player::Human => make_sliding_turn(&grid, ui, last_move, marker)?,
+            Player::Cpu => {
+                let slide_move = choose_sliding_move(&grid, marker, &config.profile, rng)
+                    .expect("The CPU has legal moves");
+                ui.show_message(&format!("The CPU {}.", describe(&grid, slide_move)));
+                slide_move
+            }
+        };
+        draws.record(slide_move);
+        grid = apply_move(&grid, marker, slide_move);
+        last_move = Some(slide_move.destination());
+        player = player.opponent();
+    }
+
+    let highlights = Highlights {
+        cursor: None,
+        last_move,
+        winning_line: grid.winning_line().unwrap_or_default(),
+        fading: None,
+    };
+    ui.show_board(&grid, &highlights);
+    Ok(winner.or_else(|| config.winner(&grid)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::{seeded_rng, Personality};
+    use super::super::grid::{create_grid_of_size, from_array};
+    use super::*;
+    use crate::ui::RecordingUi;
+
+    #[test]
+    fn is_adjacent_should_follow_the_board_lines() {
+        let grid = from_array([[0; 3]; 3]);
+        assert!(is_adjacent(&grid, (0, 0), (0, 1)));
+        assert!(is_adjacent(&grid, (0, 0), (1, 1)));
+        assert!(is_adjacent(&grid, (2, 0), (1, 1)));
+        assert!(!is_adjacent(&grid, (0, 1), (1, 0)));
+        assert!(!is_adjacent(&grid, (0, 0), (0, 2)));
+        assert!(!is_adjacent(&grid, (1, 1), (1, 1)));
+        assert!(is_adjacent(&create_grid_of_size(4), (1, 2), (2, 1)));
+    }
+
+    #[test]
+    fn legal_moves_should_switch_from_placing_to_sliding() {
+        let placing = from_array([[1, 1, 0], [0, -1, 0], [0, 0, -1]]);
+        assert_eq!(legal_moves(&placing, Marker::X).len(), 5);
+        assert!(matches!(
+            legal_moves(&placing, Marker::X)[0],
+            SlideMove::Place(_)
+        ));
+
+        let sliding = from_array([[1, 1, -1], [0, -1, 0], [1, 0, -1]]);
+        assert_eq!(
+            legal_moves(&sliding, Marker::X),
+            vec![
+                SlideMove::Slide {
+                    from: (0, 0),
+                    to: (1, 0)
+                },
+                SlideMove::Slide {
+                    from: (2, 0),
+                    to: (1, 0)
+                },
+                SlideMove::Slide {
+                    from: (2, 0),
+                    to: (2, 1)
+                },
+            ]
+        );
+        let after = apply_move(
+            &sliding,
+            Marker::X,
+            SlideMove::Slide {
+                from: (2, 0),
+                to: (2, 1),
+            },
+        );
+        assert_eq!(after, from_array([[1, 1, -1], [0, -1, 0], [0, 1, -1]]));
+    }
+
+    #[test]
+    fn choose_sliding_move_should_win_and_block() {
+        let profile = CpuProfile::with_strength(Personality::Defensive, 100);
+        let grid = from_array([[1, 1, -1], [0, -1, 0], [1, 0, -1]]);
+        let winning = choose_sliding_move(&grid, Marker::O, &profile, &mut seeded_rng(1));
+        assert_eq!(
+            winning,
+            Some(SlideMove::Slide {
+                from: (1, 1),
+                to: (1, 2)
+            })
+        );
+
+        let grid = from_array([[1, 1, 0], [0, -1, 0], [0, 0, -1]]);
+        let blocking = choose_sliding_move(&grid, Marker::O, &profile, &mut seeded_rng(1));
+        assert_eq!(blocking, Some(SlideMove::Place((0, 2))));
+    }
+
+    #[test]
+    fn draw_watch_should_detect_repetition_and_the_move_cap() {
+        let mut draws = DrawWatch::default();
+        let grid = from_array([[1, 1, -1], [0, -1, 0], [1, 0, -1]]);
+        assert_eq!(draws.check(&grid, Marker::X), None);
+        assert_eq!(draws.check(&grid, Marker::O), None);
+        assert_eq!(draws.check(&grid, Marker::X), None);
+        assert_eq!(
+            draws.check(&grid, Marker::X),
+            Some("The same position came up three times, the game is drawn.".to_owned())
+        );
+
+        let mut draws = DrawWatch::default();
+        let slide = SlideMove::Slide {
+            from: (0, 0),
+            to: (1, 0),
+        };
+        for _ in 0..MAX_SLIDES {
+            draws.record(SlideMove::Place((0, 0)));
+            draws.record(slide);
+        }
+        assert_eq!(
+            draws.check(&grid, Marker::X),
+            Some("No line after 40 moves, the game is drawn.".to_owned())
+        );
+    }
+
+    #[test]
+    fn make_sliding_turn_should_ask_for_a_piece_and_a_neighbour() {
+        let grid = from_array([[1, 1, -1], [0, -1, 0], [1, 0, -1]]);
+        let mut ui = RecordingUi::new(&["2", "7", "5", "8"]);
+
+        let slide_move = make_sliding_turn(&grid, &mut ui, None, Marker::X).unwrap();
+
+        assert_eq!(
+            slide_move,
+            SlideMove::Slide {
+                from: (2, 0),
+                to: (2, 1)
+            }
+        );
+        assert_eq!(
+            ui.prompts(),
+            vec![
+                "Please select one of your pieces to move:\n1, 7",
+                "Please select one of your pieces to move:\n1, 7",
+                "Move the piece at 7 to:\n4, 8",
+                "Move the piece at 7 to:\n4, 8",
+            ]
+        );
+        assert_eq!(
+            ui.messages(),
+            vec![
+                "Candidate: 2",
+                "Position 2 is not one of your movable pieces.",
+                "Candidate: 7",
+                "Candidate: 5",
+                "Position 5 is not an empty neighbour.",
+                "Candidate: 8"
+            ]
+        );
+    }
+
+    #[test]
+    fn play_sliding_should_play_out_against_the_cpu() {
+        let config = GameConfig {
+            profile: CpuProfile::with_strength(Personality::Defensive, 100),
+            ..GameConfig::default()
+        };
+        let inputs = ["5", "1", "9", "1", "2", "2", "1"].repeat(30);
+        let mut ui = RecordingUi::new(&inputs);
+
+        let result = play_sliding(&config, &mut ui, &mut seeded_rng(2));
+
+        assert_ne!(result.unwrap(), Some(Player::Human));
+        assert!(ui
+            .messages()
+            .iter()
+            .any(|message| message.starts_with("The CPU places a piece at ")));
+    }
+}
@@ -0,0 +1,334 @@
+use super::clock;
+use super::error::{GameError, GameResult};
+use super::grid::{self, Grid, Marker};
+use super::ui::{ConsoleUi, Highlights, Ui};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    style::{StyledContent, Stylize},
+    terminal::{self, ClearType},
+};
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum CursorAction {
+    Move((i32, i32)),
+    Place,
+    Abort,
+    Ignore,
+}
+
+pub fn is_interactive_terminal() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+fn style_cell(marker: &Marker, glyph: String) -> StyledContent<String> {
+    match marker {
+        Marker::X => glyph.blue().bold(),
+        Marker::O => glyph.red().bold(),
+        Marker::Null | Marker::Blocked => glyph.dark_grey(),
+    }
+}
+
+pub fn render_styled(grid: &Grid, highlights: &Highlights) -> Vec<String> {
+    let size = grid.size as i32;
+    let separator = vec!["---"; grid.size].join("+");
+    let mut lines = Vec::new();
+    for row in 0..size {
+        let cells = (0..size)
+            .map(|col| {
+                let position = (row, col);
+                let marker = grid.grid.get(&position).unwrap_or(&Marker::Null);
+                let glyph = match marker {
+                    Marker::X => " X ".to_owned(),
+                    Marker::O => " O ".to_owned(),
+                    Marker::Null => " . ".to_owned(),
+                    Marker::Blocked => " # ".to_owned(),
+                };
+                let mut styled = style_cell(marker, glyph);
+                if highlights.winning_line.contains(&position) {
+                    styled = styled.on_green();
+                }
+                if highlights.last_move == Some(position) {
+                    styled = styled.underlined();
+                }
+                if highlights.fading == Some(position) {
+                    styled = styled.dim();
+                }
+                if highlights.cursor == Some(position) {
+                    styled = styled.reverse();
+                }
+                styled.to_string()
+            })
+            .collect::<Vec<_>>();
+        lines.push(cells.join("|"));
+        if row < size - 1 {
+            lines.push(separator.clone());
+        }
+    }
+    lines
+}
+
+fn interpret_key(key: KeyEvent, cursor: (i32, i32), size: usize) -> CursorAction {
+    let last = size as i32 - 1;
+    let (row, col) = cursor;
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => CursorAction::Abort,
+        KeyCode::Up | KeyCode::Char('k') => CursorAction::Move(((row - 1).max(0), col)),
+        KeyCode::Down | KeyCode::Char('j') => CursorAction::Move(((row + 1).min(last), col)),
+        KeyCode::Left | KeyCode::Char('h') => CursorAction::Move((row, (col - 1).max(0))),
+        KeyCode::Right | KeyCode::Char('l') => CursorAction::Move((row, (col + 1).min(last))),
+        KeyCode::Enter | KeyCode::Char(' ') => CursorAction::Place,
+        KeyCode::Esc | KeyCode::Char('q') => CursorAction::Abort,
+        _ => CursorAction::Ignore,
+    }
+}
+
+struct RawScreen;
+
+impl RawScreen {
+    fn enter() -> io::Result<RawScreen> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(RawScreen)
+    }
+}
+
+impl Drop for RawScreen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn draw(grid: &Grid, highlights: &Highlights, status: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    execute!(
+        stdout,
+        cursor::MoveTo(0, 0),
+        terminal::Clear(ClearType::All)
+    )?;
+    let mut lines = vec![
+        "Move with arrow keys or hjkl, place with Enter, quit with q".to_owned(),
+        String::new(),
+    ];
+    lines.extend(render_styled(grid, highlights));
+    lines.push(String::new());
+    lines.push(status.to_owned());
+    write!(stdout, "{}", lines.join("\r\n"))?;
+    stdout.flush()
+}
+
+pub fn select_position(
+    grid: &Grid,
+    last_move: Option<(i32, i32)>,
+    status: &str,
+    deadline: Option<Instant>,
+) -> io::Result<(i32, i32)> {
+    let middle = grid.size as i32 / 2;
+    let mut highlights = Highlights {
+        cursor: Some((middle, middle)),
+        last_move,
+        winning_line: Vec::new(),
+        fading: None,
+    };
+    let mut message = status.to_owned();
+    let mut drawn = None;
+
+    let _screen = RawScreen::enter()?;
+    loop {
+        let mut status = message.clone();
+        if let Some(deadline) = deadline {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "time is up"));
+            }
+            status = format!("{} [{} left]", status, clock::format_duration(left));
+        }
+        let frame = (highlights.clone(), status);
+        if drawn.as_ref() != Some(&frame) {
+            draw(grid, &frame.0, &frame.1)?;
+            drawn = Some(frame);
+        }
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let cursor = highlights.cursor.unwrap_or((middle, middle));
+        match interpret_key(key, cursor, grid.size) {
+            CursorAction::Move(new_cursor) => highlights.cursor = Some(new_cursor),
+            CursorAction::Place if grid.grid.get(&cursor) == Some(&Marker::Null) => {
+                return Ok(cursor)
+            }
+            CursorAction::Place => message = "This position is already taken".to_owned(),
+            CursorAction::Abort => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "Game aborted from the terminal UI",
+                ))
+            }
+            CursorAction::Ignore => (),
+        }
+    }
+}
+
+pub struct TerminalUi {
+    console: ConsoleUi,
+    status: String,
+    line_mode: bool,
+    deadline: Option<Instant>,
+}
+
+impl TerminalUi {
+    pub fn new() -> TerminalUi {
+        TerminalUi {
+            console: ConsoleUi::new(),
+            status: String::new(),
+            line_mode: false,
+            deadline: None,
+        }
+    }
+}
+
+impl Ui for TerminalUi {
+    fn show_message(&mut self, message: &str) {
+        self.console.show_message(message);
+        self.status = message.to_owned();
+    }
+
+    fn show_board(&mut self, grid: &Grid, highlights: &Highlights) {
+        println!("\n{}\n", render_styled(grid, highlights).join("\n"));
+    }
+
+    fn prompt(&mut self, message: &str) -> GameResult<String> {
+        self.console.prompt(message)
+    }
+
+    fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.console.set_deadline(deadline);
+        self.deadline = deadline;
+    }
+
+    fn choose_position(
+        &mut self,
+        grid: &Grid,
+        last_move: Option<(i32, i32)>,
+    ) -> GameResult<(i32, i32)> {
+        if self.line_mode {
+            return grid::prompt_for_position(grid, self);
+        }
+        match select_position(grid, last_move, &self.status, self.deadline) {
+            Ok(position) => Ok(position),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => Err(GameError::Interrupted),
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => Err(GameError::Timeout),
+            Err(error) => {
+                self.show_message(&format!(
+                    "Terminal UI unavailable ({}), using line mode.",
+                    error
+                ));
+                self.line_mode = true;
+                grid::prompt_for_position(grid, self)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::grid::from_array;
+    use super::*;
+
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn interpret_key_should_move_with_arrows_and_hjkl() {
+        assert_eq!(
+            interpret_key(press(KeyCode::Up), (1, 1), 3),
+            CursorAction::Move((0, 1))
+        );
+        assert_eq!(
+            interpret_key(press(KeyCode::Char('j')), (1, 1), 3),
+            CursorAction::Move((2, 1))
+        );
+        assert_eq!(
+            interpret_key(press(KeyCode::Char('h')), (1, 1), 3),
+            CursorAction::Move((1, 0))
+        );
+        assert_eq!(
+            interpret_key(press(KeyCode::Right), (1, 1), 3),
+            CursorAction::Move((1, 2))
+        );
+    }
+
+    #[test]
+    fn interpret_key_should_keep_cursor_inside_grid() {
+        assert_eq!(
+            interpret_key(press(KeyCode::Up), (0, 0), 3),
+            CursorAction::Move((0, 0))
+        );
+        assert_eq!(
+            interpret_key(press(KeyCode::Char('l')), (2, 2), 3),
+            CursorAction::Move((2, 2))
+        );
+    }
+
+    #[test]
+    fn interpret_key_should_place_and_abort() {
+        assert_eq!(
+            interpret_key(press(KeyCode::Enter), (0, 0), 3),
+            CursorAction::Place
+        );
+        assert_eq!(
+            interpret_key(press(KeyCode::Esc), (0, 0), 3),
+            CursorAction::Abort
+        );
+        assert_eq!(
+            interpret_key(
+                KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+                (0, 0),
+                3
+            ),
+            CursorAction::Abort
+        );
+        assert_eq!(
+            interpret_key(press(KeyCode::Char('z')), (0, 0), 3),
+            CursorAction::Ignore
+        );
+    }
+
+    #[test]
+    fn render_styled_should_color_markers_differently() {
+        let grid = from_array([[1, -1, 0], [0, 0, 0], [0, 0, 0]]);
+        let lines = render_styled(&grid, &Highlights::default());
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].contains(&" X ".blue().bold().to_string()));
+        assert!(lines[0].contains(&" O ".red().bold().to_string()));
+        assert_eq!(lines[1], "---+---+---");
+    }
+
+    #[test]
+    fn render_styled_should_highlight_cursor_last_move_winning_line_and_fading() {
+        let grid = from_array([[1, 1, 1], [-1, -1, 0], [0, 0, 0]]);
+        let highlights = Highlights {
+            cursor: Some((2, 2)),
+            last_move: Some((1, 1)),
+            winning_line: grid.winning_line().unwrap(),
+            fading: Some((1, 0)),
+        };
+        let lines = render_styled(&grid, &highlights);
+        assert!(lines[0].contains(&" X ".blue().bold().on_green().to_string()));
+        assert!(lines[2].contains(&" O ".red().bold().underlined().to_string()));
+        assert!(lines[2].starts_with(&" O ".red().bold().dim().to_string()));
+        assert!(lines[4].contains(&" . ".dark_grey().reverse().to_string()));
+    }
+}
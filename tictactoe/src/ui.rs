@@ -0,0 +1,298 @@
+use super::error::{GameError, GameResult};
+use super::grid::{self, Grid};
+
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+pub fn install_interrupt_handler() {
+    let _ = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst));
+}
+
+fn take_interrupt() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Highlights {
+    pub cursor: Option<(i32, i32)>,
+    pub last_move: Option<(i32, i32)>,
+    pub winning_line: Vec<(i32, i32)>,
+    // A marker that leaves the board on the next move.
+    pub fading: Option<(i32, i32)>,
+}
+
+pub trait Ui {
+    fn show_message(&mut self, message: &str);
+    fn show_board(&mut self, grid: &Grid, highlights: &Highlights);
+    fn prompt(&mut self, message: &str) -> GameResult<String>;
+
+    // Prompts after the deadline give up with GameError::Timeout.
+    fn set_deadline(&mut self, _deadline: Option<Instant>) {}
+
+    fn choose_position(
+        &mut self,
+        grid: &Grid,
+        _last_move: Option<(i32, i32)>,
+    ) -> GameResult<(i32, i32)> {
+        grid::prompt_for_position(grid, self)
+    }
+}
+
+// Text boards have no styling, only the fading marker stands out.
+fn render_plain(grid: &Grid, highlights: &Highlights) -> String {
+    match highlights.fading {
+        Some(position) => grid.render_fading(position),
+        None => grid.render(),
+    }
+}
+
+// Stdin is read on a helper thread, one line per request, so that a prompt
+// can give up on Ctrl-C without leaving a read racing the terminal UI. Each
+// request carries a generation: a prompt that timed out bumps it, so the
+// line typed too late is dropped instead of answering the next prompt.
+struct LineReader {
+    requests: Sender<u64>,
+    lines: Receiver<(u64, io::Result<String>)>,
+    generation: u64,
+    pending: bool,
+}
+
+impl LineReader {
+    fn spawn() -> LineReader {
+        LineReader::spawn_with(|buffer| io::stdin().read_line(buffer))
+    }
+
+    fn spawn_with<F>(mut read: F) -> LineReader
+    where
+        F: FnMut(&mut String) -> io::Result<usize> + Send + 'static,
+    {
+        let (request_sender, request_receiver) = mpsc::channel::<u64>();
+        let (line_sender, line_receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for generation in request_receiver {
+                let mut buffer = String::new();
+                let result = read(&mut buffer).map(|_| buffer);
+                let finished = !matches!(&result, Ok(line) if !line.is_empty());
+                if line_sender.send((generation, result)).is_err() || finished {
+                    break;
+                }
+            }
+        });
+        LineReader {
+            requests: request_sender,
+            lines: line_receiver,
+            generation: 0,
+            pending: false,
+        }
+    }
+
+    fn read_line(&mut self, deadline: Option<Instant>) -> GameResult<String> {
+        if !self.pending {
+            self.requests
+                .send(self.generation)
+                .map_err(|_| GameError::EndOfInput)?;
+            self.pending = true;
+        }
+        loop {
+            if take_interrupt() {
+                return Err(GameError::Interrupted);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                self.generation += 1;
+                self.pending = false;
+                return Err(GameError::Timeout);
+            }
+            match self.lines.recv_timeout(Duration::from_millis(50)) {
+                Ok((generation, _)) if generation != self.generation => continue,
+                Ok((_, Ok(line))) if line.is_empty() => return Err(GameError::EndOfInput),
+                Ok((_, result)) => {
+                    self.pending = false;
+                    return Ok(result?);
+                }
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return Err(GameError::EndOfInput),
+            }
+        }
+    }
+}
+
+pub struct ConsoleUi {
+    reader: LineReader,
+    deadline: Option<Instant>,
+}
+
+impl ConsoleUi {
+    pub fn new() -> ConsoleUi {
+        ConsoleUi {
+            reader: LineReader::spawn(),
+            deadline: None,
+        }
+    }
+}
+
+impl Ui for ConsoleUi {
+    fn show_message(&mut self, message: &str) {
+        println!("{}", message);
+    }
+
+    fn show_board(&mut self, grid: &Grid, highlights: &Highlights) {
+        println!("\n{}\n", render_plain(grid, highlights));
+    }
+
+    fn prompt(&mut self, message: &str) -> GameResult<String> {
+        println!("{}", message);
+        self.reader.read_line(self.deadline)
+    }
+
+    fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiEvent {
+    Message(String),
+    Board(String),
+    Prompt(String),
+}
+
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct RecordingUi {
+    inputs: VecDeque<String>,
+    deadline: Option<Instant>,
+    pub events: Vec<UiEvent>,
+}
+
+#[cfg(test)]
+impl RecordingUi {
+    pub fn new(inputs: &[&str]) -> RecordingUi {
+        RecordingUi {
+            inputs: inputs.iter().map(|input| input.to_string()).collect(),
+            deadline: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn messages(&self) -> Vec<&str> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                UiEvent::Message(message) => Some(message.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn boards(&self) -> Vec<&str> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                UiEvent::Board(board) => Some(board.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn prompts(&self) -> Vec<&str> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                UiEvent::Prompt(message) => Some(message.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl Ui for RecordingUi {
+    fn show_message(&mut self, message: &str) {
+        self.events.push(UiEvent::Message(message.to_owned()));
+    }
+
+    fn show_board(&mut self, grid: &Grid, highlights: &Highlights) {
+        self.events
+            .push(UiEvent::Board(render_plain(grid, highlights)));
+    }
+
+    fn prompt(&mut self, message: &str) -> GameResult<String> {
+        self.events.push(UiEvent::Prompt(message.to_owned()));
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(GameError::Timeout);
+        }
+        self.inputs.pop_front().ok_or(GameError::EndOfInput)
+    }
+
+    fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_ui_should_replay_inputs_and_record_events() {
+        let mut ui = RecordingUi::new(&["first", "second"]);
+        ui.show_message("hello");
+        assert_eq!(ui.prompt("question 1").unwrap(), "first");
+        assert_eq!(ui.prompt("question 2").unwrap(), "second");
+        assert!(matches!(
+            ui.prompt("question 3"),
+            Err(GameError::EndOfInput)
+        ));
+        ui.show_board(&grid::create_grid(), &Highlights::default());
+
+        assert_eq!(ui.messages(), vec!["hello"]);
+        assert_eq!(ui.prompts(), vec!["question 1", "question 2", "question 3"]);
+        assert_eq!(
+            ui.events.last(),
+            Some(&UiEvent::Board(grid::create_grid().render()))
+        );
+    }
+
+    #[test]
+    fn line_reader_should_drop_the_answer_to_a_timed_out_prompt() {
+        let (typed, lines) = mpsc::channel::<&str>();
+        let mut reader = LineReader::spawn_with(move |buffer| {
+            let line = lines.recv().unwrap_or_default();
+            buffer.push_str(line);
+            Ok(line.len())
+        });
+
+        let result = reader.read_line(Some(Instant::now() + Duration::from_millis(20)));
+        assert!(matches!(result, Err(GameError::Timeout)));
+
+        typed.send("late\n").unwrap();
+        typed.send("fresh\n").unwrap();
+        assert_eq!(reader.read_line(None).unwrap(), "fresh\n");
+    }
+
+    #[test]
+    fn default_choose_position_should_prompt_until_valid() {
+        let mut ui = RecordingUi::new(&["42", "5"]);
+        let position = ui.choose_position(&grid::create_grid(), None).unwrap();
+        assert_eq!(position, (1, 1));
+        assert_eq!(ui.prompts().len(), 2);
+    }
+
+    #[test]
+    fn default_choose_position_should_stop_when_input_runs_out() {
+        let mut ui = RecordingUi::new(&["42"]);
+        let result = ui.choose_position(&grid::create_grid(), None);
+        assert!(matches!(result, Err(GameError::EndOfInput)));
+    }
+}
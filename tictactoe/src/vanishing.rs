@@ -0,0 +1,254 @@
+//! Disappearing markers: each side keeps at most three markers on the board,
+//! placing a fourth removes that side's oldest one, so a game cannot end
+//! with a full board. It goes on until someone completes a line.
+
+use super::cpu::CpuProfile;
+use super::error::GameResult;
+use super::game::GameConfig;
+use super::grid::{self, Grid, GridChecker, Marker, Player};
+use super::ui::{Highlights, Ui};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+pub const MAX_MARKERS: usize = 3;
+const SEARCH_DEPTH: usize = 6;
+
+// The marker that `marker` loses with its next placement, if any.
+pub fn next_to_vanish(grid: &Grid, marker: Marker) -> Option<(i32, i32)> {
+    if grid.count_markers(marker) < MAX_MARKERS {
+        return None;
+    }
+    grid.oldest(marker)
+}
+
+pub fn apply_vanishing_move(grid: &Grid, marker: Marker, position: (i32, i32)) -> Grid {
+    let mut after = grid.clone();
+    if let Some(oldest) = next_to_vanish(grid, marker) {
+        after.remove(oldest);
+    }
+    after.place_tracked(position, marker);
+    after
+}
+
+fn empty_cells(grid: &Grid) -> Vec<(i32, i32)> {
+    let mut cells = grid
+        .extract_empty_positions()
+        .into_values()
+        .collect::<Vec<_>>();
+    cells.sort();
+    cells
+}
+
+// Searches through the removals too, so a line that is about to lose its
+// oldest marker is not counted as a threat.
+fn negamax(grid: &Grid, marker: Marker, depth: usize) -> i32 {
+    if grid.winning_marker().is_some() {
+        return -(10 + depth as i32);
+    }
+    if depth == 0 {
+        return 0;
+    }
+    empty_cells(grid)
+        .into_iter()
+        .map(|position| {
+            -negamax(
+                &apply_vanishing_move(grid, marker, position),
+                marker.opponent(),
+                depth - 1,
+            )
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+pub fn choose_vanishing_move<R: Rng>(
+    grid: &Grid,
+    marker: Marker,
+    profile: &CpuProfile,
+    rng: &mut R,
+) -> (i32, i32) {
+    let cells = empty_cells(grid);
+    if rng.gen_bool(profile.blunder_rate.clamp(0.0, 1.0)) {
+        return *cells
+            .choose(rng)
+            .expect("A vanishing board always has room");
+    }
+    let scored = cells
+        .into_iter()
+        .map(|position| {
+            let after = apply_vanishing_move(grid, marker, position);
+            (
+                position,
+                -negamax(&after, marker.opponent(), SEARCH_DEPTH - 1),
+            )
+        })
+        .collect::<Vec<_>>();
+    let best_score = scored.iter().map(|(_, score)| *score).max().unwrap_or(0);
+    let best = scored
+        .into_iter()
+        .filter(|(_, score)| *score == best_score)
+        .map(|(position, _)| position)
+        .collect::<Vec<_>>();
+    *best.choose(rng).expect("A vanishing board always has room")
+}
+
+pub fn play_vanishing<U: Ui + ?Sized, R: Rng>(
+    config: &GameConfig,
+    ui: &mut U,
+    rng: &mut R,
+) -> GameResult<Option<Player>> {
+    ui.show_message(&format!(
+        "Starting a vanishing game! Each side keeps {} markers, a new one removes your oldest.",
+        MAX_MARKERS
+    ));
+    let mut grid = config.new_grid();
+    let mut player = config.first_player;
+    let mut last_move = None;
+
+    loop {
+        let marker = config.marker_of(player);
+        let highlights = Highlights {
+            last_move,
+            fading: next_to_vanish(&grid, marker),
+            ..Highlights::default()
+        };
+        let position = match player {
+            Player::Human => grid::prompt_for_position_showing(&grid, ui, &highlights)?,
+            Player::Cpu => {
+                let position = choose_vanishing_move(&grid, marker, &config.profile, rng);
+                ui.show_message(&format!(
+                    "The CPU places {} at {}.",
+                    marker.glyph(),
+                    grid.position_number(position)
+                ));
+                position
+            }
+        };
+        if let Some(oldest) = highlights.fading {
+            ui.show_message(&format!(
+                "The {} at {} vanishes.",
+                marker.glyph(),
+                grid.position_number(oldest)
+            ));
+        }
+        grid = apply_vanishing_move(&grid, marker, position);
+        last_move = Some(position);
+        if let Some(winning_line) = grid.winning_line() {
+            let highlights = Highlights {
+                last_move,
+                winning_line,
+                ..Highlights::default()
+            };
+            ui.show_board(&grid, &highlights);
+            return Ok(config.winner(&grid));
+        }
+        player = player.opponent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cpu::{seeded_rng, Personality};
+    use super::*;
+    use crate::ui::RecordingUi;
+
+    fn tracked(moves: &[((i32, i32), Marker)]) -> Grid {
+        moves
+            .iter()
+            .fold(grid::create_grid_of_size(3), |grid, (position, marker)| {
+                apply_vanishing_move(&grid, *marker, *position)
+            })
+    }
+
+    fn strong() -> CpuProfile {
+        CpuProfile::with_strength(Personality::Defensive, 100)
+    }
+
+    #[test]
+    fn apply_vanishing_move_should_remove_the_oldest_marker_of_the_mover() {
+        let grid = tracked(&[
+            ((0, 0), Marker::X),
+            ((1, 1), Marker::O),
+            ((0, 1), Marker::X),
+            ((2, 2), Marker::O),
+            ((2, 0), Marker::X),
+        ]);
+        assert_eq!(next_to_vanish(&grid, Marker::X), Some((0, 0)));
+        assert_eq!(next_to_vanish(&grid, Marker::O), None);
+
+        let after = apply_vanishing_move(&grid, Marker::X, (1, 2));
+        assert_eq!(after.grid.get(&(0, 0)), Some(&Marker::Null));
+        assert_eq!(after.count_markers(Marker::X), MAX_MARKERS);
+        assert_eq!(next_to_vanish(&after, Marker::X), Some((0, 1)));
+    }
+
+    #[test]
+    fn apply_vanishing_move_should_not_count_the_vanished_marker_in_a_line() {
+        // X's oldest marker leaves the top row as the third one lands.
+        let grid = tracked(&[
+            ((0, 0), Marker::X),
+            ((1, 0), Marker::O),
+            ((0, 1), Marker::X),
+            ((1, 1), Marker::O),
+            ((2, 2), Marker::X),
+            ((2, 0), Marker::O),
+        ]);
+        let grid = apply_vanishing_move(&grid, Marker::X, (0, 2));
+        assert_eq!(grid.winning_marker(), None);
+        assert_eq!(grid.to_notation(), ".XX/OO./O.X");
+    }
+
+    #[test]
+    fn choose_vanishing_move_should_see_through_lines_that_lose_a_marker() {
+        // Completing the top row or the diagonal would remove the X at 1,
+        // only the middle column really wins.
+        let grid = tracked(&[
+            ((0, 0), Marker::X),
+            ((1, 0), Marker::O),
+            ((1, 1), Marker::X),
+            ((2, 0), Marker::O),
+            ((0, 1), Marker::X),
+            ((1, 2), Marker::O),
+        ]);
+        let position = choose_vanishing_move(&grid, Marker::X, &strong(), &mut seeded_rng(1));
+        assert_eq!(position, (2, 1));
+    }
+
+    #[test]
+    fn choose_vanishing_move_should_block_a_lasting_threat() {
+        let grid = tracked(&[
+            ((1, 0), Marker::O),
+            ((2, 2), Marker::X),
+            ((1, 1), Marker::O),
+        ]);
+        let position = choose_vanishing_move(&grid, Marker::X, &strong(), &mut seeded_rng(1));
+        assert_eq!(position, (1, 2));
+    }
+
+    #[test]
+    fn play_vanishing_should_mark_the_marker_about_to_vanish() {
+        let config = GameConfig::default();
+        let mut ui = RecordingUi::new(&["1", "2", "3", "4", "5", "6", "7", "8", "9"]);
+
+        play_vanishing(&config, &mut ui, &mut seeded_rng(3)).unwrap();
+
+        assert!(ui.boards().iter().any(|board| board.starts_with(" x |")));
+        assert!(ui.messages().contains(&"The X at 1 vanishes."));
+    }
+
+    #[test]
+    fn play_vanishing_should_end_with_a_line() {
+        let config = GameConfig {
+            first_player: Player::Cpu,
+            profile: strong(),
+            ..GameConfig::default()
+        };
+        let inputs = ["1", "2", "3", "4", "5", "6", "7", "8", "9"].repeat(20);
+        let mut ui = RecordingUi::new(&inputs);
+
+        let winner = play_vanishing(&config, &mut ui, &mut seeded_rng(4)).unwrap();
+
+        assert_eq!(winner, Some(Player::Cpu));
+    }
+}